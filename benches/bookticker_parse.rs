@@ -0,0 +1,28 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use dynamo_rust::bookticker_stream::bookticker::{BestPrices, StreamBookTicker};
+use dynamo_rust::bookticker_stream::borrowed::decode_book_ticker;
+
+const FRAME: &str = r#"{"stream":"btcusdt@bookTicker","data":{"e":"bookTicker","u":5863012345678,"s":"BTCUSDT","b":"67234.50","B":"3.214","a":"67234.60","A":"0.875","T":1718000000123,"E":1718000000125}}"#;
+
+/// The original path: owned strings, then every price parsed again.
+fn owned(c: &mut Criterion) {
+    c.bench_function("bookticker owned serde_json", |b| {
+        b.iter(|| {
+            let ticker: StreamBookTicker = serde_json::from_str(black_box(FRAME)).unwrap();
+            BestPrices::from_ticker(&ticker.data, 0).unwrap()
+        })
+    });
+}
+
+fn borrowed(c: &mut Criterion) {
+    let mut buffer = Vec::new();
+    c.bench_function("bookticker borrowed", |b| {
+        b.iter(|| {
+            let frame = decode_book_ticker(black_box(FRAME), &mut buffer).unwrap();
+            frame.data.quote(0).unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, owned, borrowed);
+criterion_main!(benches);
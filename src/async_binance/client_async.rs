@@ -1,5 +1,5 @@
 use crate::async_binance::errors::CustomError;
-use crate::async_binance::models::{ExchangeInfo, ListenKey};
+use crate::async_binance::models::{DepthSnapshot, ExchangeInfo, ListenKey, ServerTime};
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, USER_AGENT};
 use ring::hmac;
 use std::time::Duration;
@@ -216,6 +216,20 @@ impl AsyncBinanceClient {
         }
     }
 
+    pub async fn get_server_time(&self) -> Result<u64, CustomError> {
+        let response: ServerTime = self.get("time", None).await?;
+        Ok(response.serverTime)
+    }
+
+    pub async fn get_depth_snapshot(
+        &self,
+        symbol: &str,
+        limit: u32,
+    ) -> Result<DepthSnapshot, CustomError> {
+        let request = format!("symbol={}&limit={}", symbol, limit);
+        self.get("depth", Some(&request)).await
+    }
+
     pub async fn get_available_coins_name(&self) -> Vec<String> {
         // if no exchange info then panic
         let exchange_info = self.get_exchange_info().await.unwrap();
@@ -6,6 +6,23 @@ pub struct ListenKey {
     pub listenKey: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+pub struct ServerTime {
+    pub serverTime: u64,
+}
+
+/// REST order book snapshot; each level is `[price, quantity]`.
+#[derive(Debug, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+pub struct DepthSnapshot {
+    pub lastUpdateId: u64,
+    pub E: u64,
+    pub T: u64,
+    pub bids: Vec<(String, String)>,
+    pub asks: Vec<(String, String)>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[allow(non_snake_case)]
 pub struct ExchangeInfo {
@@ -0,0 +1,213 @@
+use crate::aws_resources::write_rate::{is_throttling_error, WriteError, WritePriority, WriteRate};
+use aws_sdk_dynamodb::config::http::HttpResponse;
+use aws_sdk_dynamodb::error::SdkError;
+use aws_sdk_dynamodb::types::{AttributeValue, PutRequest, WriteRequest};
+use std::collections::HashMap;
+use tokio::time::{self, Duration};
+use tracing::info;
+
+/// DynamoDB rejects `BatchWriteItem` requests with more than 25 items.
+pub const MAX_BATCH_WRITE_ITEMS: usize = 25;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BatchRetryConfig {
+    pub max_retries: u32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for BatchRetryConfig {
+    fn default() -> Self {
+        BatchRetryConfig {
+            max_retries: 8,
+            base_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl BatchRetryConfig {
+    pub fn backoff_delay(&self, attempt: u32) -> Duration {
+        self.base_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BatchWriteOutcome {
+    pub written: u64,
+    /// Items sent again after being returned in `UnprocessedItems`.
+    pub retried: u64,
+    /// Items given up on after `max_retries`.
+    pub failed: u64,
+    /// Items never sent because `rate` shed them.
+    pub shed: u64,
+}
+
+pub fn put_requests(
+    items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+) -> Vec<WriteRequest> {
+    items
+        .into_iter()
+        .filter_map(
+            |item| match PutRequest::builder().set_item(Some(item)).build() {
+                Ok(put_request) => Some(WriteRequest::builder().put_request(put_request).build()),
+                Err(e) => {
+                    info!("Unable to build put request: {}", e);
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+/// Writes up to `MAX_BATCH_WRITE_ITEMS` requests, retrying `UnprocessedItems`
+/// and throttled or transient failures with exponential backoff; any other
+/// failure fails the whole batch at once. The first attempt may be shed by
+/// `rate`; retries wait for capacity. Every attempt first takes its
+/// items from `rate`, and reports accepted and throttled items back to it.
+pub async fn batch_write_with_retry(
+    client: &aws_sdk_dynamodb::Client,
+    table_name: &str,
+    mut pending: Vec<WriteRequest>,
+    retry: &BatchRetryConfig,
+    rate: &WriteRate,
+    priority: WritePriority,
+) -> BatchWriteOutcome {
+    let mut outcome = BatchWriteOutcome::default();
+    let mut attempt: u32 = 0;
+    while !pending.is_empty() {
+        if attempt == 0 {
+            if !rate.acquire(pending.len(), priority).await {
+                outcome.shed += pending.len() as u64;
+                break;
+            }
+        } else {
+            rate.acquire_retry(pending.len(), priority).await;
+        }
+        let sent = pending.len() as u64;
+        let result = client
+            .batch_write_item()
+            .request_items(table_name, pending.clone())
+            .send()
+            .await;
+        match result {
+            Ok(output) => {
+                let unprocessed = output
+                    .unprocessed_items
+                    .and_then(|mut items| items.remove(table_name))
+                    .unwrap_or_default();
+                let written = sent - unprocessed.len() as u64;
+                outcome.written += written;
+                outcome.retried += unprocessed.len() as u64;
+                rate.record_success(written);
+                // DynamoDB returns items it lacked capacity for unprocessed.
+                if !unprocessed.is_empty() {
+                    rate.record_throttled(unprocessed.len() as u64);
+                }
+                pending = unprocessed;
+            }
+            Err(e) => {
+                let transient = is_transient_error(&e);
+                let error = aws_sdk_dynamodb::Error::from(e);
+                let throttled = is_throttling_error(&error);
+                if throttled {
+                    rate.record_throttled(sent);
+                }
+                info!("Failed to batch write to {}: {:?}", table_name, error);
+                // Sending the same request again cannot fix it.
+                if !(throttled || transient) {
+                    outcome.failed += sent;
+                    rate.record_failed(sent);
+                    break;
+                }
+            }
+        }
+        if pending.is_empty() {
+            break;
+        }
+        if attempt >= retry.max_retries {
+            info!(
+                "Giving up on {} items for {} after {} retries",
+                pending.len(),
+                table_name,
+                attempt
+            );
+            outcome.failed += pending.len() as u64;
+            rate.record_failed(pending.len() as u64);
+            break;
+        }
+        time::sleep(retry.backoff_delay(attempt)).await;
+        attempt += 1;
+    }
+    outcome
+}
+
+/// Whether a failed call may succeed if sent again: it timed out, never
+/// reached DynamoDB, or DynamoDB failed on its side.
+pub fn is_transient_error<E>(error: &SdkError<E, HttpResponse>) -> bool {
+    match error {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+            true
+        }
+        SdkError::ServiceError(error) => error.raw().status().is_server_error(),
+        _ => false,
+    }
+}
+
+/// Writes a single item, retrying throttled and transient failures with
+/// exponential backoff. Other errors are returned at once; an item still
+/// failing after `max_retries` is counted as failed by `rate`.
+pub async fn put_item_with_retry(
+    client: &aws_sdk_dynamodb::Client,
+    table_name: &str,
+    item: HashMap<String, AttributeValue>,
+    retry: &BatchRetryConfig,
+    rate: &WriteRate,
+    priority: WritePriority,
+) -> Result<(), WriteError> {
+    let mut attempt: u32 = 0;
+    loop {
+        if attempt == 0 {
+            if !rate.acquire(1, priority).await {
+                return Err(WriteError::Shed);
+            }
+        } else {
+            rate.acquire_retry(1, priority).await;
+        }
+        let result = client
+            .put_item()
+            .table_name(table_name)
+            .set_item(Some(item.clone()))
+            .send()
+            .await;
+        let e = match result {
+            Ok(_) => {
+                rate.record_success(1);
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        let transient = is_transient_error(&e);
+        let error = WriteError::from(aws_sdk_dynamodb::Error::from(e));
+        let throttled = matches!(error, WriteError::Throttled(_));
+        if throttled {
+            rate.record_throttled(1);
+        }
+        if !(throttled || transient) {
+            return Err(error);
+        }
+        if attempt >= retry.max_retries {
+            info!(
+                "Giving up on a write to {} after {} retries: {}",
+                table_name, attempt, error
+            );
+            rate.record_failed(1);
+            return Err(error);
+        }
+        info!("Write to {} failed, retrying: {}", table_name, error);
+        time::sleep(retry.backoff_delay(attempt)).await;
+        attempt += 1;
+    }
+}
@@ -1,4 +1,5 @@
 use aws_sdk_dynamodb as dynamodb;
+use aws_sdk_dynamodbstreams as dynamodbstreams;
 use aws_sdk_ssm as ssm;
 
 pub async fn get_config() -> aws_config::SdkConfig {
@@ -11,6 +12,12 @@ pub async fn get_ddb_client() -> Result<dynamodb::Client, dynamodb::Error> {
     Ok(ddb_client)
 }
 
+pub async fn get_ddb_streams_client() -> Result<dynamodbstreams::Client, dynamodbstreams::Error> {
+    let config = get_config().await;
+    let streams_client = dynamodbstreams::Client::new(&config);
+    Ok(streams_client)
+}
+
 pub async fn get_ssm_client() -> Result<ssm::Client, ssm::Error> {
     let config = get_config().await;
     let ssm_client = ssm::Client::new(&config);
@@ -0,0 +1,358 @@
+use aws_sdk_dynamodb::error::BuildError;
+use aws_sdk_dynamodb::types::{
+    AttributeDefinition, BillingMode, KeySchemaElement, KeyType, ProvisionedThroughput,
+    ScalarAttributeType, StreamSpecification, StreamViewType, TableDescription, TableStatus,
+    TimeToLiveSpecification, TimeToLiveStatus,
+};
+use thiserror::Error;
+use tokio::time::{self, Duration};
+use tracing::info;
+
+#[derive(Error, Debug)]
+pub enum TableError {
+    #[error(transparent)]
+    DynamoError(Box<aws_sdk_dynamodb::Error>),
+    #[error("table {table} does not match its configuration: {reason}")]
+    SchemaMismatch { table: String, reason: String },
+    #[error("table {table} expires items by {found}, expected {expected}")]
+    TtlMismatch {
+        table: String,
+        expected: String,
+        found: String,
+    },
+    #[error("table {0} did not become active in time")]
+    NotActive(String),
+    #[error("TTL on table {0} is still being disabled; it can be enabled again once that finishes, which may take up to an hour")]
+    TtlDisabling(String),
+}
+
+impl From<aws_sdk_dynamodb::Error> for TableError {
+    fn from(error: aws_sdk_dynamodb::Error) -> Self {
+        TableError::DynamoError(Box::new(error))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAttributeType {
+    String,
+    Number,
+}
+
+impl KeyAttributeType {
+    fn scalar_type(&self) -> ScalarAttributeType {
+        match self {
+            KeyAttributeType::String => ScalarAttributeType::S,
+            KeyAttributeType::Number => ScalarAttributeType::N,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableBillingMode {
+    PayPerRequest,
+    Provisioned {
+        read_capacity_units: i64,
+        write_capacity_units: i64,
+    },
+}
+
+/// Layout of a table keyed by a string partition key and a sort key,
+/// optionally expiring items through a TTL attribute.
+#[derive(Debug, Clone)]
+pub struct TableConfig {
+    pub table_name: String,
+    pub partition_key: String,
+    pub sort_key: String,
+    pub sort_key_type: KeyAttributeType,
+    /// Attribute holding the expiry time in epoch seconds. `None` disables TTL.
+    pub ttl_attribute: Option<String>,
+    /// How long items are kept when `ttl_attribute` is set.
+    pub retention: Option<Duration>,
+    pub billing_mode: TableBillingMode,
+    /// Publishes new and old item images to the table's DynamoDB stream.
+    pub stream_enabled: bool,
+}
+
+impl TableConfig {
+    /// Epoch seconds after which an item created at `event_time_ms` expires.
+    pub fn expires_at(&self, event_time_ms: u64) -> Option<u64> {
+        match (&self.ttl_attribute, self.retention) {
+            (Some(_), Some(retention)) => Some(event_time_ms / 1000 + retention.as_secs()),
+            _ => None,
+        }
+    }
+}
+
+/// Creates the table described by `config` if it does not exist, otherwise
+/// checks that its key schema, and the view type of an enabled stream,
+/// match. TTL and the stream are enabled in both cases.
+pub async fn ensure_table(
+    client: &aws_sdk_dynamodb::Client,
+    config: &TableConfig,
+) -> Result<(), TableError> {
+    let describe = client
+        .describe_table()
+        .table_name(&config.table_name)
+        .send()
+        .await;
+    match describe {
+        Ok(output) => {
+            if let Some(table) = output.table() {
+                validate_table(table, config)?;
+                let stream = table
+                    .stream_specification()
+                    .filter(|stream| stream.stream_enabled());
+                match stream {
+                    _ if !config.stream_enabled => {}
+                    None => {
+                        enable_stream(client, &config.table_name).await?;
+                        wait_until_active(client, &config.table_name).await?;
+                    }
+                    // A table has one stream, and its view type cannot change
+                    // while it is enabled.
+                    Some(stream)
+                        if stream.stream_view_type() != Some(&StreamViewType::NewAndOldImages) =>
+                    {
+                        return Err(TableError::SchemaMismatch {
+                            table: config.table_name.clone(),
+                            reason: format!(
+                                "expected stream view type {:?}, found {:?}",
+                                StreamViewType::NewAndOldImages,
+                                stream.stream_view_type()
+                            ),
+                        });
+                    }
+                    Some(_) => {}
+                }
+            }
+            info!("Table {} already exists", config.table_name);
+        }
+        Err(e)
+            if e.as_service_error()
+                .is_some_and(|e| e.is_resource_not_found_exception()) =>
+        {
+            create_table(client, config).await?;
+            wait_until_active(client, &config.table_name).await?;
+            info!("Created table {}", config.table_name);
+        }
+        Err(e) => return Err(aws_sdk_dynamodb::Error::from(e).into()),
+    }
+    if let Some(ttl_attribute) = &config.ttl_attribute {
+        enable_ttl(client, &config.table_name, ttl_attribute).await?;
+    }
+    Ok(())
+}
+
+async fn create_table(
+    client: &aws_sdk_dynamodb::Client,
+    config: &TableConfig,
+) -> Result<(), aws_sdk_dynamodb::Error> {
+    let mut request = client
+        .create_table()
+        .table_name(&config.table_name)
+        .key_schema(
+            KeySchemaElement::builder()
+                .attribute_name(&config.partition_key)
+                .key_type(KeyType::Hash)
+                .build()?,
+        )
+        .key_schema(
+            KeySchemaElement::builder()
+                .attribute_name(&config.sort_key)
+                .key_type(KeyType::Range)
+                .build()?,
+        )
+        .attribute_definitions(
+            AttributeDefinition::builder()
+                .attribute_name(&config.partition_key)
+                .attribute_type(ScalarAttributeType::S)
+                .build()?,
+        )
+        .attribute_definitions(
+            AttributeDefinition::builder()
+                .attribute_name(&config.sort_key)
+                .attribute_type(config.sort_key_type.scalar_type())
+                .build()?,
+        );
+    request = match config.billing_mode {
+        TableBillingMode::PayPerRequest => request.billing_mode(BillingMode::PayPerRequest),
+        TableBillingMode::Provisioned {
+            read_capacity_units,
+            write_capacity_units,
+        } => request
+            .billing_mode(BillingMode::Provisioned)
+            .provisioned_throughput(
+                ProvisionedThroughput::builder()
+                    .read_capacity_units(read_capacity_units)
+                    .write_capacity_units(write_capacity_units)
+                    .build()?,
+            ),
+    };
+    if config.stream_enabled {
+        request = request.stream_specification(stream_specification()?);
+    }
+    request.send().await?;
+    Ok(())
+}
+
+fn stream_specification() -> Result<StreamSpecification, BuildError> {
+    StreamSpecification::builder()
+        .stream_enabled(true)
+        .stream_view_type(StreamViewType::NewAndOldImages)
+        .build()
+}
+
+async fn enable_stream(
+    client: &aws_sdk_dynamodb::Client,
+    table_name: &str,
+) -> Result<(), aws_sdk_dynamodb::Error> {
+    client
+        .update_table()
+        .table_name(table_name)
+        .stream_specification(stream_specification()?)
+        .send()
+        .await?;
+    info!("Enabled stream on {}", table_name);
+    Ok(())
+}
+
+fn validate_table(table: &TableDescription, config: &TableConfig) -> Result<(), TableError> {
+    let mismatch = |reason: String| TableError::SchemaMismatch {
+        table: config.table_name.clone(),
+        reason,
+    };
+    let key_name = |key_type: KeyType| {
+        table
+            .key_schema()
+            .iter()
+            .find(|key| *key.key_type() == key_type)
+            .map(|key| key.attribute_name().to_string())
+    };
+    let attribute_type = |name: &str| {
+        table
+            .attribute_definitions()
+            .iter()
+            .find(|attribute| attribute.attribute_name() == name)
+            .map(|attribute| attribute.attribute_type().clone())
+    };
+
+    if key_name(KeyType::Hash).as_deref() != Some(config.partition_key.as_str()) {
+        return Err(mismatch(format!(
+            "expected partition key {}, found {:?}",
+            config.partition_key,
+            key_name(KeyType::Hash)
+        )));
+    }
+    let partition_key_type = attribute_type(&config.partition_key);
+    if partition_key_type != Some(ScalarAttributeType::S) {
+        return Err(mismatch(format!(
+            "expected partition key type S, found {:?}",
+            partition_key_type
+        )));
+    }
+    if key_name(KeyType::Range).as_deref() != Some(config.sort_key.as_str()) {
+        return Err(mismatch(format!(
+            "expected sort key {}, found {:?}",
+            config.sort_key,
+            key_name(KeyType::Range)
+        )));
+    }
+    let sort_key_type = attribute_type(&config.sort_key);
+    if sort_key_type.as_ref() != Some(&config.sort_key_type.scalar_type()) {
+        return Err(mismatch(format!(
+            "expected sort key type {:?}, found {:?}",
+            config.sort_key_type.scalar_type(),
+            sort_key_type
+        )));
+    }
+
+    // Tables created as provisioned have no billing mode summary.
+    let on_demand = table
+        .billing_mode_summary()
+        .and_then(|summary| summary.billing_mode())
+        .is_some_and(|mode| *mode == BillingMode::PayPerRequest);
+    if on_demand != (config.billing_mode == TableBillingMode::PayPerRequest) {
+        return Err(mismatch(format!(
+            "expected billing mode {:?}, found {}",
+            config.billing_mode,
+            if on_demand {
+                "PayPerRequest"
+            } else {
+                "Provisioned"
+            }
+        )));
+    }
+    Ok(())
+}
+
+async fn wait_until_active(
+    client: &aws_sdk_dynamodb::Client,
+    table_name: &str,
+) -> Result<(), TableError> {
+    for _ in 0..60 {
+        let output = client
+            .describe_table()
+            .table_name(table_name)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+        let status = output.table().and_then(|table| table.table_status());
+        if status == Some(&TableStatus::Active) {
+            return Ok(());
+        }
+        time::sleep(Duration::from_secs(2)).await;
+    }
+    Err(TableError::NotActive(table_name.to_string()))
+}
+
+async fn enable_ttl(
+    client: &aws_sdk_dynamodb::Client,
+    table_name: &str,
+    ttl_attribute: &str,
+) -> Result<(), TableError> {
+    let output = client
+        .describe_time_to_live()
+        .table_name(table_name)
+        .send()
+        .await
+        .map_err(aws_sdk_dynamodb::Error::from)?;
+    let description = output.time_to_live_description();
+    let status = description.and_then(|description| description.time_to_live_status());
+    if matches!(
+        status,
+        Some(TimeToLiveStatus::Enabled) | Some(TimeToLiveStatus::Enabling)
+    ) {
+        let found = description
+            .and_then(|description| description.attribute_name())
+            .unwrap_or_default();
+        // A table expires items by at most one attribute, so items written
+        // with ours would never expire.
+        if found != ttl_attribute {
+            return Err(TableError::TtlMismatch {
+                table: table_name.to_string(),
+                expected: ttl_attribute.to_string(),
+                found: found.to_string(),
+            });
+        }
+        return Ok(());
+    }
+    // DynamoDB rejects any TTL change until disabling completes.
+    if status == Some(&TimeToLiveStatus::Disabling) {
+        return Err(TableError::TtlDisabling(table_name.to_string()));
+    }
+    client
+        .update_time_to_live()
+        .table_name(table_name)
+        .time_to_live_specification(
+            TimeToLiveSpecification::builder()
+                .enabled(true)
+                .attribute_name(ttl_attribute)
+                .build()
+                .map_err(aws_sdk_dynamodb::Error::from)?,
+        )
+        .send()
+        .await
+        .map_err(aws_sdk_dynamodb::Error::from)?;
+    info!("Enabled TTL on {} using {}", table_name, ttl_attribute);
+    Ok(())
+}
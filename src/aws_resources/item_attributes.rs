@@ -0,0 +1,67 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Why a DynamoDB item could not be mapped back into a typed value.
+#[derive(Error, Debug)]
+pub enum ItemError {
+    #[error("item has no {0} attribute")]
+    MissingAttribute(String),
+    #[error("invalid {attribute} attribute {value:?}")]
+    InvalidAttribute { attribute: String, value: String },
+}
+
+/// String or number attribute as text; key and price attributes may be
+/// either depending on the table's configuration.
+pub fn string_attribute<'a>(
+    item: &'a HashMap<String, AttributeValue>,
+    name: &str,
+) -> Result<&'a str, ItemError> {
+    match item.get(name) {
+        Some(AttributeValue::S(value)) | Some(AttributeValue::N(value)) => Ok(value),
+        Some(other) => Err(ItemError::InvalidAttribute {
+            attribute: name.to_string(),
+            value: format!("{:?}", other),
+        }),
+        None => Err(ItemError::MissingAttribute(name.to_string())),
+    }
+}
+
+pub fn number_attribute<T: std::str::FromStr>(
+    item: &HashMap<String, AttributeValue>,
+    name: &str,
+) -> Result<T, ItemError> {
+    optional_number_attribute(item, name)?
+        .ok_or_else(|| ItemError::MissingAttribute(name.to_string()))
+}
+
+pub fn optional_number_attribute<T: std::str::FromStr>(
+    item: &HashMap<String, AttributeValue>,
+    name: &str,
+) -> Result<Option<T>, ItemError> {
+    if !item.contains_key(name) {
+        return Ok(None);
+    }
+    let value = string_attribute(item, name)?;
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| ItemError::InvalidAttribute {
+            attribute: name.to_string(),
+            value: value.to_string(),
+        })
+}
+
+pub fn bool_attribute(
+    item: &HashMap<String, AttributeValue>,
+    name: &str,
+) -> Result<bool, ItemError> {
+    match item.get(name) {
+        Some(AttributeValue::Bool(value)) => Ok(*value),
+        Some(other) => Err(ItemError::InvalidAttribute {
+            attribute: name.to_string(),
+            value: format!("{:?}", other),
+        }),
+        None => Err(ItemError::MissingAttribute(name.to_string())),
+    }
+}
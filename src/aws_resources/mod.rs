@@ -1,2 +1,6 @@
+pub mod batch_write;
 pub mod clients;
+pub mod dynamodb_tables;
+pub mod item_attributes;
 pub mod ssm_params;
+pub mod write_rate;
@@ -27,11 +27,11 @@ pub async fn get_param_value(
     param_name: String,
 ) -> Result<Option<String>, SdkError<GetParameterError, HttpResponse>> {
     let param_info = request_param(ssm_client, param_name.clone()).await?;
-    if let Some(parameter) = param_info.parameter() {
-        if let Some(value) = parameter.value() {
-            info!("Succeed in getting {:?}", &param_name);
-            return Ok(Some(value.to_string()));
-        }
+    if let Some(parameter) = param_info.parameter()
+        && let Some(value) = parameter.value()
+    {
+        info!("Succeed in getting {:?}", &param_name);
+        return Ok(Some(value.to_string()));
     }
     Ok(None)
 }
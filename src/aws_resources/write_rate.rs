@@ -0,0 +1,432 @@
+use crate::aws_resources::batch_write::MAX_BATCH_WRITE_ITEMS;
+use aws_sdk_dynamodb::error::ProvideErrorMetadata;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::time::{self, Duration, Instant};
+use tracing::info;
+
+#[derive(Error, Debug)]
+pub enum WriteError {
+    /// DynamoDB rejected the write for exceeding table, partition or account
+    /// capacity.
+    #[error("write throttled: {0}")]
+    Throttled(Box<aws_sdk_dynamodb::Error>),
+    #[error(transparent)]
+    DynamoError(Box<aws_sdk_dynamodb::Error>),
+    /// Dropped before being sent because higher priority writes needed the
+    /// capacity.
+    #[error("write shed under load")]
+    Shed,
+}
+
+impl From<aws_sdk_dynamodb::Error> for WriteError {
+    fn from(error: aws_sdk_dynamodb::Error) -> Self {
+        if is_throttling_error(&error) {
+            WriteError::Throttled(Box::new(error))
+        } else {
+            WriteError::DynamoError(Box::new(error))
+        }
+    }
+}
+
+/// Whether DynamoDB rejected a request for lack of capacity rather than
+/// because the request itself was wrong.
+pub fn is_throttling_error(error: &aws_sdk_dynamodb::Error) -> bool {
+    match error {
+        aws_sdk_dynamodb::Error::ProvisionedThroughputExceededException(_)
+        | aws_sdk_dynamodb::Error::RequestLimitExceeded(_) => true,
+        other => other.code() == Some("ThrottlingException"),
+    }
+}
+
+/// While `High` writes wait for capacity, new `Low` writes that would eat
+/// into their reserve are shed instead of waiting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WritePriority {
+    /// Orders, fills and other records that must not be lost.
+    High,
+    /// Tickers, which the next update supersedes anyway.
+    Low,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WriteRateConfig {
+    /// Items per second allowed before any feedback from DynamoDB.
+    pub initial_rate: f64,
+    pub min_rate: f64,
+    pub max_rate: f64,
+    /// Items per second added after each `increase_interval` without
+    /// throttling.
+    pub additive_increase: f64,
+    pub increase_interval: Duration,
+    /// Factor applied to the rate on throttling, at most once per
+    /// `decrease_cooldown` so one burst of rejections halves it only once.
+    pub multiplicative_decrease: f64,
+    pub decrease_cooldown: Duration,
+    /// Share of the bucket, at least one batch, that `Low` writes leave
+    /// untouched while `High` writes are waiting.
+    pub high_reserve: f64,
+}
+
+impl Default for WriteRateConfig {
+    fn default() -> Self {
+        WriteRateConfig {
+            initial_rate: 500.0,
+            min_rate: 10.0,
+            max_rate: 10_000.0,
+            additive_increase: 25.0,
+            increase_interval: Duration::from_secs(1),
+            multiplicative_decrease: 0.5,
+            decrease_cooldown: Duration::from_secs(1),
+            high_reserve: 0.2,
+        }
+    }
+}
+
+impl WriteRateConfig {
+    /// Reads `DYNAMO_WRITE_RATE` and `DYNAMO_MAX_WRITE_RATE` (items per
+    /// second), falling back to the defaults.
+    pub fn from_env() -> Self {
+        let mut config = WriteRateConfig::default();
+        let rate = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<f64>().ok())
+                .filter(|rate| *rate > 0.0)
+        };
+        if let Some(initial_rate) = rate("DYNAMO_WRITE_RATE") {
+            config.initial_rate = initial_rate;
+        }
+        if let Some(max_rate) = rate("DYNAMO_MAX_WRITE_RATE") {
+            config.max_rate = max_rate;
+        }
+        config
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+    increased_at: Instant,
+    decreased_at: Option<Instant>,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity());
+        self.refilled_at = now;
+    }
+
+    /// One second of burst, and always room for a full batch.
+    fn capacity(&self) -> f64 {
+        self.rate.max(MAX_BATCH_WRITE_ITEMS as f64)
+    }
+}
+
+#[derive(Debug, Default)]
+struct WriteRateStats {
+    written: AtomicU64,
+    throttled: AtomicU64,
+    failed: AtomicU64,
+    shed: AtomicU64,
+    high_waiting: AtomicUsize,
+}
+
+/// Counts a `High` priority writer in `high_waiting` until dropped, even if
+/// its `acquire` is cancelled.
+struct HighWaiting<'a>(&'a WriteRateStats);
+
+impl<'a> HighWaiting<'a> {
+    fn new(stats: &'a WriteRateStats) -> Self {
+        stats.high_waiting.fetch_add(1, Ordering::SeqCst);
+        HighWaiting(stats)
+    }
+}
+
+impl Drop for HighWaiting<'_> {
+    fn drop(&mut self) {
+        self.0.high_waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteRateSnapshot {
+    /// Current allowed items per second.
+    pub rate: f64,
+    /// Items DynamoDB accepted.
+    pub written: u64,
+    /// Requests or items DynamoDB rejected for capacity.
+    pub throttled: u64,
+    /// Items given up on after exhausting their retries.
+    pub failed: u64,
+    /// `Low` priority items dropped without being sent.
+    pub shed: u64,
+}
+
+/// Write budget shared by every DynamoDB writer, adjusted AIMD-style: the
+/// rate grows linearly while writes succeed and is cut multiplicatively
+/// whenever DynamoDB throttles. `High` priority writes wait for capacity,
+/// and while they do `Low` writes leave `high_reserve` of the bucket to them;
+/// a new `Low` write that cannot is shed, while retries of `Low` writes
+/// already sent wait like any other.
+#[derive(Debug, Clone)]
+pub struct WriteRate {
+    config: WriteRateConfig,
+    bucket: Arc<Mutex<Bucket>>,
+    stats: Arc<WriteRateStats>,
+}
+
+impl Default for WriteRate {
+    fn default() -> Self {
+        WriteRate::new(WriteRateConfig::default())
+    }
+}
+
+impl WriteRate {
+    pub fn new(config: WriteRateConfig) -> Self {
+        let now = Instant::now();
+        let rate = config
+            .initial_rate
+            .clamp(config.min_rate, config.max_rate.max(config.min_rate));
+        WriteRate {
+            config,
+            bucket: Arc::new(Mutex::new(Bucket {
+                rate,
+                tokens: rate.max(MAX_BATCH_WRITE_ITEMS as f64),
+                refilled_at: now,
+                increased_at: now,
+                decreased_at: None,
+            })),
+            stats: Arc::new(WriteRateStats::default()),
+        }
+    }
+
+    /// Waits until `items` writes fit in the budget. Returns `false`, and
+    /// counts the items as shed, when a `Low` priority write would take
+    /// capacity reserved for waiting `High` priority ones.
+    pub async fn acquire(&self, items: usize, priority: WritePriority) -> bool {
+        self.take(items, priority, true).await
+    }
+
+    /// Waits until `items` writes fit in the budget, for retries of writes
+    /// already sent, which are never shed.
+    pub async fn acquire_retry(&self, items: usize, priority: WritePriority) {
+        self.take(items, priority, false).await;
+    }
+
+    async fn take(&self, items: usize, priority: WritePriority, may_shed: bool) -> bool {
+        if items == 0 {
+            return true;
+        }
+        let needed = items as f64;
+        let _waiting = (priority == WritePriority::High).then(|| HighWaiting::new(&self.stats));
+        let acquired = loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                bucket.refill(Instant::now());
+                let reserve = if priority == WritePriority::Low
+                    && self.stats.high_waiting.load(Ordering::SeqCst) > 0
+                {
+                    (bucket.capacity() * self.config.high_reserve).max(MAX_BATCH_WRITE_ITEMS as f64)
+                } else {
+                    0.0
+                };
+                let required = (needed + reserve).min(bucket.capacity());
+                if bucket.tokens >= required {
+                    bucket.tokens -= needed;
+                    break true;
+                }
+                if may_shed && reserve > 0.0 {
+                    break false;
+                }
+                Duration::from_secs_f64((required - bucket.tokens) / bucket.rate)
+            };
+            time::sleep(wait).await;
+        };
+        if !acquired {
+            self.stats.shed.fetch_add(items as u64, Ordering::Relaxed);
+        }
+        acquired
+    }
+
+    /// Reports `items` accepted writes, growing the rate once per
+    /// `increase_interval`.
+    pub fn record_success(&self, items: u64) {
+        self.stats.written.fetch_add(items, Ordering::Relaxed);
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        if now.duration_since(bucket.increased_at) >= self.config.increase_interval {
+            bucket.refill(now);
+            bucket.rate = (bucket.rate + self.config.additive_increase).min(self.config.max_rate);
+            bucket.increased_at = now;
+        }
+    }
+
+    /// Reports `items` writes DynamoDB throttled, cutting the rate.
+    pub fn record_throttled(&self, items: u64) {
+        self.stats.throttled.fetch_add(items, Ordering::Relaxed);
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        // Hold off growth until a full interval passes without throttling.
+        bucket.increased_at = now;
+        let cooled_down = bucket.decreased_at.is_none_or(|decreased_at| {
+            now.duration_since(decreased_at) >= self.config.decrease_cooldown
+        });
+        if cooled_down {
+            bucket.refill(now);
+            let rate =
+                (bucket.rate * self.config.multiplicative_decrease).max(self.config.min_rate);
+            info!(
+                "DynamoDB throttled writes, lowering write rate from {:.0} to {:.0} items/s",
+                bucket.rate, rate
+            );
+            bucket.rate = rate;
+            bucket.tokens = bucket.tokens.min(rate);
+            bucket.decreased_at = Some(now);
+        }
+    }
+
+    /// Reports `items` writes abandoned after exhausting their retries.
+    pub fn record_failed(&self, items: u64) {
+        self.stats.failed.fetch_add(items, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> WriteRateSnapshot {
+        WriteRateSnapshot {
+            rate: self.bucket.lock().unwrap().rate,
+            written: self.stats.written.load(Ordering::Relaxed),
+            throttled: self.stats.throttled.load(Ordering::Relaxed),
+            failed: self.stats.failed.load(Ordering::Relaxed),
+            shed: self.stats.shed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> WriteRateConfig {
+        WriteRateConfig {
+            initial_rate: 100.0,
+            min_rate: 10.0,
+            max_rate: 160.0,
+            ..Default::default()
+        }
+    }
+
+    fn rate(write_rate: &WriteRate) -> f64 {
+        write_rate.snapshot().rate
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn grows_rate_additively_once_per_interval() {
+        let write_rate = WriteRate::new(config());
+        write_rate.record_success(1);
+        assert_eq!(rate(&write_rate), 100.0);
+        time::advance(Duration::from_secs(1)).await;
+        write_rate.record_success(1);
+        write_rate.record_success(1);
+        assert_eq!(rate(&write_rate), 125.0);
+        for _ in 0..3 {
+            time::advance(Duration::from_secs(1)).await;
+            write_rate.record_success(1);
+        }
+        assert_eq!(rate(&write_rate), 160.0);
+        assert_eq!(write_rate.snapshot().written, 6);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn halves_rate_once_per_cooldown_down_to_the_minimum() {
+        let write_rate = WriteRate::new(config());
+        write_rate.record_throttled(1);
+        write_rate.record_throttled(1);
+        assert_eq!(rate(&write_rate), 50.0);
+        time::advance(Duration::from_secs(1)).await;
+        write_rate.record_throttled(1);
+        assert_eq!(rate(&write_rate), 25.0);
+        for _ in 0..2 {
+            time::advance(Duration::from_secs(1)).await;
+            write_rate.record_throttled(1);
+        }
+        assert_eq!(rate(&write_rate), 10.0);
+        assert_eq!(write_rate.snapshot().throttled, 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttling_holds_off_growth_for_an_interval() {
+        let write_rate = WriteRate::new(config());
+        time::advance(Duration::from_millis(900)).await;
+        write_rate.record_throttled(1);
+        time::advance(Duration::from_millis(500)).await;
+        write_rate.record_success(1);
+        assert_eq!(rate(&write_rate), 50.0);
+        time::advance(Duration::from_millis(500)).await;
+        write_rate.record_success(1);
+        assert_eq!(rate(&write_rate), 75.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_the_unprocessed_part_of_a_throttled_low_batch() {
+        let write_rate = WriteRate::new(config());
+        assert!(write_rate.acquire(25, WritePriority::Low).await);
+        // DynamoDB returned 10 of the 25 items unprocessed.
+        write_rate.record_success(15);
+        write_rate.record_throttled(10);
+        time::advance(Duration::from_millis(50)).await;
+        write_rate.acquire_retry(10, WritePriority::Low).await;
+        // New ticker batches keep flowing too.
+        assert!(write_rate.acquire(25, WritePriority::Low).await);
+        assert_eq!(write_rate.snapshot().shed, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sheds_low_writes_only_when_waiting_high_writes_need_the_reserve() {
+        let write_rate = WriteRate::new(config());
+        assert!(write_rate.acquire(90, WritePriority::High).await);
+        // Ten tokens left and no `High` write waiting: `Low` waits for more.
+        let started = Instant::now();
+        assert!(write_rate.acquire(20, WritePriority::Low).await);
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        let high = tokio::spawn({
+            let write_rate = write_rate.clone();
+            async move { write_rate.acquire(20, WritePriority::High).await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!write_rate.acquire(3, WritePriority::Low).await);
+        assert_eq!(write_rate.snapshot().shed, 3);
+        assert!(high.await.unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn high_writes_wait_for_capacity() {
+        let write_rate = WriteRate::new(config());
+        let started = Instant::now();
+        assert!(write_rate.acquire(100, WritePriority::High).await);
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert!(write_rate.acquire(50, WritePriority::High).await);
+        assert!(started.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn low_retries_leave_a_reserve_to_waiting_high_writes() {
+        let write_rate = WriteRate::new(config());
+        assert!(write_rate.acquire(100, WritePriority::High).await);
+        let started = Instant::now();
+        let high = tokio::spawn({
+            let write_rate = write_rate.clone();
+            async move { write_rate.acquire(20, WritePriority::High).await }
+        });
+        tokio::task::yield_now().await;
+        // Without a waiting `High` write one token would be ready in 10 ms;
+        // with one, a `Low` retry also leaves a batch of 25 for it.
+        write_rate.acquire_retry(1, WritePriority::Low).await;
+        assert!(started.elapsed() >= Duration::from_millis(260));
+        assert!(high.await.unwrap());
+        assert_eq!(write_rate.snapshot().shed, 0);
+    }
+}
@@ -0,0 +1,74 @@
+//! Follows the order or ticker table's DynamoDB stream and logs every change,
+//! checkpointing its position so a restart continues where it stopped.
+//!
+//! Usage: `change_stream <orders | tickers> [consumer]`
+//!
+//! Set `AWS_ENDPOINT_URL=http://localhost:8000` to read from DynamoDB Local.
+
+use dynamo_rust::aws_resources::clients::{get_ddb_client, get_ddb_streams_client};
+use dynamo_rust::bookticker_stream::ticker_db::TickerTableConfig;
+use dynamo_rust::change_stream::reader::{ChangeStreamConfig, ChangeStreamReader};
+use dynamo_rust::change_stream::records::ChangeTable;
+use dynamo_rust::order_stream::order_db::OrderTableConfig;
+use futures::StreamExt;
+use std::fmt::Debug;
+use tracing::{info, Level};
+
+async fn follow<T: ChangeTable>(
+    reader: ChangeStreamReader<T>,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    T::Record: Debug,
+{
+    let records = reader.records().await?;
+    futures::pin_mut!(records);
+    while let Some(record) = records.next().await {
+        match record {
+            Ok(record) => info!(
+                "{:?} {} at {:?}: {:?}",
+                record.kind,
+                record.sequence_number,
+                record.created_at,
+                record.new.as_ref().or(record.old.as_ref())
+            ),
+            Err(e) => info!("Error reading change stream: {}", e),
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(table) = args.first() else {
+        return Err("usage: change_stream <orders | tickers> [consumer]".into());
+    };
+    let mut config = ChangeStreamConfig::from_env();
+    if let Some(consumer) = args.get(1) {
+        config.consumer = consumer.clone();
+    }
+    let client = get_ddb_client().await?;
+    let streams_client = get_ddb_streams_client().await?;
+    match table.as_str() {
+        "orders" => {
+            let reader = ChangeStreamReader::new(
+                client,
+                streams_client,
+                OrderTableConfig::from_env(),
+                config,
+            );
+            follow(reader).await
+        }
+        "tickers" => {
+            let reader = ChangeStreamReader::new(
+                client,
+                streams_client,
+                TickerTableConfig::from_env(),
+                config,
+            );
+            follow(reader).await
+        }
+        _ => Err(format!("unknown table {}", table).into()),
+    }
+}
@@ -0,0 +1,77 @@
+//! Exports the ticker or order table to Parquet or CSV files partitioned by
+//! date and symbol. Re-running an interrupted export resumes it.
+//!
+//! Usage: `export <tickers | orders> <parquet | csv> <out dir> [segments]`
+//! or `export <tickers | orders> <parquet | csv> <out dir> --symbol <symbol> --from <ms> --to <ms>`
+
+use dynamo_rust::aws_resources::clients::get_ddb_client;
+use dynamo_rust::bookticker_stream::ticker_db::TickerTableConfig;
+use dynamo_rust::export::exporter::{ExportConfig, ExportSelection, ExportSummary, Exporter};
+use dynamo_rust::export::writer::ExportFormat;
+use dynamo_rust::order_stream::order_db::OrderTableConfig;
+use std::path::PathBuf;
+use tracing::{info, Level};
+
+const USAGE: &str = "usage: export <tickers | orders> <parquet | csv> <out dir> \
+                     [segments | --symbol <symbol> --from <ms> --to <ms>]";
+
+fn parse_selection(args: &[String]) -> Result<ExportSelection, Box<dyn std::error::Error>> {
+    if args.first().map(String::as_str) != Some("--symbol") {
+        let segments = args.first().map(|s| s.parse()).transpose()?.unwrap_or(4);
+        return Ok(ExportSelection::Scan { segments });
+    }
+    let mut symbol = None;
+    let mut from = 0;
+    // Largest 13-digit millisecond timestamp, the width of the sort keys.
+    let mut to = 9_999_999_999_999;
+    for pair in args.chunks(2) {
+        let [flag, value] = pair else {
+            return Err(USAGE.into());
+        };
+        match flag.as_str() {
+            "--symbol" => symbol = Some(value.clone()),
+            "--from" => from = value.parse()?,
+            "--to" => to = value.parse()?,
+            _ => return Err(USAGE.into()),
+        }
+    }
+    Ok(ExportSelection::Query {
+        symbol: symbol.ok_or(USAGE)?,
+        from,
+        to,
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [table, format, out_dir, rest @ ..] = args.as_slice() else {
+        return Err(USAGE.into());
+    };
+    let config = ExportConfig {
+        out_dir: PathBuf::from(out_dir),
+        format: ExportFormat::parse(format).ok_or_else(|| format!("invalid format {}", format))?,
+        selection: parse_selection(rest)?,
+        ..Default::default()
+    };
+    let client = get_ddb_client().await?;
+    let summary: ExportSummary = match table.as_str() {
+        "tickers" => {
+            Exporter::new(client, TickerTableConfig::from_env(), config)
+                .run()
+                .await?
+        }
+        "orders" => {
+            Exporter::new(client, OrderTableConfig::from_env(), config)
+                .run()
+                .await?
+        }
+        _ => return Err(format!("unknown table {}", table).into()),
+    };
+    info!(
+        "Export finished: {} items, {} files, {} units already complete",
+        summary.items, summary.files, summary.resumed
+    );
+    Ok(())
+}
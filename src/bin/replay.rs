@@ -0,0 +1,69 @@
+//! Replays a raw frame journal through the book ticker and user data
+//! handlers and prints the resulting book.
+//!
+//! Usage: `replay <journal dir> [realtime | max | <factor>] [from ms] [to ms]`
+
+use dynamo_rust::bookticker_stream::bookticker::BookTickerStream;
+use dynamo_rust::clock::enable_simulated_time;
+use dynamo_rust::journal::replay::{ReplayConfig, ReplaySpeed, Replayer};
+use dynamo_rust::order_stream::order_update::UserDataStream;
+use std::path::PathBuf;
+use tracing::{info, Level};
+
+fn parse_speed(speed: &str) -> Option<ReplaySpeed> {
+    match speed {
+        "realtime" => Some(ReplaySpeed::RealTime),
+        "max" => Some(ReplaySpeed::AsFastAsPossible),
+        factor => factor
+            .trim_end_matches('x')
+            .parse::<f64>()
+            .ok()
+            .filter(|factor| *factor > 0.0)
+            .map(ReplaySpeed::Accelerated),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(dir) = args.first() else {
+        return Err(
+            "usage: replay <journal dir> [realtime | max | <factor>] [from ms] [to ms]".into(),
+        );
+    };
+    let speed = match args.get(1) {
+        Some(speed) => parse_speed(speed).ok_or_else(|| format!("invalid speed {}", speed))?,
+        None => ReplaySpeed::AsFastAsPossible,
+    };
+    let config = ReplayConfig {
+        dir: PathBuf::from(dir),
+        speed,
+        from: args.get(2).map(|from| from.parse()).transpose()?,
+        to: args.get(3).map(|to| to.parse()).transpose()?,
+        ..Default::default()
+    };
+    // This process runs no live streams, so the replay may drive the clock.
+    enable_simulated_time();
+    let book_ticker = BookTickerStream::new();
+    let stats = Replayer::new(config)
+        .with_book_ticker(book_ticker.clone())
+        .with_user_data(UserDataStream::new(String::new()))
+        .run()
+        .await?;
+    let book = book_ticker.book_ticker.lock().await;
+    let mut symbols: Vec<&String> = book.keys().collect();
+    symbols.sort();
+    for symbol in symbols {
+        let prices = &book[symbol];
+        info!(
+            "{}: Bid: {} x {}, Ask: {} x {}, update {}",
+            symbol, prices.bid, prices.bid_qty, prices.ask, prices.ask_qty, prices.update_id
+        );
+    }
+    info!(
+        "Replayed {} frames up to {:?}",
+        stats.replayed, stats.last_received_at
+    );
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use crate::aws_resources::batch_write::{
+    batch_write_with_retry, put_requests, BatchRetryConfig, MAX_BATCH_WRITE_ITEMS,
+};
+use crate::aws_resources::dynamodb_tables::{KeyAttributeType, TableBillingMode, TableConfig};
+use crate::aws_resources::write_rate::{WritePriority, WriteRate};
+use crate::bookticker_stream::bars::{Bar, BarSink, Ohlc};
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+use thiserror::Error;
+
+pub const BAR_TABLE_NAME: &str = "TestTickerBarTable";
+
+/// Items are keyed by `<partition_key>` = `symbol#interval` (e.g.
+/// `BTCUSDT#1m`) and `<sort_key>` = bar open time in milliseconds.
+#[derive(Debug, Clone)]
+pub struct BarTableConfig {
+    pub table: TableConfig,
+}
+
+impl Default for BarTableConfig {
+    fn default() -> Self {
+        BarTableConfig {
+            table: TableConfig {
+                table_name: BAR_TABLE_NAME.to_string(),
+                partition_key: "PK".to_string(),
+                sort_key: "SK".to_string(),
+                sort_key_type: KeyAttributeType::Number,
+                ttl_attribute: None,
+                retention: None,
+                billing_mode: TableBillingMode::PayPerRequest,
+                stream_enabled: false,
+            },
+        }
+    }
+}
+
+impl BarTableConfig {
+    /// Reads `BAR_TABLE_NAME`, falling back to the default table.
+    pub fn from_env() -> Self {
+        let mut config = BarTableConfig::default();
+        if let Ok(table_name) = std::env::var("BAR_TABLE_NAME") {
+            config.table.table_name = table_name;
+        }
+        config
+    }
+
+    pub fn bar_to_item(&self, bar: &Bar) -> HashMap<String, AttributeValue> {
+        let number = |value: f64| AttributeValue::N(value.to_string());
+        let mut item = HashMap::from([
+            (
+                self.table.partition_key.clone(),
+                AttributeValue::S(format!("{}#{}", bar.symbol, bar.interval)),
+            ),
+            (
+                self.table.sort_key.clone(),
+                AttributeValue::N(bar.open_time.to_string()),
+            ),
+            ("symbol".to_string(), AttributeValue::S(bar.symbol.clone())),
+            (
+                "interval".to_string(),
+                AttributeValue::S(bar.interval.to_string()),
+            ),
+            (
+                "close_time".to_string(),
+                AttributeValue::N(bar.close_time.to_string()),
+            ),
+            ("spread_min".to_string(), number(bar.spread_min)),
+            ("spread_max".to_string(), number(bar.spread_max)),
+            ("spread_mean".to_string(), number(bar.spread_mean)),
+            (
+                "updates".to_string(),
+                AttributeValue::N(bar.updates.to_string()),
+            ),
+        ]);
+        for (prefix, ohlc) in [("mid", &bar.mid), ("bid", &bar.bid), ("ask", &bar.ask)] {
+            insert_ohlc(&mut item, prefix, ohlc);
+        }
+        if let (Some(ttl_attribute), Some(expires_at)) = (
+            &self.table.ttl_attribute,
+            self.table.expires_at(bar.close_time),
+        ) {
+            item.insert(
+                ttl_attribute.clone(),
+                AttributeValue::N(expires_at.to_string()),
+            );
+        }
+        item
+    }
+}
+
+fn insert_ohlc(item: &mut HashMap<String, AttributeValue>, prefix: &str, ohlc: &Ohlc) {
+    for (name, value) in [
+        ("open", ohlc.open),
+        ("high", ohlc.high),
+        ("low", ohlc.low),
+        ("close", ohlc.close),
+    ] {
+        item.insert(
+            format!("{}_{}", prefix, name),
+            AttributeValue::N(value.to_string()),
+        );
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("failed to write {failed} of {total} bars")]
+pub struct BarWriteError {
+    pub failed: u64,
+    pub total: u64,
+}
+
+/// Writes closed bars to DynamoDB with `BatchWriteItem`.
+#[derive(Debug, Clone)]
+pub struct DynamoBarSink {
+    client: aws_sdk_dynamodb::Client,
+    config: BarTableConfig,
+    retry: BatchRetryConfig,
+    write_rate: WriteRate,
+}
+
+impl DynamoBarSink {
+    pub fn new(client: aws_sdk_dynamodb::Client, config: BarTableConfig) -> Self {
+        DynamoBarSink {
+            client,
+            config,
+            retry: BatchRetryConfig::default(),
+            write_rate: WriteRate::default(),
+        }
+    }
+
+    /// Shares `write_rate` with the other writers. Bars are few and are
+    /// written at `WritePriority::High`.
+    pub fn with_write_rate(mut self, write_rate: WriteRate) -> Self {
+        self.write_rate = write_rate;
+        self
+    }
+}
+
+impl BarSink for DynamoBarSink {
+    type Error = BarWriteError;
+
+    async fn write_bars(&mut self, bars: Vec<Bar>) -> Result<(), BarWriteError> {
+        let total = bars.len() as u64;
+        let mut written = 0;
+        for chunk in bars.chunks(MAX_BATCH_WRITE_ITEMS) {
+            let requests = put_requests(chunk.iter().map(|bar| self.config.bar_to_item(bar)));
+            let outcome = batch_write_with_retry(
+                &self.client,
+                &self.config.table.table_name,
+                requests,
+                &self.retry,
+                &self.write_rate,
+                WritePriority::High,
+            )
+            .await;
+            written += outcome.written;
+        }
+        if written < total {
+            return Err(BarWriteError {
+                failed: total - written,
+                total,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,380 @@
+use crate::bookticker_stream::bookticker::{BookTicker, BookTickerStream};
+use crate::bookticker_stream::subscription::SubscriptionOptions;
+use crate::clock::{current_time_millis, is_simulated};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration};
+use tracing::info;
+
+/// Bar length, aligned to multiples of the interval since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct BarInterval {
+    millis: u64,
+}
+
+impl BarInterval {
+    pub const ONE_SECOND: BarInterval = BarInterval { millis: 1_000 };
+    pub const ONE_MINUTE: BarInterval = BarInterval { millis: 60_000 };
+    pub const ONE_HOUR: BarInterval = BarInterval { millis: 3_600_000 };
+
+    pub fn from_millis(millis: u64) -> Self {
+        BarInterval {
+            millis: millis.max(1),
+        }
+    }
+
+    pub fn as_millis(&self) -> u64 {
+        self.millis
+    }
+
+    pub fn open_time(&self, event_time: u64) -> u64 {
+        event_time - event_time % self.millis
+    }
+}
+
+impl fmt::Display for BarInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.millis {
+            m if m % 3_600_000 == 0 => write!(f, "{}h", m / 3_600_000),
+            m if m % 60_000 == 0 => write!(f, "{}m", m / 60_000),
+            m if m % 1_000 == 0 => write!(f, "{}s", m / 1_000),
+            m => write!(f, "{}ms", m),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Ohlc {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+impl Ohlc {
+    fn new(price: f64) -> Self {
+        Ohlc {
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+        }
+    }
+
+    fn update(&mut self, price: f64) {
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Bar {
+    pub symbol: String,
+    pub interval: BarInterval,
+    /// Exchange event time of the first millisecond in the bar.
+    pub open_time: u64,
+    /// Exchange event time of the last millisecond in the bar.
+    pub close_time: u64,
+    pub mid: Ohlc,
+    pub bid: Ohlc,
+    pub ask: Ohlc,
+    pub spread_min: f64,
+    pub spread_max: f64,
+    pub spread_mean: f64,
+    pub updates: u64,
+}
+
+impl Bar {
+    fn new(symbol: &str, interval: BarInterval, open_time: u64, bid: f64, ask: f64) -> Self {
+        let spread = ask - bid;
+        Bar {
+            symbol: symbol.to_string(),
+            interval,
+            open_time,
+            close_time: open_time + interval.as_millis() - 1,
+            mid: Ohlc::new((bid + ask) / 2.0),
+            bid: Ohlc::new(bid),
+            ask: Ohlc::new(ask),
+            spread_min: spread,
+            spread_max: spread,
+            spread_mean: spread,
+            updates: 1,
+        }
+    }
+
+    fn update(&mut self, bid: f64, ask: f64) {
+        let spread = ask - bid;
+        self.mid.update((bid + ask) / 2.0);
+        self.bid.update(bid);
+        self.ask.update(ask);
+        self.spread_min = self.spread_min.min(spread);
+        self.spread_max = self.spread_max.max(spread);
+        self.updates += 1;
+        self.spread_mean += (spread - self.spread_mean) / self.updates as f64;
+    }
+}
+
+/// Builds bars per symbol and interval from book ticker updates.
+#[derive(Debug)]
+pub struct BarBuilder {
+    intervals: Vec<BarInterval>,
+    open_bars: HashMap<(String, BarInterval), Bar>,
+    /// Open time of the last bar emitted per key, so late tickers never
+    /// reopen a bar that was already written.
+    last_closed: HashMap<(String, BarInterval), u64>,
+}
+
+impl BarBuilder {
+    pub fn new(intervals: Vec<BarInterval>) -> Self {
+        BarBuilder {
+            intervals,
+            open_bars: HashMap::new(),
+            last_closed: HashMap::new(),
+        }
+    }
+
+    /// Adds a ticker and returns the bars it closed. Tickers older than the
+    /// currently open bar are ignored.
+    pub fn update(&mut self, ticker: &BookTicker) -> Vec<Bar> {
+        let (Ok(bid), Ok(ask)) = (
+            ticker.best_bid.parse::<f64>(),
+            ticker.best_ask.parse::<f64>(),
+        ) else {
+            return Vec::new();
+        };
+        let mut closed = Vec::new();
+        for interval in &self.intervals {
+            let open_time = interval.open_time(ticker.event_time);
+            let key = (ticker.symbol.clone(), *interval);
+            if self
+                .last_closed
+                .get(&key)
+                .is_some_and(|closed_open_time| *closed_open_time >= open_time)
+            {
+                continue;
+            }
+            match self.open_bars.get_mut(&key) {
+                Some(bar) if bar.open_time == open_time => bar.update(bid, ask),
+                Some(bar) if bar.open_time > open_time => {}
+                _ => {
+                    let bar = Bar::new(&ticker.symbol, *interval, open_time, bid, ask);
+                    if let Some(previous) = self.open_bars.insert(key.clone(), bar) {
+                        self.last_closed.insert(key, previous.open_time);
+                        closed.push(previous);
+                    }
+                }
+            }
+        }
+        closed
+    }
+
+    /// Closes every bar whose close time is before `now_ms`, so symbols that
+    /// stop updating still emit their last bar.
+    pub fn close_expired(&mut self, now_ms: u64) -> Vec<Bar> {
+        let expired: Vec<(String, BarInterval)> = self
+            .open_bars
+            .iter()
+            .filter(|(_, bar)| bar.close_time < now_ms)
+            .map(|(key, _)| key.clone())
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|key| {
+                let bar = self.open_bars.remove(&key)?;
+                self.last_closed.insert(key, bar.open_time);
+                Some(bar)
+            })
+            .collect()
+    }
+}
+
+/// Destination for closed bars.
+pub trait BarSink: Send + 'static {
+    type Error: fmt::Debug;
+
+    fn write_bars(
+        &mut self,
+        bars: Vec<Bar>,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+#[derive(Debug, Clone)]
+pub struct BarAggregatorConfig {
+    pub intervals: Vec<BarInterval>,
+    /// Symbols to aggregate. `None` aggregates every symbol.
+    pub symbols: Option<HashSet<String>>,
+    /// Extra time waited after a bar's close time before closing it without
+    /// a newer update, to absorb clock skew and late messages.
+    pub close_grace: Duration,
+    pub subscription_capacity: usize,
+}
+
+impl Default for BarAggregatorConfig {
+    fn default() -> Self {
+        BarAggregatorConfig {
+            intervals: vec![BarInterval::ONE_MINUTE, BarInterval::ONE_HOUR],
+            symbols: None,
+            close_grace: Duration::from_secs(2),
+            subscription_capacity: 10_000,
+        }
+    }
+}
+
+/// Aggregates updates from `stream` into bars and writes closed bars to `sink`.
+pub async fn spawn_bar_aggregator<S: BarSink>(
+    stream: &BookTickerStream,
+    config: BarAggregatorConfig,
+    mut sink: S,
+) -> JoinHandle<()> {
+    let mut subscription = stream
+        .subscribe(SubscriptionOptions {
+            symbols: config.symbols.clone(),
+            capacity: config.subscription_capacity,
+            ..Default::default()
+        })
+        .await;
+    tokio::spawn(async move {
+        let mut builder = BarBuilder::new(config.intervals.clone());
+        let mut expiry_check = time::interval(Duration::from_secs(1));
+        loop {
+            let closed = tokio::select! {
+                message = subscription.recv() => match message {
+                    // A replay's clock jumps with every frame instead of
+                    // following `expiry_check`, so bars expire by each
+                    // replayed ticker's event time before it is applied.
+                    Some(ticker) if is_simulated() => {
+                        let grace = config.close_grace.as_millis() as u64;
+                        let mut closed = builder.close_expired(ticker.event_time.saturating_sub(grace));
+                        closed.extend(builder.update(&ticker));
+                        closed
+                    }
+                    Some(ticker) => builder.update(&ticker),
+                    None => {
+                        info!("Book Ticker subscription closed, bar aggregator stopped");
+                        break;
+                    }
+                },
+                _ = expiry_check.tick(), if !is_simulated() => {
+                    let now = current_time_millis().saturating_sub(config.close_grace.as_millis() as u64);
+                    builder.close_expired(now)
+                }
+            };
+            if closed.is_empty() {
+                continue;
+            }
+            if let Err(e) = sink.write_bars(closed).await {
+                info!("Failed to write bars: {:?}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticker(symbol: &str, event_time: u64, bid: &str, ask: &str) -> BookTicker {
+        BookTicker {
+            event: "bookTicker".to_string(),
+            update_id: event_time,
+            symbol: symbol.to_string(),
+            best_bid: bid.to_string(),
+            bid_qty: "1".to_string(),
+            best_ask: ask.to_string(),
+            ask_qty: "1".to_string(),
+            trans_time: event_time,
+            event_time,
+        }
+    }
+
+    #[test]
+    fn update_in_next_interval_closes_previous_bar() {
+        let mut builder = BarBuilder::new(vec![BarInterval::ONE_MINUTE]);
+        assert!(builder
+            .update(&ticker("BTCUSDT", 60_000, "100", "102"))
+            .is_empty());
+        assert!(builder
+            .update(&ticker("BTCUSDT", 90_000, "104", "106"))
+            .is_empty());
+        assert!(builder
+            .update(&ticker("BTCUSDT", 119_999, "98", "99"))
+            .is_empty());
+
+        let closed = builder.update(&ticker("BTCUSDT", 120_000, "110", "111"));
+        assert_eq!(closed.len(), 1);
+        let bar = &closed[0];
+        assert_eq!(bar.open_time, 60_000);
+        assert_eq!(bar.close_time, 119_999);
+        assert_eq!(bar.updates, 3);
+        assert_eq!(bar.mid.open, 101.0);
+        assert_eq!(bar.mid.high, 105.0);
+        assert_eq!(bar.mid.low, 98.5);
+        assert_eq!(bar.mid.close, 98.5);
+        assert_eq!(bar.spread_min, 1.0);
+        assert_eq!(bar.spread_max, 2.0);
+        assert!((bar.spread_mean - 5.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rollover_skips_empty_intervals() {
+        let mut builder = BarBuilder::new(vec![BarInterval::ONE_MINUTE]);
+        builder.update(&ticker("BTCUSDT", 60_000, "100", "101"));
+        let closed = builder.update(&ticker("BTCUSDT", 300_000, "100", "101"));
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].open_time, 60_000);
+        let closed = builder.close_expired(360_000);
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].open_time, 300_000);
+    }
+
+    #[test]
+    fn intervals_roll_over_independently() {
+        let mut builder = BarBuilder::new(vec![BarInterval::ONE_MINUTE, BarInterval::ONE_HOUR]);
+        builder.update(&ticker("BTCUSDT", 0, "100", "101"));
+        let closed = builder.update(&ticker("BTCUSDT", 60_000, "100", "101"));
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].interval, BarInterval::ONE_MINUTE);
+        let closed = builder.update(&ticker("BTCUSDT", 3_600_000, "100", "101"));
+        let mut intervals: Vec<_> = closed.iter().map(|bar| bar.interval).collect();
+        intervals.sort_by_key(|interval| interval.as_millis());
+        assert_eq!(
+            intervals,
+            vec![BarInterval::ONE_MINUTE, BarInterval::ONE_HOUR]
+        );
+    }
+
+    #[test]
+    fn late_tickers_never_reopen_a_closed_bar() {
+        let mut builder = BarBuilder::new(vec![BarInterval::ONE_MINUTE]);
+        builder.update(&ticker("BTCUSDT", 60_000, "100", "101"));
+        builder.update(&ticker("BTCUSDT", 120_000, "100", "101"));
+        // Older than the open bar.
+        assert!(builder
+            .update(&ticker("BTCUSDT", 70_000, "1", "2"))
+            .is_empty());
+
+        let closed = builder.close_expired(180_000);
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].updates, 1);
+        // Belongs to the bar that was just closed by expiry.
+        assert!(builder
+            .update(&ticker("BTCUSDT", 150_000, "1", "2"))
+            .is_empty());
+        assert!(builder.close_expired(u64::MAX).is_empty());
+    }
+
+    #[test]
+    fn close_expired_keeps_open_bars() {
+        let mut builder = BarBuilder::new(vec![BarInterval::ONE_MINUTE]);
+        builder.update(&ticker("BTCUSDT", 60_000, "100", "101"));
+        builder.update(&ticker("ETHUSDT", 120_000, "10", "11"));
+        let closed = builder.close_expired(120_000);
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].symbol, "BTCUSDT");
+        assert!(builder.close_expired(179_999).is_empty());
+    }
+}
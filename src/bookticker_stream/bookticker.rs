@@ -1,16 +1,86 @@
-use futures::{SinkExt, StreamExt};
-use serde::Deserialize;
-use std::collections::HashMap;
+use crate::async_binance::client_async::AsyncBinanceClient;
+use crate::bookticker_stream::borrowed::{decode_book_ticker, BookTickerRef};
+use crate::bookticker_stream::connection::{bookticker_stream_name, BOOKTICKER_WS_URL};
+use crate::bookticker_stream::feeds::{FeedStats, FeedStatsSnapshot, SecondaryFeedConfig};
+use crate::bookticker_stream::latency::{LatencyReport, LatencyTracker};
+use crate::bookticker_stream::partitioner::{
+    imbalance, match_groups, partition_symbols, PartitionerConfig,
+};
+use crate::bookticker_stream::staleness::{
+    StaleSymbol, StalenessConfig, StalenessEvent, StalenessTracker,
+};
+use crate::bookticker_stream::subscription::{
+    BookTickerSubscription, Subscriber, SubscriptionOptions,
+};
+use crate::clock::{current_time_millis, ClockOffset};
+use crate::journal::record::JournalSource;
+use crate::journal::writer::Journal;
+use crate::websocket::connection::{
+    ConnectionDriver, ConnectionHandle, ConnectionState, ConnectionTaskError, FrameHandler,
+};
+use crate::websocket::feed::Feed;
+use crate::websocket::frame_errors::{FrameError, FrameErrorReporter, FrameMetricsSnapshot};
+use crate::websocket::reconnect::{ReconnectPolicy, ReconnectStatus};
+use crate::websocket::rotation::RotationPolicy;
+use crate::websocket::router::{Routed, RouterStatsSnapshot, StreamRouter};
+use futures::future;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
 use tokio::time;
-use tokio_tungstenite::connect_async;
-use tokio_tungstenite::tungstenite::Message;
 use tracing::info;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BestPrices {
     pub bid: f64,
+    pub bid_qty: f64,
     pub ask: f64,
+    pub ask_qty: f64,
+    /// Order book update id; quotes only ever move to a higher one.
+    pub update_id: u64,
+    /// Exchange transaction time of the quote, in epoch milliseconds.
+    pub trans_time: u64,
+    /// Exchange event time of the quote, in epoch milliseconds.
+    pub event_time: u64,
+    /// Local time the quote was received, in epoch milliseconds.
+    pub received_at: u64,
+}
+
+impl BestPrices {
+    pub fn from_ticker(ticker: &BookTicker, received_at: u64) -> Result<BestPrices, FrameError> {
+        BookTickerRef::from(ticker).quote(received_at)
+    }
+}
+
+/// Stores `quote` for `symbol` unless the book already holds one with the
+/// same or a later `update_id`, so duplicated or reordered messages, e.g.
+/// from overlapping connections, never overwrite fresher data. Returns
+/// whether the quote was stored.
+pub fn apply_quote(
+    book_ticker: &mut HashMap<String, BestPrices>,
+    symbol: &str,
+    quote: BestPrices,
+) -> bool {
+    match book_ticker.get_mut(symbol) {
+        Some(current) if current.update_id >= quote.update_id => false,
+        Some(current) => {
+            *current = quote;
+            true
+        }
+        None => {
+            book_ticker.insert(symbol.to_string(), quote);
+            true
+        }
+    }
+}
+
+/// A quote accepted into the store, as published by
+/// `BookTickerStream::quotes`.
+#[derive(Debug, Clone)]
+pub struct AcceptedQuote {
+    pub symbol: String,
+    pub prices: BestPrices,
 }
 
 #[derive(Deserialize, Debug)]
@@ -19,7 +89,7 @@ pub struct StreamBookTicker {
     pub data: BookTicker,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct BookTicker {
     #[serde(rename = "e")]
     pub event: String,
@@ -41,9 +111,78 @@ pub struct BookTicker {
     pub event_time: u64,
 }
 
+/// At most one log line per frame error kind in this interval.
+const FRAME_ERROR_LOG_INTERVAL: time::Duration = time::Duration::from_secs(10);
+/// Weight of the newest sample in the exponentially smoothed message rates.
+const RATE_SMOOTHING: f64 = 0.2;
+/// Accepted quotes a `quotes` receiver may fall behind before it lags.
+const QUOTE_CAPACITY: usize = 16_384;
+
+#[derive(Debug, Clone)]
+pub struct BookTickerStreamConfig {
+    pub url: String,
+    pub reconnect: ReconnectPolicy,
+    pub rotation: RotationPolicy,
+    pub partitioner: PartitionerConfig,
+    pub staleness: StalenessConfig,
+    /// Runs a second connection per partition when set.
+    pub secondary_feed: Option<SecondaryFeedConfig>,
+    /// How often the exchange clock offset is re-estimated by `sync_clock`.
+    pub clock_sync_interval: time::Duration,
+}
+
+impl Default for BookTickerStreamConfig {
+    fn default() -> Self {
+        BookTickerStreamConfig {
+            url: BOOKTICKER_WS_URL.to_string(),
+            reconnect: ReconnectPolicy::default(),
+            rotation: RotationPolicy::default(),
+            partitioner: PartitionerConfig::default(),
+            staleness: StalenessConfig::default(),
+            secondary_feed: None,
+            clock_sync_interval: time::Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedStatus {
+    pub feed: Feed,
+    pub url: String,
+    pub reconnect: ReconnectStatus,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStatus {
+    pub id: usize,
+    pub symbols: Vec<String>,
+    pub feeds: Vec<FeedStatus>,
+    /// Time since the newest quote of any of the connection's symbols,
+    /// `None` before the first one.
+    pub last_message_age_ms: Option<u64>,
+}
+
 #[derive(Debug, Clone)]
 pub struct BookTickerStream {
     pub book_ticker: Arc<tokio::sync::Mutex<HashMap<String, BestPrices>>>,
+    subscribers: Arc<tokio::sync::Mutex<Vec<Subscriber>>>,
+    config: BookTickerStreamConfig,
+    connections: Arc<tokio::sync::Mutex<Vec<ConnectionHandle>>>,
+    /// Recent messages per second by symbol, used to balance connections.
+    symbol_rates: Arc<std::sync::Mutex<HashMap<String, f64>>>,
+    staleness: StalenessTracker,
+    feed_stats: FeedStats,
+    router: StreamRouter<BookTicker>,
+    frame_errors: FrameErrorReporter,
+    clock: ClockOffset,
+    latency: LatencyTracker,
+    /// Same samples as `latency`, windowed by `take_interval_latency`.
+    interval_latency: LatencyTracker,
+    journal: Option<Journal>,
+    quotes: broadcast::Sender<Arc<AcceptedQuote>>,
+    task_failures: mpsc::UnboundedSender<ConnectionTaskError>,
+    /// Connection tasks that died, reported by `listen_all_coins_bookticker`.
+    failed_tasks: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<ConnectionTaskError>>>,
 }
 
 impl Default for BookTickerStream {
@@ -54,128 +193,604 @@ impl Default for BookTickerStream {
 
 impl BookTickerStream {
     pub fn new() -> Self {
+        Self::with_config(BookTickerStreamConfig::default())
+    }
+
+    pub fn with_config(config: BookTickerStreamConfig) -> Self {
+        let (task_failures, failed_tasks) = mpsc::unbounded_channel();
         BookTickerStream {
+            staleness: StalenessTracker::new(&config.staleness),
             book_ticker: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            subscribers: Arc::new(tokio::sync::Mutex::new(Vec::new())),
+            config,
+            connections: Arc::new(tokio::sync::Mutex::new(Vec::new())),
+            symbol_rates: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            feed_stats: FeedStats::default(),
+            router: StreamRouter::new().on("bookTicker", |ticker: BookTicker| ticker),
+            frame_errors: FrameErrorReporter::new("Book Ticker", FRAME_ERROR_LOG_INTERVAL),
+            clock: ClockOffset::default(),
+            latency: LatencyTracker::default(),
+            interval_latency: LatencyTracker::default(),
+            journal: None,
+            quotes: broadcast::channel(QUOTE_CAPACITY).0,
+            task_failures,
+            failed_tasks: Arc::new(tokio::sync::Mutex::new(failed_tasks)),
         }
     }
 
-    pub async fn listen_one_coin_bookticker(
-        &self,
-        url: &String,
-    ) -> Result<(), Box<dyn std::error::Error + Send>> {
+    /// Records every frame received, before it is decoded, to `journal`.
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Symbols whose last quote is older than their staleness threshold.
+    pub async fn stale_symbols(&self) -> Vec<StaleSymbol> {
+        let book_ticker = self.book_ticker.lock().await;
+        self.staleness
+            .stale_symbols(&book_ticker, current_time_millis())
+    }
+
+    /// Events published when a symbol goes stale or recovers. Requires
+    /// `monitor_staleness` to be running.
+    pub fn staleness_events(&self) -> broadcast::Receiver<StalenessEvent> {
+        self.staleness.subscribe()
+    }
+
+    /// Accepts quotes for `symbol` without subscribing to it, for frames
+    /// fed to `handle_text` by something other than a connection, e.g. a
+    /// journal replay.
+    pub fn track_symbol(&self, symbol: &str) {
+        self.staleness.track(symbol, current_time_millis());
+    }
+
+    pub fn set_staleness_threshold(&self, symbol: &str, threshold: time::Duration) {
+        self.staleness.set_threshold(symbol, threshold);
+    }
+
+    pub fn clear_staleness_threshold(&self, symbol: &str) {
+        self.staleness.clear_threshold(symbol);
+    }
+
+    /// Checks quote ages every `check_interval`, publishing staleness events.
+    pub async fn monitor_staleness(&self) {
+        let mut interval = time::interval(self.config.staleness.check_interval);
         loop {
-            let (ws_stream, _) = match connect_async(url).await {
-                Ok(stream) => {
-                    info!("Listen to Book Ticker Stream");
-                    stream
+            interval.tick().await;
+            let book_ticker = self.book_ticker.lock().await;
+            self.staleness.check(&book_ticker, current_time_millis());
+        }
+    }
+
+    /// Reconnect state of every feed, keyed by connection id and feed.
+    pub async fn connection_health(&self) -> BTreeMap<(usize, Feed), ReconnectStatus> {
+        let connections = self.connections.lock().await;
+        connections
+            .iter()
+            .flat_map(|connection| {
+                connection
+                    .feeds
+                    .iter()
+                    .map(|feed| ((connection.id, feed.feed), feed.reconnector.status()))
+            })
+            .collect()
+    }
+
+    /// Symbols, reconnect state and last message age of every connection.
+    pub async fn connection_status(&self) -> Vec<ConnectionStatus> {
+        let connections: Vec<ConnectionHandle> = self.connections.lock().await.clone();
+        let book_ticker = self.book_ticker.lock().await;
+        let now = current_time_millis();
+        connections
+            .iter()
+            .map(|connection| {
+                let symbols = connection.symbols();
+                let last_received_at = symbols
+                    .iter()
+                    .filter_map(|symbol| book_ticker.get(symbol))
+                    .map(|prices| prices.received_at)
+                    .max();
+                ConnectionStatus {
+                    id: connection.id,
+                    feeds: connection
+                        .feeds
+                        .iter()
+                        .map(|feed| FeedStatus {
+                            feed: feed.feed,
+                            url: feed.url.clone(),
+                            reconnect: feed.reconnector.status(),
+                        })
+                        .collect(),
+                    last_message_age_ms: last_received_at
+                        .map(|received_at| now.saturating_sub(received_at)),
+                    symbols,
                 }
-                Err(e) => {
-                    info!("Failed to connect: {}, retrying...", e);
-                    continue; // Retry immediately without delay
+            })
+            .collect()
+    }
+
+    /// Win rate and latency of each feed, for spotting a degraded path.
+    pub fn feed_stats(&self) -> BTreeMap<Feed, FeedStatsSnapshot> {
+        self.feed_stats.snapshot()
+    }
+
+    /// Keeps the exchange clock offset used for latency measurement up to
+    /// date. Until the first sync, latencies assume the clocks agree.
+    pub async fn sync_clock(&self, client: AsyncBinanceClient) {
+        self.clock
+            .keep_synced(client, self.config.clock_sync_interval)
+            .await;
+    }
+
+    /// Latency percentiles per symbol and per connection feed since the
+    /// last `reset_latency`.
+    pub fn latency_report(&self) -> LatencyReport {
+        self.latency.report(self.clock.offset_ms())
+    }
+
+    pub fn reset_latency(&self) {
+        self.latency.reset();
+    }
+
+    /// Latency since the previous call, for periodic logging. Kept apart
+    /// from `latency_report` so logging does not reset its window.
+    pub fn take_interval_latency(&self) -> LatencyReport {
+        self.interval_latency.take_report(self.clock.offset_ms())
+    }
+
+    /// Counts of routed, control, unrouted and malformed frames.
+    pub fn router_stats(&self) -> RouterStatsSnapshot {
+        self.router.stats()
+    }
+
+    /// Control acks and frame errors seen on the book ticker connections.
+    pub fn frame_metrics(&self) -> FrameMetricsSnapshot {
+        self.frame_errors.snapshot()
+    }
+
+    /// Symbols assigned to every connection, keyed by connection id.
+    pub async fn connection_symbols(&self) -> BTreeMap<usize, Vec<String>> {
+        let connections = self.connections.lock().await;
+        connections
+            .iter()
+            .map(|connection| (connection.id, connection.symbols()))
+            .collect()
+    }
+
+    /// Streams each connection is subscribed to according to Binance's
+    /// `LIST_SUBSCRIPTIONS`, asking every connection at once. Connections
+    /// that are down or do not answer within `LIST_SUBSCRIPTIONS_TIMEOUT`
+    /// are left out.
+    pub async fn list_subscriptions(&self) -> BTreeMap<usize, Vec<String>> {
+        let connections: Vec<ConnectionHandle> = self.connections.lock().await.clone();
+        let replies = future::join_all(
+            connections
+                .iter()
+                .map(|connection| connection.list_subscriptions()),
+        )
+        .await;
+        connections
+            .iter()
+            .zip(replies)
+            .filter_map(|(connection, streams)| Some((connection.id, streams?)))
+            .collect()
+    }
+
+    /// Subscribes to book tickers for `symbols` at runtime. New symbols go
+    /// to the connection with the lowest message rate that has room, opening
+    /// connections as `max_streams_per_connection` is reached, and the
+    /// connections are rebalanced if the load has become uneven.
+    pub async fn add_symbols(&self, symbols: &[String]) {
+        let mut connections = self.connections.lock().await;
+        let partitioner = &self.config.partitioner;
+        let max_streams = partitioner.max_streams_per_connection.max(1);
+        let new_symbols: BTreeSet<String> = symbols
+            .iter()
+            .map(|symbol| symbol.to_uppercase())
+            .filter(|symbol| !connections.iter().any(|c| c.contains(symbol)))
+            .collect();
+        if new_symbols.is_empty() {
+            return;
+        }
+        let now = current_time_millis();
+        for symbol in &new_symbols {
+            self.staleness.track(symbol, now);
+        }
+        let rates = self.symbol_rates.lock().unwrap().clone();
+        let mut loads: Vec<f64> = connections
+            .iter()
+            .map(|connection| self.connection_load(connection, &rates))
+            .collect();
+        let mut additions: Vec<Vec<String>> = vec![Vec::new(); connections.len()];
+        for symbol in new_symbols {
+            let rate = self.symbol_rate(&symbol, &rates);
+            let target = (0..connections.len())
+                .filter(|index| {
+                    connections[*index].stream_count() + additions[*index].len() < max_streams
+                })
+                .min_by(|a, b| loads[*a].total_cmp(&loads[*b]));
+            let index = match target {
+                Some(index) => index,
+                None => {
+                    self.spawn_connection(&mut connections, Vec::new());
+                    loads.push(0.0);
+                    additions.push(Vec::new());
+                    connections.len() - 1
                 }
             };
-            let (mut write, mut read) = ws_stream.split();
-            while let Some(message) = read.next().await {
-                match message {
-                    Ok(Message::Text(text)) => {
-                        let ticker: StreamBookTicker =
-                            serde_json::from_str(&text).expect("JSON was not well format!");
-
-                        let bid: f64 = ticker
-                            .data
-                            .best_bid
-                            .parse::<f64>()
-                            .expect("Failed to parse as f64");
-                        let ask: f64 = ticker
-                            .data
-                            .best_ask
-                            .parse::<f64>()
-                            .expect("Failed to parse as f64");
-
-                        let mut book_ticker = self.book_ticker.lock().await;
-                        book_ticker.insert(ticker.data.symbol.clone(), BestPrices { bid, ask });
-                    }
-                    Ok(Message::Ping(payload)) => {
-                        if let Err(e) = write.send(Message::Pong(payload)).await {
-                            info!("Failed to send Pong response: {}", e);
-                        }
-                    }
-                    Ok(non_text_message) => {
-                        info!("Received Non Text Messages {:?}", non_text_message)
-                    }
-                    Err(e) => {
-                        info!("Error Message {}", e);
-                        break;
-                    }
+            loads[index] += rate;
+            additions[index].push(symbol);
+        }
+        for (connection, added) in connections.iter().zip(additions) {
+            connection.add_symbols(added);
+        }
+        self.rebalance_locked(&mut connections);
+    }
+
+    /// Moves symbols between connections when the busiest connection carries
+    /// more than `rebalance_threshold` times the average message rate.
+    pub async fn rebalance(&self) {
+        let mut connections = self.connections.lock().await;
+        self.rebalance_locked(&mut connections);
+    }
+
+    fn rebalance_locked(&self, connections: &mut Vec<ConnectionHandle>) {
+        let partitioner = &self.config.partitioner;
+        let rates = self.symbol_rates.lock().unwrap().clone();
+        let loads: Vec<f64> = connections
+            .iter()
+            .map(|connection| self.connection_load(connection, &rates))
+            .collect();
+        if imbalance(&loads) <= partitioner.rebalance_threshold {
+            return;
+        }
+        let current: Vec<BTreeSet<String>> = connections
+            .iter()
+            .map(|connection| connection.symbols().into_iter().collect())
+            .collect();
+        let symbol_rates: Vec<(String, f64)> = current
+            .iter()
+            .flatten()
+            .map(|symbol| (symbol.clone(), self.symbol_rate(symbol, &rates)))
+            .collect();
+        let groups = partition_symbols(
+            &symbol_rates,
+            connections.len(),
+            partitioner.max_streams_per_connection,
+        );
+        while connections.len() < groups.len() {
+            self.spawn_connection(connections, Vec::new());
+        }
+        let mut current = current;
+        current.resize(connections.len(), BTreeSet::new());
+        let targets = match_groups(&current, &groups);
+
+        // Subscribe commands for moved symbols are queued before the
+        // unsubscribes, but neither waits for Binance's ack, so a moved
+        // symbol can briefly be missing or arrive on both connections.
+        let mut moved = 0;
+        for (group, index) in groups.iter().zip(&targets) {
+            let added: Vec<String> = group
+                .iter()
+                .filter(|symbol| !current[*index].contains(*symbol))
+                .cloned()
+                .collect();
+            moved += added.len();
+            connections[*index].add_symbols(added);
+        }
+        for (group, index) in groups.iter().zip(&targets) {
+            let removed: Vec<String> = current[*index]
+                .iter()
+                .filter(|symbol| !group.contains(*symbol))
+                .cloned()
+                .collect();
+            connections[*index].remove_symbols(removed);
+        }
+        info!(
+            "Rebalanced Book Ticker connections, moved {} symbols (imbalance {:.2})",
+            moved,
+            imbalance(&loads)
+        );
+    }
+
+    fn symbol_rate(&self, symbol: &str, rates: &HashMap<String, f64>) -> f64 {
+        rates
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.config.partitioner.default_symbol_rate)
+    }
+
+    fn connection_load(&self, connection: &ConnectionHandle, rates: &HashMap<String, f64>) -> f64 {
+        connection
+            .symbols()
+            .iter()
+            .map(|symbol| self.symbol_rate(symbol, rates))
+            .sum()
+    }
+
+    /// Unsubscribes `symbols` from whichever connections carry them.
+    pub async fn remove_symbols(&self, symbols: &[String]) {
+        let symbols: Vec<String> = symbols.iter().map(|symbol| symbol.to_uppercase()).collect();
+        let connections = self.connections.lock().await;
+        for connection in connections.iter() {
+            let owned: Vec<String> = symbols
+                .iter()
+                .filter(|symbol| connection.contains(symbol))
+                .cloned()
+                .collect();
+            connection.remove_symbols(owned);
+        }
+        let mut book_ticker = self.book_ticker.lock().await;
+        let mut rates = self.symbol_rates.lock().unwrap();
+        for symbol in &symbols {
+            book_ticker.remove(symbol);
+            rates.remove(symbol);
+            self.staleness.remove(symbol);
+        }
+    }
+
+    fn spawn_connection(&self, connections: &mut Vec<ConnectionHandle>, symbols: Vec<String>) {
+        let mut feeds = vec![(Feed::A, self.config.url.clone())];
+        if let Some(secondary) = &self.config.secondary_feed {
+            feeds.push((Feed::B, secondary.url.clone()));
+        }
+        let driver = ConnectionDriver {
+            name: "Book Ticker".to_string(),
+            stream_name: bookticker_stream_name,
+            feeds,
+            reconnect: self.config.reconnect.clone(),
+            rotation: self.config.rotation.clone(),
+            handler: self.clone(),
+            failures: Some(self.task_failures.clone()),
+        };
+        connections.push(driver.spawn(connections.len(), symbols));
+    }
+
+    /// Subscribes to every book ticker update accepted into the store,
+    /// instead of polling `book_ticker`.
+    pub async fn subscribe(&self, options: SubscriptionOptions) -> BookTickerSubscription {
+        let (subscriber, subscription) = Subscriber::new(options);
+        self.subscribers.lock().await.push(subscriber);
+        subscription
+    }
+
+    /// Every quote accepted into the store, with the prices and receive
+    /// time stored for it. Receivers that fall `QUOTE_CAPACITY` quotes
+    /// behind lag.
+    pub fn quotes(&self) -> broadcast::Receiver<Arc<AcceptedQuote>> {
+        self.quotes.subscribe()
+    }
+
+    /// Hands `ticker` to the subscribers, allocating an owned copy only
+    /// when there are any.
+    async fn publish(&self, ticker: &BookTickerRef<'_>) {
+        let mut subscribers = self.subscribers.lock().await;
+        if subscribers.is_empty() {
+            return;
+        }
+        let ticker = ticker.to_owned();
+        subscribers.retain(|subscriber| subscriber.offer(&ticker));
+    }
+
+    /// Folds the messages counted since the last sample into the per-symbol
+    /// message rates. Symbols of the connection that sent nothing decay
+    /// toward 0, so a quiet symbol stops weighing on its connection.
+    fn record_rates(&self, state: &mut ConnectionState, period: time::Duration) {
+        let counts = std::mem::take(&mut state.message_counts);
+        // Both feeds carry every update, so feed A's counts alone give the
+        // rate; folding in feed B's would sample each symbol twice.
+        if state.feed != Feed::A {
+            return;
+        }
+        let seconds = period.as_secs_f64();
+        let mut rates = self.symbol_rates.lock().unwrap();
+        for symbol in state.symbols() {
+            let count = counts.get(&symbol).copied().unwrap_or(0);
+            let sample = count as f64 / seconds;
+            match rates.get_mut(&symbol) {
+                Some(rate) => *rate += RATE_SMOOTHING * (sample - *rate),
+                // Unmeasured symbols keep `default_symbol_rate` until their
+                // first message.
+                None if count > 0 => {
+                    rates.insert(symbol, sample);
                 }
+                None => {}
+            }
+        }
+    }
+
+    /// Applies one frame. Book ticker frames take the borrowed decoding
+    /// path; anything else, or a frame it cannot decode, goes through the
+    /// router.
+    async fn apply_frame(&self, text: &str, state: &mut ConnectionState) -> Result<(), FrameError> {
+        let frame_received = time::Instant::now();
+        let received_at = current_time_millis();
+        let connection = (state.connection_id, state.feed);
+        let frame = decode_book_ticker(text, &mut state.scratch);
+        if let Some(frame) = frame.filter(|frame| frame.stream.ends_with("@bookTicker")) {
+            return self
+                .apply_ticker(
+                    &frame.data,
+                    connection,
+                    received_at,
+                    frame_received,
+                    &mut state.message_counts,
+                )
+                .await;
+        }
+        match self.router.route(text) {
+            Routed::Message(ticker) => {
+                let ticker = BookTickerRef::from(&ticker);
+                self.apply_ticker(
+                    &ticker,
+                    connection,
+                    received_at,
+                    frame_received,
+                    &mut state.message_counts,
+                )
+                .await
             }
-            info!("Book Ticker Connection lost, retrying immediately...");
-            continue;
+            Routed::Control(response) => {
+                state.handle_response(response)?;
+                self.frame_errors.record_ack();
+                Ok(())
+            }
+            Routed::Unrouted(stream) => Err(FrameError::Unrouted(stream)),
+            Routed::Malformed(reason) => Err(FrameError::Malformed(reason)),
         }
     }
 
-    pub async fn listen_all_coins_bookticker(
+    async fn apply_ticker(
         &self,
-        names: Vec<String>,
-        parition: usize,
-    ) -> Result<(), Box<dyn std::error::Error + Send>> {
-        let urls = generate_bookticker_url_in_n_pieces(names, parition);
-        let mut tasks = vec![];
-        for url in urls {
-            let self_clone = self.clone();
-            // let book_ticker_clone = Arc::clone(&self.book_ticker);
-            tasks.push(tokio::spawn(async move {
-                if let Err(e) = self_clone.listen_one_coin_bookticker(&url).await {
-                    info!(
-                        "Unable to connect the websockets stream URL {:?} {:?}",
-                        &url, e
-                    );
-                };
-            }))
-        }
-        for task in tasks {
-            let _ = task.await;
+        ticker: &BookTickerRef<'_>,
+        connection: (usize, Feed),
+        received_at: u64,
+        frame_received: time::Instant,
+        message_counts: &mut HashMap<String, u64>,
+    ) -> Result<(), FrameError> {
+        let quote = ticker.quote(received_at)?;
+        let latency_ms = self.clock.to_exchange_time(received_at) - ticker.event_time as i64;
+        let published = (self.quotes.receiver_count() > 0).then(|| quote.clone());
+        let won = {
+            let mut book_ticker = self.book_ticker.lock().await;
+            // `remove_symbols` drops a symbol under this lock, so a frame
+            // still in flight after it cannot put the quote back.
+            if !self.staleness.is_tracked(ticker.symbol) {
+                return Ok(());
+            }
+            apply_quote(&mut book_ticker, ticker.symbol, quote)
+        };
+        // With redundant feeds the first arrival of an update wins and the
+        // copy from the other feed is dropped here.
+        self.feed_stats
+            .record(connection.1, won, latency_ms.max(0) as u64);
+        match message_counts.get_mut(ticker.symbol) {
+            Some(count) => *count += 1,
+            None => {
+                message_counts.insert(ticker.symbol.to_string(), 1);
+            }
+        }
+        if !won {
+            return Ok(());
+        }
+        // Only the winning copy reached the book, so only it has a store
+        // latency worth reporting.
+        let receive_to_store = frame_received.elapsed();
+        for latency in [&self.latency, &self.interval_latency] {
+            latency.record(ticker.symbol, connection, latency_ms, receive_to_store);
+        }
+        self.staleness.on_quote(ticker.symbol, received_at);
+        if let Some(prices) = published {
+            let _ = self.quotes.send(Arc::new(AcceptedQuote {
+                symbol: ticker.symbol.to_string(),
+                prices,
+            }));
         }
+        self.publish(ticker).await;
         Ok(())
     }
 
+    /// Subscribes to `names`, opening as many connections as the stream
+    /// limits require, and keeps rebalancing them by message rate. Returns
+    /// only when a connection task fails, leaving its symbols unserved.
+    pub async fn listen_all_coins_bookticker(&self, names: Vec<String>) -> ConnectionTaskError {
+        let mut failures = self.failed_tasks.lock().await;
+        self.add_symbols(&names).await;
+        let mut rebalance = time::interval_at(
+            time::Instant::now() + self.config.partitioner.rebalance_interval,
+            self.config.partitioner.rebalance_interval,
+        );
+        loop {
+            tokio::select! {
+                _ = rebalance.tick() => self.rebalance().await,
+                Some(failure) = failures.recv() => return failure,
+            }
+        }
+    }
+
     pub async fn show_bookticker(&self) {
         loop {
             time::sleep(time::Duration::new(1800, 0)).await;
             let book_ticker = self.book_ticker.lock().await;
             info!("Current Book Ticker:");
             for (symbol, prices) in book_ticker.iter() {
-                info!("{}: Bid: {}, Ask: {}", symbol, prices.bid, prices.ask);
+                info!(
+                    "{}: Bid: {} x {}, Ask: {} x {}, update {}",
+                    symbol,
+                    prices.bid,
+                    prices.bid_qty,
+                    prices.ask,
+                    prices.ask_qty,
+                    prices.update_id
+                );
             }
         }
     }
 }
 
-fn create_websocket_url(coin_names: &[String]) -> String {
-    let streams: Vec<String> = coin_names
-        .iter()
-        .map(|coin| format!("{}@bookTicker", coin.to_lowercase()))
-        .collect();
-    format!(
-        "wss://fstream.binance.com/stream?streams={}",
-        streams.join("/")
-    )
+impl FrameHandler for BookTickerStream {
+    async fn handle_text(&self, text: &str, state: &mut ConnectionState) {
+        if let Some(journal) = &self.journal {
+            journal.record(
+                JournalSource::BookTicker,
+                state.connection_id,
+                state.feed,
+                text,
+            );
+        }
+        if let Err(e) = self.apply_frame(text, state).await {
+            self.frame_errors.record(&e);
+        }
+    }
+
+    fn on_tick(&self, state: &mut ConnectionState, period: time::Duration) {
+        self.record_rates(state, period);
+    }
 }
 
-fn generate_bookticker_url_in_n_pieces(coin_names: Vec<String>, n: usize) -> Vec<String> {
-    let total_length = coin_names.len();
-    let piece_size = total_length / n;
-    let remainder = total_length % n;
-    let mut urls = Vec::new();
-    let mut start = 0;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(update_id: u64, bid: f64) -> BestPrices {
+        BestPrices {
+            bid,
+            bid_qty: 1.0,
+            ask: bid + 1.0,
+            ask_qty: 1.0,
+            update_id,
+            trans_time: 0,
+            event_time: 0,
+            received_at: 0,
+        }
+    }
+
+    #[test]
+    fn stores_the_first_quote_for_a_symbol() {
+        let mut book_ticker = HashMap::new();
+        assert!(apply_quote(&mut book_ticker, "BTCUSDT", quote(5, 100.0)));
+        assert_eq!(book_ticker["BTCUSDT"].update_id, 5);
+    }
 
-    for i in 0..n {
-        let current_piece_size = piece_size + if i < remainder { 1 } else { 0 };
-        let end = start + current_piece_size;
-        let piece = &coin_names[start..end];
-        urls.push(create_websocket_url(piece.to_vec().as_slice()));
-        start = end; // Update the start index for the next piece
+    #[test]
+    fn keeps_the_stored_quote_over_older_and_equal_updates() {
+        let mut book_ticker = HashMap::new();
+        apply_quote(&mut book_ticker, "BTCUSDT", quote(5, 100.0));
+        assert!(!apply_quote(&mut book_ticker, "BTCUSDT", quote(4, 99.0)));
+        assert!(!apply_quote(&mut book_ticker, "BTCUSDT", quote(5, 101.0)));
+        let stored = &book_ticker["BTCUSDT"];
+        assert_eq!((stored.update_id, stored.bid), (5, 100.0));
     }
 
-    urls
+    #[test]
+    fn replaces_the_stored_quote_with_a_newer_update() {
+        let mut book_ticker = HashMap::new();
+        apply_quote(&mut book_ticker, "BTCUSDT", quote(5, 100.0));
+        apply_quote(&mut book_ticker, "ETHUSDT", quote(9, 10.0));
+        assert!(apply_quote(&mut book_ticker, "BTCUSDT", quote(6, 102.0)));
+        let stored = &book_ticker["BTCUSDT"];
+        assert_eq!((stored.update_id, stored.bid), (6, 102.0));
+        // Update ids are per symbol.
+        assert_eq!(book_ticker["ETHUSDT"].update_id, 9);
+    }
 }
@@ -0,0 +1,201 @@
+use crate::bookticker_stream::bookticker::{BestPrices, BookTicker};
+use crate::websocket::frame_errors::FrameError;
+use serde::Deserialize;
+
+/// `StreamBookTicker` borrowing its strings from the frame, so decoding a
+/// tick allocates nothing. Binance never escapes these fields; a frame that
+/// did would fail to decode and fall back to the owned path.
+#[derive(Deserialize, Debug)]
+pub struct StreamBookTickerRef<'a> {
+    #[serde(borrow)]
+    pub stream: &'a str,
+    #[serde(borrow)]
+    pub data: BookTickerRef<'a>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BookTickerRef<'a> {
+    #[serde(rename = "e", borrow)]
+    pub event: &'a str,
+    #[serde(rename = "u")]
+    pub update_id: u64,
+    #[serde(rename = "s", borrow)]
+    pub symbol: &'a str,
+    #[serde(rename = "b", borrow)]
+    pub best_bid: &'a str,
+    #[serde(rename = "B", borrow)]
+    pub bid_qty: &'a str,
+    #[serde(rename = "a", borrow)]
+    pub best_ask: &'a str,
+    #[serde(rename = "A", borrow)]
+    pub ask_qty: &'a str,
+    #[serde(rename = "T")]
+    pub trans_time: u64,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+}
+
+impl BookTickerRef<'_> {
+    /// Parses the prices straight from the frame into a stored quote.
+    pub fn quote(&self, received_at: u64) -> Result<BestPrices, FrameError> {
+        let parse = |field: &'static str, value: &str| {
+            value.parse::<f64>().map_err(|_| FrameError::InvalidNumber {
+                symbol: self.symbol.to_string(),
+                field,
+                value: value.to_string(),
+            })
+        };
+        Ok(BestPrices {
+            bid: parse("bid", self.best_bid)?,
+            bid_qty: parse("bid_qty", self.bid_qty)?,
+            ask: parse("ask", self.best_ask)?,
+            ask_qty: parse("ask_qty", self.ask_qty)?,
+            update_id: self.update_id,
+            trans_time: self.trans_time,
+            event_time: self.event_time,
+            received_at,
+        })
+    }
+
+    pub fn to_owned(&self) -> BookTicker {
+        BookTicker {
+            event: self.event.to_string(),
+            update_id: self.update_id,
+            symbol: self.symbol.to_string(),
+            best_bid: self.best_bid.to_string(),
+            bid_qty: self.bid_qty.to_string(),
+            best_ask: self.best_ask.to_string(),
+            ask_qty: self.ask_qty.to_string(),
+            trans_time: self.trans_time,
+            event_time: self.event_time,
+        }
+    }
+}
+
+impl<'a> From<&'a BookTicker> for BookTickerRef<'a> {
+    fn from(ticker: &'a BookTicker) -> Self {
+        BookTickerRef {
+            event: &ticker.event,
+            update_id: ticker.update_id,
+            symbol: &ticker.symbol,
+            best_bid: &ticker.best_bid,
+            bid_qty: &ticker.bid_qty,
+            best_ask: &ticker.best_ask,
+            ask_qty: &ticker.ask_qty,
+            trans_time: ticker.trans_time,
+            event_time: ticker.event_time,
+        }
+    }
+}
+
+/// Decodes a book ticker frame without allocating. `buffer` is scratch space
+/// reused across calls; it is only needed by the `simd-json` parser, which
+/// decodes in place.
+#[cfg(not(feature = "simd-json"))]
+pub fn decode_book_ticker<'a>(
+    text: &'a str,
+    _buffer: &'a mut Vec<u8>,
+) -> Option<StreamBookTickerRef<'a>> {
+    serde_json::from_str(text).ok()
+}
+
+/// Decodes a book ticker frame with `simd-json`. The frame is copied into
+/// `buffer`, which is reused across calls, because the parser works in place.
+#[cfg(feature = "simd-json")]
+pub fn decode_book_ticker<'a>(
+    text: &str,
+    buffer: &'a mut Vec<u8>,
+) -> Option<StreamBookTickerRef<'a>> {
+    buffer.clear();
+    buffer.extend_from_slice(text.as_bytes());
+    simd_json::serde::from_slice(buffer).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookticker_stream::bookticker::StreamBookTicker;
+
+    const FRAME: &str = r#"{"stream":"btcusdt@bookTicker","data":{"e":"bookTicker","u":400900217,"s":"BTCUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000","T":1568014460891,"E":1568014460893}}"#;
+
+    fn assert_parity(text: &str) {
+        let owned = serde_json::from_str::<StreamBookTicker>(text).unwrap();
+        let mut buffer = Vec::new();
+        let borrowed = decode_book_ticker(text, &mut buffer).unwrap();
+        assert_eq!(borrowed.stream, owned.stream);
+        assert_eq!(
+            format!("{:?}", borrowed.data.to_owned()),
+            format!("{:?}", owned.data)
+        );
+
+        let quote = borrowed.data.quote(7).unwrap();
+        let price = |value: &str| value.parse::<f64>().unwrap();
+        assert_eq!(quote.bid, price(&owned.data.best_bid));
+        assert_eq!(quote.bid_qty, price(&owned.data.bid_qty));
+        assert_eq!(quote.ask, price(&owned.data.best_ask));
+        assert_eq!(quote.ask_qty, price(&owned.data.ask_qty));
+        assert_eq!(quote.update_id, owned.data.update_id);
+        assert_eq!(quote.trans_time, owned.data.trans_time);
+        assert_eq!(quote.event_time, owned.data.event_time);
+        assert_eq!(quote.received_at, 7);
+    }
+
+    #[test]
+    fn decodes_book_tickers_like_the_owned_path() {
+        assert_parity(FRAME);
+        // Field order and whitespace do not matter.
+        assert_parity(
+            r#"{ "data": { "E": 2, "T": 1, "A": "4", "a": "3", "B": "2", "b": "1", "s": "ETHUSDT", "u": 5, "e": "bookTicker" }, "stream": "ethusdt@bookTicker" }"#,
+        );
+    }
+
+    #[test]
+    fn escaped_fields_fall_back_to_the_owned_path() {
+        let text = FRAME.replace(r#""s":"BTCUSDT""#, r#""s":"\u0042TCUSDT""#);
+        let owned = serde_json::from_str::<StreamBookTicker>(&text).unwrap();
+        assert_eq!(owned.data.symbol, "BTCUSDT");
+        let mut buffer = Vec::new();
+        // serde_json cannot borrow an escaped string; simd-json unescapes in
+        // place and borrows the result. Either way nothing decodes wrongly.
+        let decoded =
+            decode_book_ticker(&text, &mut buffer).map(|frame| frame.data.symbol.to_string());
+        if cfg!(feature = "simd-json") {
+            assert_eq!(decoded.as_deref(), Some(owned.data.symbol.as_str()));
+        } else {
+            assert_eq!(decoded, None);
+        }
+    }
+
+    #[test]
+    fn other_frames_are_not_book_tickers() {
+        let mut buffer = Vec::new();
+        for text in [
+            r#"{"result":null,"id":1}"#,
+            r#"{"stream":"btcusdt@aggTrade","data":{"e":"aggTrade","s":"BTCUSDT","p":"1","q":"2","T":1,"E":2}}"#,
+            r#"{"stream":"btcusdt@bookTicker","data":{"e":"bookTicker"}}"#,
+            "not json",
+        ] {
+            assert!(decode_book_ticker(text, &mut buffer).is_none(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_prices_that_are_not_numbers() {
+        let text = FRAME.replace("25.36520000", "n/a");
+        let mut buffer = Vec::new();
+        let borrowed = decode_book_ticker(&text, &mut buffer).unwrap();
+        match borrowed.data.quote(0) {
+            Err(FrameError::InvalidNumber {
+                symbol,
+                field,
+                value,
+            }) => {
+                assert_eq!(
+                    (symbol.as_str(), field, value.as_str()),
+                    ("BTCUSDT", "ask", "n/a")
+                );
+            }
+            other => panic!("expected an invalid number, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,6 @@
+/// Combined-stream endpoint; streams are added with `SUBSCRIBE` after connecting.
+pub const BOOKTICKER_WS_URL: &str = "wss://fstream.binance.com/stream";
+
+pub fn bookticker_stream_name(symbol: &str) -> String {
+    format!("{}@bookTicker", symbol.to_lowercase())
+}
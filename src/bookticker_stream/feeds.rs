@@ -0,0 +1,145 @@
+use crate::bookticker_stream::connection::BOOKTICKER_WS_URL;
+use crate::websocket::feed::Feed;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+/// Weight of the newest sample in `recent_latency_ms`.
+const LATENCY_SMOOTHING: f64 = 0.05;
+
+/// Second, independent connection per partition. Both feeds carry the same
+/// symbols and the first arrival of each `update_id` wins.
+#[derive(Debug, Clone)]
+pub struct SecondaryFeedConfig {
+    /// Endpoint for feed B; may differ from feed A's to use another path.
+    pub url: String,
+}
+
+impl Default for SecondaryFeedConfig {
+    fn default() -> Self {
+        SecondaryFeedConfig {
+            url: BOOKTICKER_WS_URL.to_string(),
+        }
+    }
+}
+
+impl SecondaryFeedConfig {
+    /// Enabled when `BOOKTICKER_FEED_B_URL` is set.
+    pub fn from_env() -> Option<Self> {
+        env::var("BOOKTICKER_FEED_B_URL")
+            .ok()
+            .map(|url| SecondaryFeedConfig { url })
+    }
+}
+
+#[derive(Debug, Default)]
+struct FeedCounters {
+    messages: u64,
+    wins: u64,
+    latency_total_ms: u64,
+    recent_latency_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedStatsSnapshot {
+    pub messages: u64,
+    /// Updates this feed delivered first.
+    pub wins: u64,
+    pub win_rate: f64,
+    /// Mean of receive time, on the exchange clock, minus event time.
+    pub mean_latency_ms: f64,
+    /// Exponentially smoothed latency, reacting faster than the mean.
+    pub recent_latency_ms: f64,
+}
+
+/// Per-feed arbitration statistics. A feed whose win rate drops or whose
+/// latency rises relative to the other is on a degraded path.
+#[derive(Debug, Clone, Default)]
+pub struct FeedStats {
+    counters: Arc<Mutex<BTreeMap<Feed, FeedCounters>>>,
+}
+
+impl FeedStats {
+    pub fn record(&self, feed: Feed, won: bool, latency_ms: u64) {
+        let mut counters = self.counters.lock().unwrap();
+        let counters = counters.entry(feed).or_default();
+        counters.messages += 1;
+        if won {
+            counters.wins += 1;
+        }
+        counters.latency_total_ms += latency_ms;
+        let latency = latency_ms as f64;
+        let recent = counters.recent_latency_ms.get_or_insert(latency);
+        *recent += LATENCY_SMOOTHING * (latency - *recent);
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<Feed, FeedStatsSnapshot> {
+        let counters = self.counters.lock().unwrap();
+        counters
+            .iter()
+            .map(|(feed, counters)| {
+                let messages = counters.messages.max(1) as f64;
+                let snapshot = FeedStatsSnapshot {
+                    messages: counters.messages,
+                    wins: counters.wins,
+                    win_rate: counters.wins as f64 / messages,
+                    mean_latency_ms: counters.latency_total_ms as f64 / messages,
+                    recent_latency_ms: counters.recent_latency_ms.unwrap_or_default(),
+                };
+                (*feed, snapshot)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_win_rate_per_feed() {
+        let stats = FeedStats::default();
+        for won in [true, true, false, true] {
+            stats.record(Feed::A, won, 10);
+        }
+        for won in [false, false, true, false] {
+            stats.record(Feed::B, won, 10);
+        }
+        let snapshot = stats.snapshot();
+        assert_eq!(
+            (snapshot[&Feed::A].messages, snapshot[&Feed::A].wins),
+            (4, 3)
+        );
+        assert_eq!(snapshot[&Feed::A].win_rate, 0.75);
+        assert_eq!(snapshot[&Feed::B].win_rate, 0.25);
+    }
+
+    #[test]
+    fn averages_latency_and_smooths_recent_latency() {
+        let stats = FeedStats::default();
+        assert!(stats.snapshot().is_empty());
+        stats.record(Feed::A, true, 10);
+        let snapshot = &stats.snapshot()[&Feed::A];
+        // The first sample seeds the smoothed latency.
+        assert_eq!(
+            (snapshot.mean_latency_ms, snapshot.recent_latency_ms),
+            (10.0, 10.0)
+        );
+
+        stats.record(Feed::A, true, 30);
+        let snapshot = &stats.snapshot()[&Feed::A];
+        assert_eq!(snapshot.mean_latency_ms, 20.0);
+        assert!((snapshot.recent_latency_ms - 11.0).abs() < 1e-9);
+
+        // A sustained rise moves the smoothed latency towards it faster than
+        // the mean, which still remembers the early samples.
+        for _ in 0..200 {
+            stats.record(Feed::A, true, 100);
+        }
+        let snapshot = &stats.snapshot()[&Feed::A];
+        assert!(snapshot.recent_latency_ms > 99.0);
+        assert!(snapshot.mean_latency_ms < 99.3);
+        assert!(!stats.snapshot().contains_key(&Feed::B));
+    }
+}
@@ -0,0 +1,237 @@
+use crate::websocket::feed::Feed;
+use hdrhistogram::Histogram;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Significant decimal digits kept by every histogram.
+const HISTOGRAM_PRECISION: u8 = 3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl LatencySummary {
+    fn from_histogram(histogram: &Histogram<u64>) -> Self {
+        LatencySummary {
+            count: histogram.len(),
+            p50: histogram.value_at_quantile(0.5),
+            p90: histogram.value_at_quantile(0.9),
+            p99: histogram.value_at_quantile(0.99),
+            max: histogram.max(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyStats {
+    /// Exchange event time to local receipt, corrected for clock offset, in
+    /// milliseconds.
+    pub exchange_to_receive_ms: LatencySummary,
+    /// Frame receipt to the quote being applied to the book, in microseconds.
+    pub receive_to_store_us: LatencySummary,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionLatency {
+    pub connection: usize,
+    pub feed: Feed,
+    pub latency: LatencyStats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyReport {
+    /// Exchange clock minus local clock when the report was taken.
+    pub clock_offset_ms: i64,
+    pub symbols: BTreeMap<String, LatencyStats>,
+    pub connections: Vec<ConnectionLatency>,
+}
+
+#[derive(Debug)]
+struct LatencyHistograms {
+    exchange_to_receive_ms: Histogram<u64>,
+    receive_to_store_us: Histogram<u64>,
+}
+
+impl LatencyHistograms {
+    fn new() -> Self {
+        let histogram = || {
+            Histogram::new(HISTOGRAM_PRECISION).expect("precision is within hdrhistogram's bounds")
+        };
+        LatencyHistograms {
+            exchange_to_receive_ms: histogram(),
+            receive_to_store_us: histogram(),
+        }
+    }
+
+    fn record(&mut self, exchange_to_receive_ms: u64, receive_to_store_us: u64) {
+        self.exchange_to_receive_ms
+            .saturating_record(exchange_to_receive_ms);
+        self.receive_to_store_us
+            .saturating_record(receive_to_store_us);
+    }
+
+    fn stats(&self) -> LatencyStats {
+        LatencyStats {
+            exchange_to_receive_ms: LatencySummary::from_histogram(&self.exchange_to_receive_ms),
+            receive_to_store_us: LatencySummary::from_histogram(&self.receive_to_store_us),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct LatencyState {
+    symbols: HashMap<String, LatencyHistograms>,
+    connections: BTreeMap<(usize, Feed), LatencyHistograms>,
+}
+
+impl LatencyState {
+    fn report(&self, clock_offset_ms: i64) -> LatencyReport {
+        LatencyReport {
+            clock_offset_ms,
+            symbols: self
+                .symbols
+                .iter()
+                .map(|(symbol, histograms)| (symbol.clone(), histograms.stats()))
+                .collect(),
+            connections: self
+                .connections
+                .iter()
+                .map(|((connection, feed), histograms)| ConnectionLatency {
+                    connection: *connection,
+                    feed: *feed,
+                    latency: histograms.stats(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Latency histograms per symbol and per connection feed. Clones share the
+/// histograms; `reset` starts a new window.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyTracker {
+    state: Arc<Mutex<LatencyState>>,
+}
+
+impl LatencyTracker {
+    /// Records one quote. A negative `exchange_to_receive_ms`, left over
+    /// from clock offset error, counts as zero.
+    pub fn record(
+        &self,
+        symbol: &str,
+        connection: (usize, Feed),
+        exchange_to_receive_ms: i64,
+        receive_to_store: Duration,
+    ) {
+        let exchange_to_receive_ms = exchange_to_receive_ms.max(0) as u64;
+        let receive_to_store_us = receive_to_store.as_micros() as u64;
+        let mut state = self.state.lock().unwrap();
+        match state.symbols.get_mut(symbol) {
+            Some(histograms) => histograms.record(exchange_to_receive_ms, receive_to_store_us),
+            None => {
+                let mut histograms = LatencyHistograms::new();
+                histograms.record(exchange_to_receive_ms, receive_to_store_us);
+                state.symbols.insert(symbol.to_string(), histograms);
+            }
+        }
+        state
+            .connections
+            .entry(connection)
+            .or_insert_with(LatencyHistograms::new)
+            .record(exchange_to_receive_ms, receive_to_store_us);
+    }
+
+    pub fn report(&self, clock_offset_ms: i64) -> LatencyReport {
+        self.state.lock().unwrap().report(clock_offset_ms)
+    }
+
+    /// Report of the current window, starting a new one without losing
+    /// samples recorded in between.
+    pub fn take_report(&self, clock_offset_ms: i64) -> LatencyReport {
+        let state = std::mem::take(&mut *self.state.lock().unwrap());
+        state.report(clock_offset_ms)
+    }
+
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.symbols.clear();
+        state.connections.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarises_percentiles() {
+        let tracker = LatencyTracker::default();
+        for latency_ms in 1..=100 {
+            tracker.record(
+                "BTCUSDT",
+                (0, Feed::A),
+                latency_ms,
+                Duration::from_micros(5),
+            );
+        }
+        let report = tracker.report(0);
+        let summary = &report.symbols["BTCUSDT"].exchange_to_receive_ms;
+        assert_eq!(
+            (
+                summary.count,
+                summary.p50,
+                summary.p90,
+                summary.p99,
+                summary.max
+            ),
+            (100, 50, 90, 99, 100)
+        );
+        let store = &report.symbols["BTCUSDT"].receive_to_store_us;
+        assert_eq!((store.p50, store.max), (5, 5));
+    }
+
+    #[test]
+    fn keys_histograms_by_symbol_and_connection_feed() {
+        let tracker = LatencyTracker::default();
+        tracker.record("BTCUSDT", (0, Feed::A), 10, Duration::ZERO);
+        tracker.record("BTCUSDT", (0, Feed::B), 20, Duration::ZERO);
+        tracker.record("ETHUSDT", (1, Feed::A), 30, Duration::ZERO);
+        // Negative latencies left over from clock offset error count as zero.
+        tracker.record("ETHUSDT", (1, Feed::A), -5, Duration::ZERO);
+        let report = tracker.report(-3);
+        assert_eq!(report.clock_offset_ms, -3);
+        assert_eq!(report.symbols["BTCUSDT"].exchange_to_receive_ms.count, 2);
+        let eth = &report.symbols["ETHUSDT"].exchange_to_receive_ms;
+        assert_eq!((eth.count, eth.p50, eth.max), (2, 0, 30));
+        let connections: Vec<_> = report
+            .connections
+            .iter()
+            .map(|c| (c.connection, c.feed, c.latency.exchange_to_receive_ms.count))
+            .collect();
+        assert_eq!(
+            connections,
+            [(0, Feed::A, 1), (0, Feed::B, 1), (1, Feed::A, 2)]
+        );
+    }
+
+    #[test]
+    fn take_report_starts_a_new_window() {
+        let tracker = LatencyTracker::default();
+        tracker.record("BTCUSDT", (0, Feed::A), 10, Duration::ZERO);
+        let report = tracker.take_report(0);
+        assert_eq!(report.symbols["BTCUSDT"].exchange_to_receive_ms.count, 1);
+        assert!(tracker.report(0).symbols.is_empty());
+
+        tracker.record("BTCUSDT", (0, Feed::A), 10, Duration::ZERO);
+        tracker.reset();
+        let report = tracker.report(0);
+        assert!(report.symbols.is_empty() && report.connections.is_empty());
+    }
+}
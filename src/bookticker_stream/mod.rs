@@ -1,2 +1,13 @@
+pub mod bar_db;
+pub mod bars;
 pub mod bookticker;
+pub mod borrowed;
+pub mod connection;
+pub mod feeds;
+pub mod latency;
+pub mod partitioner;
+pub mod staleness;
+pub mod subscription;
 pub mod ticker_db;
+pub mod ticker_query;
+pub mod ticker_writer;
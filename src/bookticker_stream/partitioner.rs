@@ -0,0 +1,191 @@
+use crate::websocket::control::MAX_STREAMS_PER_CONNECTION;
+use std::collections::{BTreeSet, HashMap};
+use tokio::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct PartitionerConfig {
+    pub max_streams_per_connection: usize,
+    /// Rebalance once the busiest connection carries this many times the
+    /// average message rate.
+    pub rebalance_threshold: f64,
+    pub rebalance_interval: Duration,
+    /// Messages per second assumed for a symbol before its rate is measured.
+    pub default_symbol_rate: f64,
+}
+
+impl Default for PartitionerConfig {
+    fn default() -> Self {
+        PartitionerConfig {
+            max_streams_per_connection: MAX_STREAMS_PER_CONNECTION,
+            rebalance_threshold: 1.5,
+            rebalance_interval: Duration::from_secs(900),
+            default_symbol_rate: 1.0,
+        }
+    }
+}
+
+/// Splits symbols into `connections` groups of at most `max_per_connection`,
+/// assigning the busiest symbols first to the least loaded group.
+pub fn partition_symbols(
+    symbol_rates: &[(String, f64)],
+    connections: usize,
+    max_per_connection: usize,
+) -> Vec<Vec<String>> {
+    let max_per_connection = max_per_connection.max(1);
+    let connections = connections.max(symbol_rates.len().div_ceil(max_per_connection));
+    let mut sorted: Vec<&(String, f64)> = symbol_rates.iter().collect();
+    sorted.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut groups: Vec<(Vec<String>, f64)> = vec![(Vec::new(), 0.0); connections];
+    for (symbol, rate) in sorted {
+        if let Some(group) = groups
+            .iter_mut()
+            .filter(|group| group.0.len() < max_per_connection)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+        {
+            group.0.push(symbol.clone());
+            group.1 += rate;
+        }
+    }
+    groups.into_iter().map(|(symbols, _)| symbols).collect()
+}
+
+/// Ratio of the busiest load to the average load; 1.0 is perfectly balanced.
+pub fn imbalance(loads: &[f64]) -> f64 {
+    let total: f64 = loads.iter().sum();
+    if loads.is_empty() || total <= 0.0 {
+        return 1.0;
+    }
+    let mean = total / loads.len() as f64;
+    loads.iter().cloned().fold(0.0, f64::max) / mean
+}
+
+/// Matches each target group to the current connection it overlaps most, so
+/// a rebalance moves as few symbols as possible. Returns, for each target
+/// group, the index of the connection it should be applied to.
+pub fn match_groups(current: &[BTreeSet<String>], targets: &[Vec<String>]) -> Vec<usize> {
+    let mut overlaps: Vec<(usize, usize, usize)> = Vec::new();
+    for (target_index, target) in targets.iter().enumerate() {
+        for (current_index, symbols) in current.iter().enumerate() {
+            let overlap = target.iter().filter(|s| symbols.contains(*s)).count();
+            overlaps.push((overlap, target_index, current_index));
+        }
+    }
+    overlaps.sort_by_key(|overlap| std::cmp::Reverse(overlap.0));
+
+    let mut assignment: HashMap<usize, usize> = HashMap::new();
+    let mut used: BTreeSet<usize> = BTreeSet::new();
+    for (_, target_index, current_index) in overlaps {
+        if assignment.contains_key(&target_index) || used.contains(&current_index) {
+            continue;
+        }
+        assignment.insert(target_index, current_index);
+        used.insert(current_index);
+    }
+    (0..targets.len())
+        .map(|target_index| {
+            assignment
+                .get(&target_index)
+                .copied()
+                .unwrap_or(target_index)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates(count: usize, rate: impl Fn(usize) -> f64) -> Vec<(String, f64)> {
+        (0..count)
+            .map(|index| (format!("SYM{:04}USDT", index), rate(index)))
+            .collect()
+    }
+
+    fn loads(groups: &[Vec<String>], symbol_rates: &[(String, f64)]) -> Vec<f64> {
+        let rates: HashMap<&str, f64> = symbol_rates
+            .iter()
+            .map(|(symbol, rate)| (symbol.as_str(), *rate))
+            .collect();
+        groups
+            .iter()
+            .map(|group| group.iter().map(|symbol| rates[symbol.as_str()]).sum())
+            .collect()
+    }
+
+    #[test]
+    fn opens_enough_connections_for_the_stream_limit() {
+        let symbol_rates = rates(450, |_| 1.0);
+        let groups = partition_symbols(&symbol_rates, 1, MAX_STREAMS_PER_CONNECTION);
+        assert_eq!(groups.len(), 3);
+        assert!(groups
+            .iter()
+            .all(|group| group.len() <= MAX_STREAMS_PER_CONNECTION));
+        let assigned: BTreeSet<&String> = groups.iter().flatten().collect();
+        assert_eq!(assigned.len(), 450);
+    }
+
+    #[test]
+    fn keeps_requested_connections_when_under_the_limit() {
+        let symbol_rates = rates(10, |_| 1.0);
+        let groups = partition_symbols(&symbol_rates, 4, MAX_STREAMS_PER_CONNECTION);
+        assert_eq!(groups.len(), 4);
+        assert_eq!(groups.iter().map(Vec::len).sum::<usize>(), 10);
+    }
+
+    #[test]
+    fn spreads_busy_symbols_by_rate() {
+        // Two very busy symbols and many quiet ones.
+        let symbol_rates = rates(300, |index| if index < 2 { 100.0 } else { 1.0 });
+        let groups = partition_symbols(&symbol_rates, 2, MAX_STREAMS_PER_CONNECTION);
+        assert!(groups
+            .iter()
+            .all(|group| group.contains(&"SYM0000USDT".to_string())
+                != group.contains(&"SYM0001USDT".to_string())));
+        assert!(imbalance(&loads(&groups, &symbol_rates)) < 1.05);
+    }
+
+    #[test]
+    fn rate_balance_never_exceeds_the_stream_limit() {
+        // One busy symbol would pull every quiet one onto the other
+        // connection if the stream limit were ignored.
+        let symbol_rates = rates(400, |index| if index == 0 { 1_000.0 } else { 1.0 });
+        let groups = partition_symbols(&symbol_rates, 2, MAX_STREAMS_PER_CONNECTION);
+        assert_eq!(
+            groups.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![200, 200]
+        );
+    }
+
+    #[test]
+    fn imbalance_of_busiest_to_average() {
+        assert_eq!(imbalance(&[]), 1.0);
+        assert_eq!(imbalance(&[0.0, 0.0]), 1.0);
+        assert_eq!(imbalance(&[10.0, 10.0]), 1.0);
+        assert_eq!(imbalance(&[30.0, 10.0]), 1.5);
+    }
+
+    #[test]
+    fn rebalance_keeps_groups_on_the_connection_they_overlap() {
+        let current: Vec<BTreeSet<String>> = vec![
+            ["A", "B", "C"].iter().map(|s| s.to_string()).collect(),
+            ["D", "E", "F"].iter().map(|s| s.to_string()).collect(),
+        ];
+        let targets = vec![
+            vec!["D".to_string(), "E".to_string(), "C".to_string()],
+            vec!["A".to_string(), "B".to_string(), "F".to_string()],
+        ];
+        assert_eq!(match_groups(&current, &targets), vec![1, 0]);
+    }
+
+    #[test]
+    fn rebalance_assigns_new_groups_to_new_connections() {
+        // Connections opened for the rebalance start out empty.
+        let current: Vec<BTreeSet<String>> = vec![
+            ["A", "B"].iter().map(|s| s.to_string()).collect(),
+            BTreeSet::new(),
+        ];
+        let targets = vec![vec!["C".to_string()], vec!["A".to_string()]];
+        assert_eq!(match_groups(&current, &targets), vec![1, 0]);
+    }
+}
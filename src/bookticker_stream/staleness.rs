@@ -0,0 +1,323 @@
+use crate::bookticker_stream::bookticker::BestPrices;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::time::Duration;
+use tracing::info;
+
+const STALENESS_EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub struct StalenessConfig {
+    /// Quotes not refreshed for this long are stale, unless the symbol has
+    /// its own threshold.
+    pub default_threshold: Duration,
+    /// Per-symbol thresholds, e.g. longer ones for illiquid symbols.
+    pub thresholds: HashMap<String, Duration>,
+    pub check_interval: Duration,
+}
+
+impl Default for StalenessConfig {
+    fn default() -> Self {
+        StalenessConfig {
+            default_threshold: Duration::from_secs(30),
+            thresholds: HashMap::new(),
+            check_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleSymbol {
+    pub symbol: String,
+    /// Milliseconds since the last quote was received, or since the symbol
+    /// was subscribed when it has never been quoted.
+    pub age_ms: u64,
+    pub threshold_ms: u64,
+    /// `None` until the first quote arrives.
+    pub last_event_time: Option<u64>,
+    pub last_received_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum StalenessEvent {
+    Stale(StaleSymbol),
+    Recovered {
+        symbol: String,
+        /// Milliseconds between the symbol going stale and its next quote.
+        stale_for_ms: u64,
+    },
+}
+
+/// Tracks which symbols have stopped updating. Clones share state, so the
+/// connection tasks can report recoveries while a monitor task checks ages.
+#[derive(Debug, Clone)]
+pub struct StalenessTracker {
+    default_threshold: Duration,
+    thresholds: Arc<Mutex<HashMap<String, Duration>>>,
+    /// Subscribed symbols, with the time they were subscribed.
+    subscribed: Arc<Mutex<HashMap<String, u64>>>,
+    /// Symbols currently reported stale, with the time they were reported.
+    stale: Arc<Mutex<HashMap<String, u64>>>,
+    events: broadcast::Sender<StalenessEvent>,
+}
+
+impl StalenessTracker {
+    pub fn new(config: &StalenessConfig) -> Self {
+        let (events, _) = broadcast::channel(STALENESS_EVENT_CAPACITY);
+        StalenessTracker {
+            default_threshold: config.default_threshold,
+            thresholds: Arc::new(Mutex::new(
+                config
+                    .thresholds
+                    .iter()
+                    .map(|(symbol, threshold)| (symbol.to_uppercase(), *threshold))
+                    .collect(),
+            )),
+            subscribed: Arc::new(Mutex::new(HashMap::new())),
+            stale: Arc::new(Mutex::new(HashMap::new())),
+            events,
+        }
+    }
+
+    pub fn threshold_for(&self, symbol: &str) -> Duration {
+        self.thresholds
+            .lock()
+            .unwrap()
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.default_threshold)
+    }
+
+    pub fn set_threshold(&self, symbol: &str, threshold: Duration) {
+        self.thresholds
+            .lock()
+            .unwrap()
+            .insert(symbol.to_uppercase(), threshold);
+    }
+
+    /// Reverts `symbol` to the default threshold.
+    pub fn clear_threshold(&self, symbol: &str) {
+        self.thresholds
+            .lock()
+            .unwrap()
+            .remove(&symbol.to_uppercase());
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<StalenessEvent> {
+        self.events.subscribe()
+    }
+
+    /// Starts watching `symbol`, subscribed at `now_ms`, so it goes stale
+    /// even if its first quote never arrives. Symbols already watched keep
+    /// their subscribe time.
+    pub fn track(&self, symbol: &str, now_ms: u64) {
+        self.subscribed
+            .lock()
+            .unwrap()
+            .entry(symbol.to_uppercase())
+            .or_insert(now_ms);
+    }
+
+    pub fn is_tracked(&self, symbol: &str) -> bool {
+        self.subscribed.lock().unwrap().contains_key(symbol)
+    }
+
+    /// Subscribed symbols not quoted within their threshold at `now_ms`,
+    /// oldest first. Quotes for symbols that are not subscribed are ignored.
+    pub fn stale_symbols(
+        &self,
+        quotes: &HashMap<String, BestPrices>,
+        now_ms: u64,
+    ) -> Vec<StaleSymbol> {
+        let subscribed = self.subscribed.lock().unwrap();
+        let mut stale: Vec<StaleSymbol> = subscribed
+            .iter()
+            .filter_map(|(symbol, subscribed_at)| {
+                let prices = quotes.get(symbol);
+                let since = prices.map_or(*subscribed_at, |prices| prices.received_at);
+                let age_ms = now_ms.saturating_sub(since);
+                let threshold_ms = self.threshold_for(symbol).as_millis() as u64;
+                (age_ms > threshold_ms).then(|| StaleSymbol {
+                    symbol: symbol.clone(),
+                    age_ms,
+                    threshold_ms,
+                    last_event_time: prices.map(|prices| prices.event_time),
+                    last_received_at: prices.map(|prices| prices.received_at),
+                })
+            })
+            .collect();
+        stale.sort_by(|a, b| {
+            b.age_ms
+                .cmp(&a.age_ms)
+                .then_with(|| a.symbol.cmp(&b.symbol))
+        });
+        stale
+    }
+
+    /// Publishes a `Stale` event for every symbol that went stale since the
+    /// last check.
+    pub fn check(&self, quotes: &HashMap<String, BestPrices>, now_ms: u64) {
+        let stale_symbols = self.stale_symbols(quotes, now_ms);
+        let mut stale = self.stale.lock().unwrap();
+        for symbol in stale_symbols {
+            if stale.contains_key(&symbol.symbol) {
+                continue;
+            }
+            info!(
+                "{} is stale, no quote for {} ms",
+                symbol.symbol, symbol.age_ms
+            );
+            stale.insert(symbol.symbol.clone(), now_ms);
+            let _ = self.events.send(StalenessEvent::Stale(symbol));
+        }
+    }
+
+    /// Records a fresh quote, publishing `Recovered` if `symbol` was stale.
+    pub fn on_quote(&self, symbol: &str, now_ms: u64) {
+        let Some(stale_since) = self.stale.lock().unwrap().remove(symbol) else {
+            return;
+        };
+        let stale_for_ms = now_ms.saturating_sub(stale_since);
+        info!("{} recovered after {} ms stale", symbol, stale_for_ms);
+        let _ = self.events.send(StalenessEvent::Recovered {
+            symbol: symbol.to_string(),
+            stale_for_ms,
+        });
+    }
+
+    /// Stops watching `symbol` after it is unsubscribed.
+    pub fn remove(&self, symbol: &str) {
+        self.subscribed.lock().unwrap().remove(symbol);
+        self.stale.lock().unwrap().remove(symbol);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(received_at: u64) -> BestPrices {
+        BestPrices {
+            bid: 1.0,
+            bid_qty: 1.0,
+            ask: 2.0,
+            ask_qty: 1.0,
+            update_id: 1,
+            trans_time: received_at,
+            event_time: received_at - 1,
+            received_at,
+        }
+    }
+
+    fn tracker() -> StalenessTracker {
+        StalenessTracker::new(&StalenessConfig {
+            default_threshold: Duration::from_secs(10),
+            thresholds: HashMap::from([("ethusdt".to_string(), Duration::from_secs(60))]),
+            ..Default::default()
+        })
+    }
+
+    fn symbols(stale: &[StaleSymbol]) -> Vec<&str> {
+        stale.iter().map(|symbol| symbol.symbol.as_str()).collect()
+    }
+
+    #[test]
+    fn reports_symbols_never_quoted_since_they_were_subscribed() {
+        let tracker = tracker();
+        tracker.track("btcusdt", 1_000);
+        assert!(tracker.stale_symbols(&HashMap::new(), 11_000).is_empty());
+        let stale = tracker.stale_symbols(&HashMap::new(), 11_001);
+        assert_eq!(symbols(&stale), ["BTCUSDT"]);
+        assert_eq!(stale[0].age_ms, 10_001);
+        assert_eq!(stale[0].last_received_at, None);
+
+        // Subscribing again keeps the original subscribe time.
+        tracker.track("BTCUSDT", 11_000);
+        assert_eq!(tracker.stale_symbols(&HashMap::new(), 11_001).len(), 1);
+    }
+
+    #[test]
+    fn ages_quoted_symbols_from_their_last_quote_with_their_own_threshold() {
+        let tracker = tracker();
+        for symbol in ["BTCUSDT", "ETHUSDT", "SOLUSDT"] {
+            tracker.track(symbol, 0);
+        }
+        let quotes = HashMap::from([
+            ("BTCUSDT".to_string(), quote(5_000)),
+            ("ETHUSDT".to_string(), quote(5_000)),
+            ("SOLUSDT".to_string(), quote(20_000)),
+        ]);
+        let stale = tracker.stale_symbols(&quotes, 30_000);
+        assert_eq!(symbols(&stale), ["BTCUSDT"]);
+        assert_eq!(stale[0].threshold_ms, 10_000);
+        assert_eq!(stale[0].last_event_time, Some(4_999));
+
+        // Oldest first once the longer threshold passes too.
+        let stale = tracker.stale_symbols(&quotes, 70_000);
+        assert_eq!(symbols(&stale), ["BTCUSDT", "ETHUSDT", "SOLUSDT"]);
+        assert_eq!(stale[1].threshold_ms, 60_000);
+
+        tracker.clear_threshold("ethusdt");
+        tracker.set_threshold("solusdt", Duration::from_secs(120));
+        let stale = tracker.stale_symbols(&quotes, 30_000);
+        assert_eq!(symbols(&stale), ["BTCUSDT", "ETHUSDT"]);
+    }
+
+    #[test]
+    fn ignores_quotes_for_symbols_that_are_not_subscribed() {
+        let tracker = tracker();
+        tracker.track("BTCUSDT", 0);
+        let quotes = HashMap::from([
+            ("BTCUSDT".to_string(), quote(1_000)),
+            ("XRPUSDT".to_string(), quote(1_000)),
+        ]);
+        assert_eq!(
+            symbols(&tracker.stale_symbols(&quotes, 60_000)),
+            ["BTCUSDT"]
+        );
+        assert!(!tracker.is_tracked("XRPUSDT"));
+
+        tracker.remove("BTCUSDT");
+        assert!(!tracker.is_tracked("BTCUSDT"));
+        assert!(tracker.stale_symbols(&quotes, 60_000).is_empty());
+    }
+
+    #[test]
+    fn publishes_stale_once_and_recovered_on_the_next_quote() {
+        let tracker = tracker();
+        let mut events = tracker.subscribe();
+        tracker.track("BTCUSDT", 0);
+        tracker.check(&HashMap::new(), 5_000);
+        assert!(events.try_recv().is_err());
+
+        tracker.check(&HashMap::new(), 15_000);
+        tracker.check(&HashMap::new(), 16_000);
+        match events.try_recv().unwrap() {
+            StalenessEvent::Stale(symbol) => {
+                assert_eq!((symbol.symbol.as_str(), symbol.age_ms), ("BTCUSDT", 15_000));
+            }
+            other => panic!("expected a stale event, got {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+
+        // Quotes for symbols that are not stale publish nothing.
+        tracker.on_quote("ETHUSDT", 17_000);
+        tracker.on_quote("BTCUSDT", 18_000);
+        match events.try_recv().unwrap() {
+            StalenessEvent::Recovered {
+                symbol,
+                stale_for_ms,
+            } => assert_eq!((symbol.as_str(), stale_for_ms), ("BTCUSDT", 3_000)),
+            other => panic!("expected a recovered event, got {:?}", other),
+        }
+        tracker.on_quote("BTCUSDT", 18_500);
+        assert!(events.try_recv().is_err());
+
+        // It can go stale again after recovering.
+        let quotes = HashMap::from([("BTCUSDT".to_string(), quote(18_000))]);
+        tracker.check(&quotes, 28_001);
+        assert!(matches!(events.try_recv(), Ok(StalenessEvent::Stale(_))));
+    }
+}
@@ -0,0 +1,123 @@
+use crate::bookticker_stream::bookticker::BookTicker;
+use futures::Stream;
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+pub const DEFAULT_SUBSCRIPTION_CAPACITY: usize = 1024;
+
+/// What to do when a subscriber's buffer is full and a new update arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Drop the new update and keep the subscription open.
+    DropNewest,
+    /// Close the subscription; the stream ends once the buffer is drained.
+    Disconnect,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscriptionOptions {
+    /// Symbols to receive, e.g. `BTCUSDT`. `None` subscribes to every symbol.
+    pub symbols: Option<HashSet<String>>,
+    pub capacity: usize,
+    pub lag_policy: LagPolicy,
+}
+
+impl Default for SubscriptionOptions {
+    fn default() -> Self {
+        SubscriptionOptions {
+            symbols: None,
+            capacity: DEFAULT_SUBSCRIPTION_CAPACITY,
+            lag_policy: LagPolicy::DropNewest,
+        }
+    }
+}
+
+impl SubscriptionOptions {
+    pub fn for_symbols<I, S>(symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        SubscriptionOptions {
+            symbols: Some(
+                symbols
+                    .into_iter()
+                    .map(|symbol| symbol.as_ref().to_uppercase())
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+}
+
+/// Sending half kept by `BookTickerStream` for every live subscription.
+#[derive(Debug)]
+pub struct Subscriber {
+    sender: mpsc::Sender<BookTicker>,
+    symbols: Option<HashSet<String>>,
+    lag_policy: LagPolicy,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscriber {
+    pub fn new(options: SubscriptionOptions) -> (Subscriber, BookTickerSubscription) {
+        let (sender, receiver) = mpsc::channel(options.capacity.max(1));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let subscriber = Subscriber {
+            sender,
+            symbols: options.symbols,
+            lag_policy: options.lag_policy,
+            dropped: Arc::clone(&dropped),
+        };
+        (subscriber, BookTickerSubscription { receiver, dropped })
+    }
+
+    /// Offers an update to the subscriber. Returns `false` once the
+    /// subscriber should be removed.
+    pub fn offer(&self, ticker: &BookTicker) -> bool {
+        if let Some(symbols) = &self.symbols
+            && !symbols.contains(&ticker.symbol)
+        {
+            return true;
+        }
+        match self.sender.try_send(ticker.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                self.lag_policy == LagPolicy::DropNewest
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+/// Receiving half of a book ticker subscription, usable as a `Stream`.
+#[derive(Debug)]
+pub struct BookTickerSubscription {
+    receiver: mpsc::Receiver<BookTicker>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl BookTickerSubscription {
+    pub async fn recv(&mut self) -> Option<BookTicker> {
+        self.receiver.recv().await
+    }
+
+    /// Number of updates dropped because this subscription's buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Stream for BookTickerSubscription {
+    type Item = BookTicker;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
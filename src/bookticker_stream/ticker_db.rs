@@ -1,32 +1,290 @@
+use crate::aws_resources::batch_write::{put_item_with_retry, BatchRetryConfig};
+use crate::aws_resources::dynamodb_tables::{KeyAttributeType, TableBillingMode, TableConfig};
+use crate::aws_resources::item_attributes::{
+    number_attribute, optional_number_attribute, string_attribute, ItemError,
+};
+use crate::aws_resources::write_rate::{WriteError, WritePriority, WriteRate};
 use crate::bookticker_stream::bookticker::BookTicker;
 use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+use std::time::Duration;
 use tracing::info;
 
+/// Table of the original layout, with the event time as a String sort key
+/// and no TTL. It stays the default so existing deployments keep writing to
+/// it.
+pub const TICKER_TABLE_NAME: &str = "TestTickerTable";
+/// Table of the opt-in V2 layout, with a Number sort key, so event times
+/// sort numerically, and a TTL. `ensure_table` rejects a table whose key
+/// types do not match, so the layouts cannot be mixed up.
+pub const TICKER_TABLE_V2_NAME: &str = "TestTickerTableV2";
+const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+/// Names of the non-key attributes written for every ticker.
+#[derive(Debug, Clone)]
+pub struct TickerAttributes {
+    pub symbol: String,
+    pub best_bid: String,
+    pub bid_qty: String,
+    pub best_ask: String,
+    pub ask_qty: String,
+    pub update_id: String,
+    pub trans_time: String,
+}
+
+impl Default for TickerAttributes {
+    fn default() -> Self {
+        TickerAttributes {
+            symbol: "symbol".to_string(),
+            best_bid: "best_bid".to_string(),
+            bid_qty: "bid_qty".to_string(),
+            best_ask: "best_ask".to_string(),
+            ask_qty: "ask_qty".to_string(),
+            update_id: "update_id".to_string(),
+            trans_time: "trans_time".to_string(),
+        }
+    }
+}
+
+/// Items are keyed by `<partition_key>` = `symbol#event` and
+/// `<sort_key>` = event time in milliseconds.
+#[derive(Debug, Clone)]
+pub struct TickerTableConfig {
+    pub table: TableConfig,
+    pub attributes: TickerAttributes,
+}
+
+impl Default for TickerTableConfig {
+    fn default() -> Self {
+        TickerTableConfig {
+            table: TableConfig {
+                table_name: TICKER_TABLE_NAME.to_string(),
+                partition_key: "PK".to_string(),
+                sort_key: "SK".to_string(),
+                sort_key_type: KeyAttributeType::String,
+                ttl_attribute: None,
+                retention: None,
+                billing_mode: TableBillingMode::PayPerRequest,
+                stream_enabled: false,
+            },
+            attributes: TickerAttributes::default(),
+        }
+    }
+}
+
+impl TickerTableConfig {
+    /// The V2 layout: `TICKER_TABLE_V2_NAME` with a Number sort key and a
+    /// `DEFAULT_RETENTION_DAYS` TTL on `expires_at`.
+    pub fn v2() -> Self {
+        let mut config = TickerTableConfig::default();
+        config.table.table_name = TICKER_TABLE_V2_NAME.to_string();
+        config.table.sort_key_type = KeyAttributeType::Number;
+        config.table.ttl_attribute = Some("expires_at".to_string());
+        config.table.retention = Some(Duration::from_secs(DEFAULT_RETENTION_DAYS * 24 * 3600));
+        config
+    }
+
+    /// Starts from the V2 layout when `TICKER_TABLE_LAYOUT=v2` and from the
+    /// original one otherwise, then applies `TICKER_TABLE_NAME`,
+    /// `TICKER_SORT_KEY_TYPE` (`string` or `number`), `TICKER_RETENTION_DAYS`
+    /// (0 disables TTL) and `TICKER_TABLE_STREAM` (1 enables the table's
+    /// stream).
+    pub fn from_env() -> Self {
+        let mut config = match std::env::var("TICKER_TABLE_LAYOUT").as_deref() {
+            Ok("v2") => TickerTableConfig::v2(),
+            Ok("v1") | Err(_) => TickerTableConfig::default(),
+            Ok(other) => {
+                info!("Ignoring unknown TICKER_TABLE_LAYOUT {}", other);
+                TickerTableConfig::default()
+            }
+        };
+        if let Ok(table_name) = std::env::var("TICKER_TABLE_NAME") {
+            config.table.table_name = table_name;
+        }
+        if let Ok(sort_key_type) = std::env::var("TICKER_SORT_KEY_TYPE") {
+            match sort_key_type.to_lowercase().as_str() {
+                "string" => config.table.sort_key_type = KeyAttributeType::String,
+                "number" => config.table.sort_key_type = KeyAttributeType::Number,
+                other => info!("Ignoring unknown TICKER_SORT_KEY_TYPE {}", other),
+            }
+        }
+        if let Some(days) = std::env::var("TICKER_RETENTION_DAYS")
+            .ok()
+            .and_then(|days| days.parse::<u64>().ok())
+        {
+            if days == 0 {
+                config.table.ttl_attribute = None;
+                config.table.retention = None;
+            } else {
+                config
+                    .table
+                    .ttl_attribute
+                    .get_or_insert_with(|| "expires_at".to_string());
+                config.table.retention = Some(Duration::from_secs(days * 24 * 3600));
+            }
+        }
+        if let Ok(stream) = std::env::var("TICKER_TABLE_STREAM") {
+            config.table.stream_enabled = stream == "1";
+        }
+        config
+    }
+
+    pub fn partition_key_value(&self, symbol: &str, event: &str) -> String {
+        format!("{}#{}", symbol, event)
+    }
+
+    pub fn sort_key_value(&self, event_time: u64) -> AttributeValue {
+        match self.table.sort_key_type {
+            KeyAttributeType::Number => AttributeValue::N(event_time.to_string()),
+            KeyAttributeType::String => AttributeValue::S(event_time.to_string()),
+        }
+    }
+
+    pub fn ticker_to_item(&self, item: &BookTicker) -> HashMap<String, AttributeValue> {
+        let attributes = &self.attributes;
+        let mut fields = HashMap::from([
+            (
+                self.table.partition_key.clone(),
+                AttributeValue::S(self.partition_key_value(&item.symbol, &item.event)),
+            ),
+            (
+                self.table.sort_key.clone(),
+                self.sort_key_value(item.event_time),
+            ),
+            (
+                attributes.symbol.clone(),
+                AttributeValue::S(item.symbol.clone()),
+            ),
+            (
+                attributes.best_bid.clone(),
+                AttributeValue::N(item.best_bid.clone()),
+            ),
+            (
+                attributes.bid_qty.clone(),
+                AttributeValue::N(item.bid_qty.clone()),
+            ),
+            (
+                attributes.best_ask.clone(),
+                AttributeValue::N(item.best_ask.clone()),
+            ),
+            (
+                attributes.ask_qty.clone(),
+                AttributeValue::N(item.ask_qty.clone()),
+            ),
+            (
+                attributes.update_id.clone(),
+                AttributeValue::N(item.update_id.to_string()),
+            ),
+            (
+                attributes.trans_time.clone(),
+                AttributeValue::N(item.trans_time.to_string()),
+            ),
+        ]);
+        if let (Some(ttl_attribute), Some(expires_at)) = (
+            &self.table.ttl_attribute,
+            self.table.expires_at(item.event_time),
+        ) {
+            fields.insert(
+                ttl_attribute.clone(),
+                AttributeValue::N(expires_at.to_string()),
+            );
+        }
+        fields
+    }
+
+    /// Maps an item written by `ticker_to_item` back into a ticker. Items
+    /// written before `update_id` and `trans_time` were stored get 0 and the
+    /// event time for them.
+    pub fn item_to_ticker(
+        &self,
+        item: &HashMap<String, AttributeValue>,
+    ) -> Result<BookTicker, ItemError> {
+        let attributes = &self.attributes;
+        let partition_key = string_attribute(item, &self.table.partition_key)?;
+        let event_time = number_attribute::<u64>(item, &self.table.sort_key)?;
+        let (symbol, event) =
+            partition_key
+                .split_once('#')
+                .ok_or_else(|| ItemError::InvalidAttribute {
+                    attribute: self.table.partition_key.clone(),
+                    value: partition_key.to_string(),
+                })?;
+        Ok(BookTicker {
+            event: event.to_string(),
+            update_id: optional_number_attribute(item, &attributes.update_id)?.unwrap_or(0),
+            symbol: symbol.to_string(),
+            best_bid: string_attribute(item, &attributes.best_bid)?.to_string(),
+            bid_qty: string_attribute(item, &attributes.bid_qty)?.to_string(),
+            best_ask: string_attribute(item, &attributes.best_ask)?.to_string(),
+            ask_qty: string_attribute(item, &attributes.ask_qty)?.to_string(),
+            trans_time: optional_number_attribute(item, &attributes.trans_time)?
+                .unwrap_or(event_time),
+            event_time,
+        })
+    }
+}
+
+/// Writes a single ticker at `WritePriority::Low`, sharing `rate` with the
+/// other writers; `TickerWriter` batches instead.
 pub async fn put_ticker_to_db(
     client: &aws_sdk_dynamodb::Client,
+    config: &TickerTableConfig,
     item: BookTicker,
-) -> Result<(), aws_sdk_dynamodb::Error> {
-    let event = AttributeValue::S(format!("{}#{}", item.symbol, item.event));
-    let symbol = AttributeValue::S(item.symbol);
-    let best_bid = AttributeValue::N(item.best_bid);
-    let bid_qty = AttributeValue::N(item.bid_qty);
-    let best_ask = AttributeValue::N(item.best_ask);
-    let ask_qty = AttributeValue::N(item.ask_qty);
-    let event_time = AttributeValue::S(item.event_time.to_string());
-
-    let request = client
-        .put_item()
-        .table_name("TestTickerTable")
-        .item("PK", event)
-        .item("SK", event_time)
-        .item("symbol", symbol)
-        .item("best_bid", best_bid)
-        .item("bid_qty", bid_qty)
-        .item("best_ask", best_ask)
-        .item("ask_qty", ask_qty);
-
+    rate: &WriteRate,
+) -> Result<(), WriteError> {
     info!("Executing request to DynamoDB");
-    request.send().await?;
+    put_item_with_retry(
+        client,
+        &config.table.table_name,
+        config.ticker_to_item(&item),
+        &BatchRetryConfig::default(),
+        rate,
+        WritePriority::Low,
+    )
+    .await?;
     info!("Successfully uploaded BookTicker to TickerTable",);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticker() -> BookTicker {
+        BookTicker {
+            event: "bookTicker".to_string(),
+            update_id: 7,
+            symbol: "BTCUSDT".to_string(),
+            best_bid: "100".to_string(),
+            bid_qty: "1".to_string(),
+            best_ask: "101".to_string(),
+            ask_qty: "2".to_string(),
+            trans_time: 1_700_000_000_000,
+            event_time: 1_700_000_000_001,
+        }
+    }
+
+    #[test]
+    fn writes_the_original_layout_by_default() {
+        let config = TickerTableConfig::default();
+        assert_eq!(config.table.table_name, "TestTickerTable");
+        let item = config.ticker_to_item(&ticker());
+        assert_eq!(item["SK"], AttributeValue::S("1700000000001".to_string()));
+        assert!(!item.contains_key("expires_at"));
+        assert_eq!(
+            config.item_to_ticker(&item).unwrap().event_time,
+            1_700_000_000_001
+        );
+    }
+
+    #[test]
+    fn writes_numeric_sort_keys_and_a_ttl_in_the_v2_layout() {
+        let config = TickerTableConfig::v2();
+        assert_eq!(config.table.table_name, "TestTickerTableV2");
+        let item = config.ticker_to_item(&ticker());
+        assert_eq!(item["SK"], AttributeValue::N("1700000000001".to_string()));
+        assert!(item.contains_key("expires_at"));
+        let decoded = config.item_to_ticker(&item).unwrap();
+        assert_eq!((decoded.update_id, decoded.best_ask.as_str()), (7, "101"));
+    }
+}
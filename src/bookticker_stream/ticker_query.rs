@@ -0,0 +1,157 @@
+use crate::aws_resources::item_attributes::ItemError;
+use crate::bookticker_stream::bookticker::BookTicker;
+use crate::bookticker_stream::ticker_db::TickerTableConfig;
+use aws_sdk_dynamodb::types::AttributeValue;
+use futures::stream::{self, Stream, TryStreamExt};
+use std::collections::HashMap;
+use thiserror::Error;
+
+pub const BOOK_TICKER_EVENT: &str = "bookTicker";
+
+#[derive(Error, Debug)]
+pub enum TickerQueryError {
+    #[error(transparent)]
+    DynamoError(Box<aws_sdk_dynamodb::Error>),
+    #[error(transparent)]
+    Item(#[from] ItemError),
+}
+
+impl From<aws_sdk_dynamodb::Error> for TickerQueryError {
+    fn from(error: aws_sdk_dynamodb::Error) -> Self {
+        TickerQueryError::DynamoError(Box::new(error))
+    }
+}
+
+type Key = HashMap<String, AttributeValue>;
+
+/// Reads tickers written by `TickerWriter` or `put_ticker_to_db`, both
+/// throttled through `WriteRate`, back from the ticker table with `Query` on
+/// the symbol's partition.
+#[derive(Debug, Clone)]
+pub struct TickerQuery {
+    client: aws_sdk_dynamodb::Client,
+    config: TickerTableConfig,
+    /// Items per `Query` page; DynamoDB also stops a page at 1 MB.
+    pub page_size: Option<i32>,
+    pub consistent_read: bool,
+}
+
+impl TickerQuery {
+    pub fn new(client: aws_sdk_dynamodb::Client, config: TickerTableConfig) -> Self {
+        TickerQuery {
+            client,
+            config,
+            page_size: None,
+            consistent_read: false,
+        }
+    }
+
+    /// Tickers for `symbol` with event times in `from..=to` milliseconds,
+    /// oldest first, fetched one page at a time as the stream is polled.
+    pub fn range(
+        &self,
+        symbol: &str,
+        from: u64,
+        to: u64,
+    ) -> impl Stream<Item = Result<BookTicker, TickerQueryError>> + Send + 'static {
+        self.pages(symbol, from, to)
+            .map_ok(|page| stream::iter(page.into_iter().map(Ok)))
+            .try_flatten()
+    }
+
+    /// Same as `range`, one `Vec` per `Query` page. A page may be empty
+    /// while more pages follow.
+    pub fn pages(
+        &self,
+        symbol: &str,
+        from: u64,
+        to: u64,
+    ) -> impl Stream<Item = Result<Vec<BookTicker>, TickerQueryError>> + Send + 'static {
+        let query = self.clone();
+        let partition_key = self
+            .config
+            .partition_key_value(&symbol.to_uppercase(), BOOK_TICKER_EVENT);
+        // `Some(start key)` while pages remain; the first page has none.
+        let first: Option<Option<Key>> = Some(None);
+        stream::try_unfold(first, move |next| {
+            let query = query.clone();
+            let partition_key = partition_key.clone();
+            async move {
+                let Some(start_key) = next else {
+                    return Ok(None);
+                };
+                let (page, last_key) = query
+                    .query_page(&partition_key, from, to, start_key)
+                    .await?;
+                Ok(Some((page, last_key.map(Some))))
+            }
+        })
+    }
+
+    /// Latest ticker for `symbol` with an event time at or before `at`.
+    pub async fn as_of(
+        &self,
+        symbol: &str,
+        at: u64,
+    ) -> Result<Option<BookTicker>, TickerQueryError> {
+        let table = &self.config.table;
+        let output = self
+            .client
+            .query()
+            .table_name(&table.table_name)
+            .key_condition_expression("#pk = :pk AND #sk <= :at")
+            .expression_attribute_names("#pk", &table.partition_key)
+            .expression_attribute_names("#sk", &table.sort_key)
+            .expression_attribute_values(
+                ":pk",
+                AttributeValue::S(
+                    self.config
+                        .partition_key_value(&symbol.to_uppercase(), BOOK_TICKER_EVENT),
+                ),
+            )
+            .expression_attribute_values(":at", self.config.sort_key_value(at))
+            .scan_index_forward(false)
+            .limit(1)
+            .consistent_read(self.consistent_read)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+        match output.items().first() {
+            Some(item) => Ok(Some(self.config.item_to_ticker(item)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// One page of `range`, with the key to resume from if there is more.
+    async fn query_page(
+        &self,
+        partition_key: &str,
+        from: u64,
+        to: u64,
+        start_key: Option<Key>,
+    ) -> Result<(Vec<BookTicker>, Option<Key>), TickerQueryError> {
+        let table = &self.config.table;
+        let output = self
+            .client
+            .query()
+            .table_name(&table.table_name)
+            .key_condition_expression("#pk = :pk AND #sk BETWEEN :from AND :to")
+            .expression_attribute_names("#pk", &table.partition_key)
+            .expression_attribute_names("#sk", &table.sort_key)
+            .expression_attribute_values(":pk", AttributeValue::S(partition_key.to_string()))
+            .expression_attribute_values(":from", self.config.sort_key_value(from))
+            .expression_attribute_values(":to", self.config.sort_key_value(to))
+            .set_exclusive_start_key(start_key)
+            .set_limit(self.page_size)
+            .consistent_read(self.consistent_read)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+        let tickers = output
+            .items()
+            .iter()
+            .map(|item| self.config.item_to_ticker(item))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((tickers, output.last_evaluated_key))
+    }
+}
@@ -0,0 +1,279 @@
+use crate::aws_resources::batch_write::{
+    batch_write_with_retry, put_requests, BatchRetryConfig, MAX_BATCH_WRITE_ITEMS,
+};
+use crate::aws_resources::write_rate::{WritePriority, WriteRate};
+use crate::bookticker_stream::bookticker::{BookTicker, BookTickerStream};
+use crate::bookticker_stream::subscription::{LagPolicy, SubscriptionOptions};
+use crate::bookticker_stream::ticker_db::TickerTableConfig;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration};
+use tracing::info;
+
+#[derive(Debug, Clone)]
+pub struct TickerWriterConfig {
+    pub table: TickerTableConfig,
+    /// Tickers waiting to be written; producers wait once it is full.
+    pub queue_capacity: usize,
+    pub batch_size: usize,
+    /// Partial batches are flushed at least this often.
+    pub flush_interval: Duration,
+    pub retry: BatchRetryConfig,
+    /// Tickers are written at `WritePriority::Low`; share the rate with the
+    /// other writers so order writes waiting for capacity come first.
+    pub write_rate: WriteRate,
+}
+
+impl Default for TickerWriterConfig {
+    fn default() -> Self {
+        TickerWriterConfig {
+            table: TickerTableConfig::default(),
+            queue_capacity: 10_000,
+            batch_size: MAX_BATCH_WRITE_ITEMS,
+            flush_interval: Duration::from_secs(1),
+            retry: BatchRetryConfig::default(),
+            write_rate: WriteRate::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct TickerWriterStats {
+    written: AtomicU64,
+    dropped: AtomicU64,
+    retried: AtomicU64,
+    failed: AtomicU64,
+    shed: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TickerWriterStatsSnapshot {
+    /// Items acknowledged by DynamoDB.
+    pub written: u64,
+    /// Tickers never queued because the writer could not keep up, or
+    /// replaced within a batch by a later one with the same key.
+    pub dropped: u64,
+    /// Items sent again after being returned in `UnprocessedItems`.
+    pub retried: u64,
+    /// Items given up on after `max_retries`.
+    pub failed: u64,
+    /// Items dropped without being sent while order writes needed the
+    /// capacity.
+    pub shed: u64,
+}
+
+/// Handle to a background task persisting book tickers with `BatchWriteItem`.
+#[derive(Debug, Clone)]
+pub struct TickerWriter {
+    sender: mpsc::Sender<BookTicker>,
+    stats: Arc<TickerWriterStats>,
+    queue_capacity: usize,
+}
+
+impl TickerWriter {
+    pub fn spawn(
+        client: aws_sdk_dynamodb::Client,
+        config: TickerWriterConfig,
+    ) -> (TickerWriter, JoinHandle<()>) {
+        let queue_capacity = config.queue_capacity.max(1);
+        let (sender, receiver) = mpsc::channel(queue_capacity);
+        let stats = Arc::new(TickerWriterStats::default());
+        let task = tokio::spawn(run_writer(client, config, receiver, Arc::clone(&stats)));
+        let writer = TickerWriter {
+            sender,
+            stats,
+            queue_capacity,
+        };
+        (writer, task)
+    }
+
+    /// Queues a ticker, waiting for space when the queue is full.
+    pub async fn write(
+        &self,
+        ticker: BookTicker,
+    ) -> Result<(), mpsc::error::SendError<BookTicker>> {
+        self.sender.send(ticker).await
+    }
+
+    /// Queues a ticker without waiting. Returns `false` and counts the ticker
+    /// as dropped when the queue is full or the writer has stopped.
+    pub fn try_write(&self, ticker: BookTicker) -> bool {
+        match self.sender.try_send(ticker) {
+            Ok(()) => true,
+            Err(_) => {
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// Feeds every update from `stream` into the writer. While the writer is
+    /// behind, updates pile up in the subscription buffer; anything beyond it
+    /// is dropped and reported in `stats().dropped`.
+    pub async fn consume(&self, stream: &BookTickerStream) -> JoinHandle<()> {
+        let mut subscription = stream
+            .subscribe(SubscriptionOptions {
+                capacity: self.queue_capacity,
+                lag_policy: LagPolicy::DropNewest,
+                ..Default::default()
+            })
+            .await;
+        let writer = self.clone();
+        tokio::spawn(async move {
+            let mut reported_drops = 0;
+            while let Some(ticker) = subscription.recv().await {
+                let drops = subscription.dropped();
+                if drops > reported_drops {
+                    writer
+                        .stats
+                        .dropped
+                        .fetch_add(drops - reported_drops, Ordering::Relaxed);
+                    reported_drops = drops;
+                }
+                if writer.write(ticker).await.is_err() {
+                    info!("Ticker writer stopped, no longer consuming Book Ticker Stream");
+                    break;
+                }
+            }
+        })
+    }
+
+    pub fn stats(&self) -> TickerWriterStatsSnapshot {
+        TickerWriterStatsSnapshot {
+            written: self.stats.written.load(Ordering::Relaxed),
+            dropped: self.stats.dropped.load(Ordering::Relaxed),
+            retried: self.stats.retried.load(Ordering::Relaxed),
+            failed: self.stats.failed.load(Ordering::Relaxed),
+            shed: self.stats.shed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+async fn run_writer(
+    client: aws_sdk_dynamodb::Client,
+    config: TickerWriterConfig,
+    mut receiver: mpsc::Receiver<BookTicker>,
+    stats: Arc<TickerWriterStats>,
+) {
+    let batch_size = config.batch_size.clamp(1, MAX_BATCH_WRITE_ITEMS);
+    let mut batch: Vec<BookTicker> = Vec::with_capacity(batch_size);
+    let mut flush_interval = time::interval(config.flush_interval);
+    loop {
+        tokio::select! {
+            message = receiver.recv() => match message {
+                Some(ticker) => {
+                    batch.push(ticker);
+                    if batch.len() >= batch_size {
+                        flush_batch(&client, &config, &mut batch, &stats).await;
+                    }
+                }
+                None => {
+                    flush_batch(&client, &config, &mut batch, &stats).await;
+                    info!("Ticker writer queue closed, writer stopped");
+                    break;
+                }
+            },
+            _ = flush_interval.tick() => {
+                flush_batch(&client, &config, &mut batch, &stats).await;
+            }
+        }
+    }
+}
+
+async fn flush_batch(
+    client: &aws_sdk_dynamodb::Client,
+    config: &TickerWriterConfig,
+    batch: &mut Vec<BookTicker>,
+    stats: &TickerWriterStats,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    let ticker_count = batch.len();
+    let items = latest_items(&config.table, batch.drain(..));
+    let item_count = items.len();
+    stats
+        .dropped
+        .fetch_add((ticker_count - item_count) as u64, Ordering::Relaxed);
+    let requests = put_requests(items);
+    let outcome = batch_write_with_retry(
+        client,
+        &config.table.table.table_name,
+        requests,
+        &config.retry,
+        &config.write_rate,
+        WritePriority::Low,
+    )
+    .await;
+    let unbuilt = item_count as u64 - (outcome.written + outcome.failed + outcome.shed);
+    stats.written.fetch_add(outcome.written, Ordering::Relaxed);
+    stats.retried.fetch_add(outcome.retried, Ordering::Relaxed);
+    stats.shed.fetch_add(outcome.shed, Ordering::Relaxed);
+    stats
+        .failed
+        .fetch_add(outcome.failed + unbuilt, Ordering::Relaxed);
+}
+
+/// A batch may not contain two items with the same key, so only the last
+/// ticker per (symbol, event, event time) is kept.
+fn latest_items(
+    table: &TickerTableConfig,
+    tickers: impl Iterator<Item = BookTicker>,
+) -> Vec<HashMap<String, AttributeValue>> {
+    let mut latest: HashMap<(String, String, u64), BookTicker> = HashMap::new();
+    for ticker in tickers {
+        let key = (
+            ticker.symbol.clone(),
+            ticker.event.clone(),
+            ticker.event_time,
+        );
+        latest.insert(key, ticker);
+    }
+    latest
+        .values()
+        .map(|ticker| table.ticker_to_item(ticker))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticker(symbol: &str, event_time: u64, best_bid: &str) -> BookTicker {
+        BookTicker {
+            event: "bookTicker".to_string(),
+            update_id: event_time,
+            symbol: symbol.to_string(),
+            best_bid: best_bid.to_string(),
+            bid_qty: "1".to_string(),
+            best_ask: "101".to_string(),
+            ask_qty: "1".to_string(),
+            trans_time: event_time,
+            event_time,
+        }
+    }
+
+    #[test]
+    fn keeps_the_last_ticker_per_key() {
+        let table = TickerTableConfig::default();
+        let tickers = vec![
+            ticker("BTCUSDT", 1, "100"),
+            ticker("BTCUSDT", 1, "100.5"),
+            ticker("BTCUSDT", 2, "101"),
+            ticker("ETHUSDT", 1, "10"),
+        ];
+        let items = latest_items(&table, tickers.into_iter());
+        assert_eq!(items.len(), 3);
+        let btc_bids: Vec<_> = items
+            .iter()
+            .map(|item| table.item_to_ticker(item).unwrap())
+            .filter(|ticker| ticker.symbol == "BTCUSDT" && ticker.event_time == 1)
+            .map(|ticker| ticker.best_bid)
+            .collect();
+        assert_eq!(btc_bids, ["100.5"]);
+    }
+}
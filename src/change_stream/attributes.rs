@@ -0,0 +1,88 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodbstreams::types::AttributeValue as StreamAttributeValue;
+use std::collections::HashMap;
+
+/// Converts a stream image into the item type the table readers decode.
+pub fn to_item(image: &HashMap<String, StreamAttributeValue>) -> HashMap<String, AttributeValue> {
+    image
+        .iter()
+        .map(|(name, value)| (name.clone(), to_attribute(value)))
+        .collect()
+}
+
+pub fn to_attribute(value: &StreamAttributeValue) -> AttributeValue {
+    match value {
+        StreamAttributeValue::S(value) => AttributeValue::S(value.clone()),
+        StreamAttributeValue::N(value) => AttributeValue::N(value.clone()),
+        StreamAttributeValue::B(value) => AttributeValue::B(value.clone()),
+        StreamAttributeValue::Bool(value) => AttributeValue::Bool(*value),
+        StreamAttributeValue::Null(value) => AttributeValue::Null(*value),
+        StreamAttributeValue::Ss(values) => AttributeValue::Ss(values.clone()),
+        StreamAttributeValue::Ns(values) => AttributeValue::Ns(values.clone()),
+        StreamAttributeValue::Bs(values) => AttributeValue::Bs(values.clone()),
+        StreamAttributeValue::L(values) => {
+            AttributeValue::L(values.iter().map(to_attribute).collect())
+        }
+        StreamAttributeValue::M(values) => AttributeValue::M(to_item(values)),
+        // Types added to the API after this SDK version.
+        _ => AttributeValue::Null(true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodb::primitives::Blob;
+
+    #[test]
+    fn converts_scalars_sets_and_nested_values() {
+        let image = HashMap::from([
+            (
+                "s".to_string(),
+                StreamAttributeValue::S("BTCUSDT".to_string()),
+            ),
+            ("n".to_string(), StreamAttributeValue::N("1.5".to_string())),
+            (
+                "b".to_string(),
+                StreamAttributeValue::B(Blob::new(vec![1, 2])),
+            ),
+            ("bool".to_string(), StreamAttributeValue::Bool(true)),
+            ("null".to_string(), StreamAttributeValue::Null(true)),
+            (
+                "ns".to_string(),
+                StreamAttributeValue::Ns(vec!["1".to_string(), "2".to_string()]),
+            ),
+            (
+                "l".to_string(),
+                StreamAttributeValue::L(vec![
+                    StreamAttributeValue::S("a".to_string()),
+                    StreamAttributeValue::M(HashMap::from([(
+                        "inner".to_string(),
+                        StreamAttributeValue::N("3".to_string()),
+                    )])),
+                ]),
+            ),
+        ]);
+        let item = to_item(&image);
+        assert_eq!(item.len(), image.len());
+        assert_eq!(item["s"], AttributeValue::S("BTCUSDT".to_string()));
+        assert_eq!(item["n"], AttributeValue::N("1.5".to_string()));
+        assert_eq!(item["b"], AttributeValue::B(Blob::new(vec![1, 2])));
+        assert_eq!(item["bool"], AttributeValue::Bool(true));
+        assert_eq!(item["null"], AttributeValue::Null(true));
+        assert_eq!(
+            item["ns"],
+            AttributeValue::Ns(vec!["1".to_string(), "2".to_string()])
+        );
+        assert_eq!(
+            item["l"],
+            AttributeValue::L(vec![
+                AttributeValue::S("a".to_string()),
+                AttributeValue::M(HashMap::from([(
+                    "inner".to_string(),
+                    AttributeValue::N("3".to_string()),
+                )])),
+            ])
+        );
+    }
+}
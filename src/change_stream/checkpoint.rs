@@ -0,0 +1,190 @@
+use crate::aws_resources::dynamodb_tables::{KeyAttributeType, TableBillingMode, TableConfig};
+use crate::aws_resources::item_attributes::{bool_attribute, string_attribute, ItemError};
+use crate::clock::current_time_millis;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+
+pub const CHECKPOINT_TABLE_NAME: &str = "TestStreamCheckpointTable";
+/// Stream records are kept for 24 hours, so older checkpoints are never read.
+const DEFAULT_RETENTION_DAYS: u64 = 7;
+
+#[derive(Error, Debug)]
+pub enum CheckpointError {
+    #[error(transparent)]
+    DynamoError(Box<aws_sdk_dynamodb::Error>),
+    #[error(transparent)]
+    Item(#[from] ItemError),
+}
+
+impl From<aws_sdk_dynamodb::Error> for CheckpointError {
+    fn from(error: aws_sdk_dynamodb::Error) -> Self {
+        CheckpointError::DynamoError(Box::new(error))
+    }
+}
+
+/// Items are keyed by `<partition_key>` = `<consumer>#<stream arn>` and
+/// `<sort_key>` = shard id, so consumers of the same stream keep separate
+/// positions.
+#[derive(Debug, Clone)]
+pub struct CheckpointTableConfig {
+    pub table: TableConfig,
+}
+
+impl Default for CheckpointTableConfig {
+    fn default() -> Self {
+        CheckpointTableConfig {
+            table: TableConfig {
+                table_name: CHECKPOINT_TABLE_NAME.to_string(),
+                partition_key: "PK".to_string(),
+                sort_key: "SK".to_string(),
+                sort_key_type: KeyAttributeType::String,
+                ttl_attribute: Some("expires_at".to_string()),
+                retention: Some(Duration::from_secs(DEFAULT_RETENTION_DAYS * 24 * 3600)),
+                billing_mode: TableBillingMode::PayPerRequest,
+                stream_enabled: false,
+            },
+        }
+    }
+}
+
+impl CheckpointTableConfig {
+    /// Reads `CHECKPOINT_TABLE_NAME`, falling back to the default.
+    pub fn from_env() -> Self {
+        let mut config = CheckpointTableConfig::default();
+        if let Ok(table_name) = std::env::var("CHECKPOINT_TABLE_NAME") {
+            config.table.table_name = table_name;
+        }
+        config
+    }
+
+    pub fn partition_key_value(&self, consumer: &str, stream_arn: &str) -> String {
+        format!("{}#{}", consumer, stream_arn)
+    }
+}
+
+/// How far a consumer has read a shard.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShardCheckpoint {
+    /// Last sequence number handed to the consumer; `None` before the first
+    /// record.
+    pub sequence_number: Option<String>,
+    /// The shard is closed and every record in it was handed over, so its
+    /// children can be read.
+    pub finished: bool,
+}
+
+/// Per-shard checkpoints of one consumer of one stream.
+#[derive(Debug, Clone)]
+pub struct CheckpointStore {
+    client: aws_sdk_dynamodb::Client,
+    config: CheckpointTableConfig,
+    partition_key_value: String,
+}
+
+impl CheckpointStore {
+    pub fn new(
+        client: aws_sdk_dynamodb::Client,
+        config: CheckpointTableConfig,
+        consumer: &str,
+        stream_arn: &str,
+    ) -> Self {
+        let partition_key_value = config.partition_key_value(consumer, stream_arn);
+        CheckpointStore {
+            client,
+            config,
+            partition_key_value,
+        }
+    }
+
+    pub fn table(&self) -> &TableConfig {
+        &self.config.table
+    }
+
+    /// Every saved checkpoint by shard id.
+    pub async fn load(&self) -> Result<HashMap<String, ShardCheckpoint>, CheckpointError> {
+        let table = &self.config.table;
+        let mut checkpoints = HashMap::new();
+        let mut start_key = None;
+        loop {
+            let output = self
+                .client
+                .query()
+                .table_name(&table.table_name)
+                .key_condition_expression("#pk = :pk")
+                .expression_attribute_names("#pk", &table.partition_key)
+                .expression_attribute_values(
+                    ":pk",
+                    AttributeValue::S(self.partition_key_value.clone()),
+                )
+                .consistent_read(true)
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await
+                .map_err(aws_sdk_dynamodb::Error::from)?;
+            for item in output.items() {
+                let shard_id = string_attribute(item, &table.sort_key)?.to_string();
+                let sequence_number = item
+                    .contains_key("sequence_number")
+                    .then(|| string_attribute(item, "sequence_number").map(str::to_string))
+                    .transpose()?;
+                let checkpoint = ShardCheckpoint {
+                    sequence_number,
+                    finished: bool_attribute(item, "finished")?,
+                };
+                checkpoints.insert(shard_id, checkpoint);
+            }
+            start_key = output.last_evaluated_key;
+            if start_key.is_none() {
+                return Ok(checkpoints);
+            }
+        }
+    }
+
+    pub async fn save(
+        &self,
+        shard_id: &str,
+        checkpoint: &ShardCheckpoint,
+    ) -> Result<(), CheckpointError> {
+        let table = &self.config.table;
+        let now = current_time_millis();
+        let mut item = HashMap::from([
+            (
+                table.partition_key.clone(),
+                AttributeValue::S(self.partition_key_value.clone()),
+            ),
+            (
+                table.sort_key.clone(),
+                AttributeValue::S(shard_id.to_string()),
+            ),
+            (
+                "finished".to_string(),
+                AttributeValue::Bool(checkpoint.finished),
+            ),
+            ("updated_at".to_string(), AttributeValue::N(now.to_string())),
+        ]);
+        if let Some(sequence_number) = &checkpoint.sequence_number {
+            item.insert(
+                "sequence_number".to_string(),
+                AttributeValue::S(sequence_number.clone()),
+            );
+        }
+        if let (Some(ttl_attribute), Some(expires_at)) =
+            (&table.ttl_attribute, table.expires_at(now))
+        {
+            item.insert(
+                ttl_attribute.clone(),
+                AttributeValue::N(expires_at.to_string()),
+            );
+        }
+        self.client
+            .put_item()
+            .table_name(&table.table_name)
+            .set_item(Some(item))
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+pub mod attributes;
+pub mod checkpoint;
+pub mod reader;
+pub mod records;
@@ -0,0 +1,783 @@
+use crate::aws_resources::dynamodb_tables::{ensure_table, TableError};
+use crate::aws_resources::item_attributes::ItemError;
+use crate::change_stream::attributes::to_item;
+use crate::change_stream::checkpoint::{
+    CheckpointError, CheckpointStore, CheckpointTableConfig, ShardCheckpoint,
+};
+use crate::change_stream::records::{ChangeKind, ChangeRecord, ChangeTable};
+use aws_sdk_dynamodbstreams::types::{OperationType, Record, ShardIteratorType};
+use futures::stream::{self, Stream};
+use std::collections::{HashMap, HashSet, VecDeque};
+use thiserror::Error;
+use tokio::time::{self, Duration, Instant};
+use tracing::info;
+
+#[derive(Error, Debug)]
+pub enum ChangeStreamError {
+    #[error(transparent)]
+    DynamoError(Box<aws_sdk_dynamodb::Error>),
+    #[error(transparent)]
+    StreamsError(Box<aws_sdk_dynamodbstreams::Error>),
+    #[error(transparent)]
+    Table(#[from] TableError),
+    #[error(transparent)]
+    Checkpoint(#[from] CheckpointError),
+    #[error(transparent)]
+    Item(#[from] ItemError),
+    #[error("table {0} has no stream enabled")]
+    StreamNotEnabled(String),
+}
+
+impl From<aws_sdk_dynamodb::Error> for ChangeStreamError {
+    fn from(error: aws_sdk_dynamodb::Error) -> Self {
+        ChangeStreamError::DynamoError(Box::new(error))
+    }
+}
+
+impl From<aws_sdk_dynamodbstreams::Error> for ChangeStreamError {
+    fn from(error: aws_sdk_dynamodbstreams::Error) -> Self {
+        ChangeStreamError::StreamsError(Box::new(error))
+    }
+}
+
+/// Where a consumer with no checkpoints starts reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartPosition {
+    /// The oldest record still in the stream, up to 24 hours back.
+    TrimHorizon,
+    /// Only changes made from now on.
+    Latest,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangeStreamConfig {
+    /// Name the consumer's checkpoints are saved under; consumers with
+    /// different names read the stream independently.
+    pub consumer: String,
+    pub checkpoint_table: CheckpointTableConfig,
+    pub start: StartPosition,
+    /// Records per `GetRecords` call, at most 1000.
+    pub batch_limit: i32,
+    /// Wait after a round over every readable shard returned nothing.
+    pub poll_interval: Duration,
+    /// How often the shard list is described again to find new shards.
+    pub shard_refresh_interval: Duration,
+}
+
+impl Default for ChangeStreamConfig {
+    fn default() -> Self {
+        ChangeStreamConfig {
+            consumer: "default".to_string(),
+            checkpoint_table: CheckpointTableConfig::default(),
+            start: StartPosition::TrimHorizon,
+            batch_limit: 1000,
+            poll_interval: Duration::from_secs(1),
+            shard_refresh_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ChangeStreamConfig {
+    /// Reads `CHANGE_STREAM_CONSUMER`, `CHANGE_STREAM_START` (`latest` or
+    /// `trim_horizon`) and `CHECKPOINT_TABLE_NAME`, falling back to the
+    /// defaults.
+    pub fn from_env() -> Self {
+        let mut config = ChangeStreamConfig {
+            checkpoint_table: CheckpointTableConfig::from_env(),
+            ..Default::default()
+        };
+        if let Ok(consumer) = std::env::var("CHANGE_STREAM_CONSUMER") {
+            config.consumer = consumer;
+        }
+        match std::env::var("CHANGE_STREAM_START").as_deref() {
+            Ok("latest") => config.start = StartPosition::Latest,
+            Ok("trim_horizon") => config.start = StartPosition::TrimHorizon,
+            _ => {}
+        }
+        config
+    }
+}
+
+/// Reads a table's DynamoDB stream. Shards are read round-robin, a child
+/// shard only once its parent is finished, so changes to one item arrive in
+/// order. Positions are checkpointed per shard once every record fetched
+/// from it has been taken from the stream and the next one is requested,
+/// which makes delivery at-least-once.
+#[derive(Debug, Clone)]
+pub struct ChangeStreamReader<T: ChangeTable> {
+    client: aws_sdk_dynamodb::Client,
+    streams_client: aws_sdk_dynamodbstreams::Client,
+    table: T,
+    config: ChangeStreamConfig,
+}
+
+impl<T: ChangeTable> ChangeStreamReader<T> {
+    pub fn new(
+        client: aws_sdk_dynamodb::Client,
+        streams_client: aws_sdk_dynamodbstreams::Client,
+        table: T,
+        config: ChangeStreamConfig,
+    ) -> Self {
+        ChangeStreamReader {
+            client,
+            streams_client,
+            table,
+            config,
+        }
+    }
+
+    pub async fn stream_arn(&self) -> Result<String, ChangeStreamError> {
+        let table_name = &self.table.table().table_name;
+        let output = self
+            .client
+            .describe_table()
+            .table_name(table_name)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+        output
+            .table()
+            .and_then(|table| table.latest_stream_arn())
+            .map(str::to_string)
+            .ok_or_else(|| ChangeStreamError::StreamNotEnabled(table_name.clone()))
+    }
+
+    /// Endless stream of the table's changes. Errors are yielded without
+    /// ending the stream; polling again retries after `poll_interval`.
+    pub async fn records(
+        self,
+    ) -> Result<
+        impl Stream<Item = Result<ChangeRecord<T::Record>, ChangeStreamError>>,
+        ChangeStreamError,
+    > {
+        ensure_table(&self.client, &self.config.checkpoint_table.table).await?;
+        let stream_arn = self.stream_arn().await?;
+        let store = CheckpointStore::new(
+            self.client.clone(),
+            self.config.checkpoint_table.clone(),
+            &self.config.consumer,
+            &stream_arn,
+        );
+        let saved = store.load().await?;
+        info!(
+            "Reading {} as {} from {} with {} saved shard checkpoints",
+            self.table.table().table_name,
+            self.config.consumer,
+            stream_arn,
+            saved.len()
+        );
+        let state = ReaderState {
+            first_run: saved.is_empty(),
+            reader: self,
+            stream_arn,
+            store,
+            saved,
+            shards: HashMap::new(),
+            ready: VecDeque::new(),
+            buffer: VecDeque::new(),
+            delivered: None,
+            refresh_at: Instant::now(),
+            idle_reads: 0,
+            pause: false,
+        };
+        Ok(stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(record) = state.buffer.pop_front() {
+                    return Some((record, state));
+                }
+                if let Err(e) = state.advance().await {
+                    state.pause = true;
+                    return Some((Err(e), state));
+                }
+            }
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct ShardState {
+    parent: Option<String>,
+    checkpoint: ShardCheckpoint,
+    /// Where the iterator opens while there is no checkpointed sequence
+    /// number.
+    start: ShardIteratorType,
+    iterator: Option<String>,
+}
+
+struct ReaderState<T: ChangeTable> {
+    reader: ChangeStreamReader<T>,
+    stream_arn: String,
+    store: CheckpointStore,
+    /// Loaded checkpoints of shards not discovered yet.
+    saved: HashMap<String, ShardCheckpoint>,
+    first_run: bool,
+    shards: HashMap<String, ShardState>,
+    /// Shards to read, in round-robin order.
+    ready: VecDeque<String>,
+    buffer: VecDeque<Result<ChangeRecord<T::Record>, ChangeStreamError>>,
+    /// Checkpoint covering the records in `buffer`, saved once they are all
+    /// taken.
+    delivered: Option<(String, ShardCheckpoint)>,
+    refresh_at: Instant,
+    /// Consecutive reads that returned nothing.
+    idle_reads: usize,
+    pause: bool,
+}
+
+impl<T: ChangeTable> ReaderState<T> {
+    /// Saves the last batch's checkpoint and reads the next shard in turn.
+    async fn advance(&mut self) -> Result<(), ChangeStreamError> {
+        if std::mem::take(&mut self.pause) {
+            time::sleep(self.reader.config.poll_interval).await;
+        }
+        if let Some((shard_id, checkpoint)) = &self.delivered {
+            self.store.save(shard_id, checkpoint).await?;
+            self.delivered = None;
+        }
+        if Instant::now() >= self.refresh_at {
+            self.refresh_shards().await?;
+        }
+        let Some(shard_id) = self.ready.pop_front() else {
+            // Waiting for the children of finished shards to appear.
+            self.pause = true;
+            self.refresh_at = Instant::now();
+            return Ok(());
+        };
+        let result = self.read_shard(&shard_id).await;
+        match self.shards.get(&shard_id) {
+            Some(shard) if shard.checkpoint.finished => {
+                info!("Finished reading shard {}", shard_id);
+                self.refresh_at = Instant::now();
+            }
+            Some(_) => self.ready.push_back(shard_id),
+            None => {}
+        }
+        result
+    }
+
+    async fn read_shard(&mut self, shard_id: &str) -> Result<(), ChangeStreamError> {
+        let streams_client = &self.reader.streams_client;
+        let Some(shard) = self.shards.get_mut(shard_id) else {
+            return Ok(());
+        };
+        if shard.iterator.is_none() {
+            let request = streams_client
+                .get_shard_iterator()
+                .stream_arn(&self.stream_arn)
+                .shard_id(shard_id);
+            let request = match &shard.checkpoint.sequence_number {
+                Some(sequence_number) => request
+                    .shard_iterator_type(ShardIteratorType::AfterSequenceNumber)
+                    .sequence_number(sequence_number),
+                None => request.shard_iterator_type(shard.start.clone()),
+            };
+            let output = request
+                .send()
+                .await
+                .map_err(aws_sdk_dynamodbstreams::Error::from)?;
+            shard.iterator = output.shard_iterator().map(str::to_string);
+            // Until its first record, a shard read from the latest record has
+            // nothing to resume from; saving it now makes a restart read it
+            // from the trim horizon instead of skipping to the latest record
+            // again.
+            if shard.start == ShardIteratorType::Latest
+                && shard.checkpoint.sequence_number.is_none()
+            {
+                self.store.save(shard_id, &shard.checkpoint).await?;
+            }
+        }
+        let Some(iterator) = shard.iterator.take() else {
+            shard.checkpoint.finished = true;
+            self.delivered = Some((shard_id.to_string(), shard.checkpoint.clone()));
+            return Ok(());
+        };
+        let result = streams_client
+            .get_records()
+            .shard_iterator(iterator)
+            .limit(self.reader.config.batch_limit)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodbstreams::Error::from);
+        let output = match result {
+            Ok(output) => output,
+            // Reopened from the checkpoint on the next read.
+            Err(aws_sdk_dynamodbstreams::Error::ExpiredIteratorException(_)) => {
+                info!("Iterator for shard {} expired, reopening", shard_id);
+                return Ok(());
+            }
+            Err(aws_sdk_dynamodbstreams::Error::TrimmedDataAccessException(_)) => {
+                info!(
+                    "Records after the checkpoint of shard {} were trimmed, restarting from the trim horizon",
+                    shard_id
+                );
+                shard.checkpoint.sequence_number = None;
+                shard.start = ShardIteratorType::TrimHorizon;
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut last_sequence_number = None;
+        for record in output.records() {
+            if let Some(sequence_number) = record
+                .dynamodb()
+                .and_then(|change| change.sequence_number())
+            {
+                last_sequence_number = Some(sequence_number.to_string());
+            }
+            if let Some(change) = decode_record(&self.reader.table, shard_id, record).transpose() {
+                self.buffer.push_back(change);
+            }
+        }
+        shard.iterator = output.next_shard_iterator().map(str::to_string);
+        let finished = shard.iterator.is_none();
+        if last_sequence_number.is_some() || finished {
+            if last_sequence_number.is_some() {
+                shard.checkpoint.sequence_number = last_sequence_number;
+            }
+            shard.checkpoint.finished = finished;
+            self.delivered = Some((shard_id.to_string(), shard.checkpoint.clone()));
+        }
+        if output.records().is_empty() {
+            self.idle_reads += 1;
+            if self.idle_reads > self.ready.len() {
+                self.idle_reads = 0;
+                self.pause = true;
+            }
+        } else {
+            self.idle_reads = 0;
+        }
+        Ok(())
+    }
+
+    /// Describes the stream, adding new shards and queueing every unfinished
+    /// shard whose parent is finished or gone.
+    async fn refresh_shards(&mut self) -> Result<(), ChangeStreamError> {
+        let mut discovered = Vec::new();
+        let mut start_shard_id = None;
+        loop {
+            let output = self
+                .reader
+                .streams_client
+                .describe_stream()
+                .stream_arn(&self.stream_arn)
+                .set_exclusive_start_shard_id(start_shard_id)
+                .send()
+                .await
+                .map_err(aws_sdk_dynamodbstreams::Error::from)?;
+            let Some(description) = output.stream_description() else {
+                break;
+            };
+            for shard in description.shards() {
+                if let Some(shard_id) = shard.shard_id() {
+                    let closed = shard
+                        .sequence_number_range()
+                        .is_some_and(|range| range.ending_sequence_number().is_some());
+                    discovered.push((
+                        shard_id.to_string(),
+                        shard.parent_shard_id().map(str::to_string),
+                        closed,
+                    ));
+                }
+            }
+            start_shard_id = description.last_evaluated_shard_id().map(str::to_string);
+            if start_shard_id.is_none() {
+                break;
+            }
+        }
+
+        let discovered_ids: HashSet<String> = discovered
+            .iter()
+            .map(|(shard_id, _, _)| shard_id.clone())
+            .collect();
+        // Shards older than 24 hours are trimmed from the stream.
+        self.shards
+            .retain(|shard_id, _| discovered_ids.contains(shard_id));
+        self.ready
+            .retain(|shard_id| discovered_ids.contains(shard_id));
+        for (shard_id, parent, closed) in discovered {
+            if self.shards.contains_key(&shard_id) {
+                continue;
+            }
+            let mut checkpoint = self.saved.remove(&shard_id).unwrap_or_default();
+            let parent_known = parent
+                .as_ref()
+                .is_some_and(|parent| discovered_ids.contains(parent));
+            let (start, skip) = start_position(
+                self.reader.config.start,
+                self.first_run,
+                parent_known,
+                closed,
+            );
+            if skip {
+                checkpoint.finished = true;
+                self.store.save(&shard_id, &checkpoint).await?;
+            }
+            self.shards.insert(
+                shard_id,
+                ShardState {
+                    parent,
+                    checkpoint,
+                    start,
+                    iterator: None,
+                },
+            );
+        }
+        self.first_run = false;
+
+        let readable = readable_shards(&self.shards, &self.ready);
+        self.ready.extend(readable);
+        self.refresh_at = Instant::now() + self.reader.config.shard_refresh_interval;
+        Ok(())
+    }
+}
+
+/// Where a newly discovered shard is read from, and whether it is skipped
+/// as already finished. A consumer starting at the latest record skips
+/// closed shards entirely; anything found later, or whose parent is still
+/// in the stream, is new and read from the start.
+fn start_position(
+    start: StartPosition,
+    first_run: bool,
+    parent_known: bool,
+    closed: bool,
+) -> (ShardIteratorType, bool) {
+    if parent_known || !first_run {
+        return (ShardIteratorType::TrimHorizon, false);
+    }
+    match start {
+        StartPosition::TrimHorizon => (ShardIteratorType::TrimHorizon, false),
+        StartPosition::Latest => (ShardIteratorType::Latest, closed),
+    }
+}
+
+/// Unfinished shards not queued yet whose parent is finished or gone, in
+/// shard id order.
+fn readable_shards(shards: &HashMap<String, ShardState>, ready: &VecDeque<String>) -> Vec<String> {
+    let mut readable: Vec<String> = shards
+        .iter()
+        .filter(|(shard_id, shard)| {
+            !shard.checkpoint.finished
+                && !ready.contains(shard_id)
+                && shard.parent.as_ref().is_none_or(|parent| {
+                    shards
+                        .get(parent)
+                        .is_none_or(|parent| parent.checkpoint.finished)
+                })
+        })
+        .map(|(shard_id, _)| shard_id.clone())
+        .collect();
+    readable.sort();
+    readable
+}
+
+fn decode_record<T: ChangeTable>(
+    table: &T,
+    shard_id: &str,
+    record: &Record,
+) -> Result<Option<ChangeRecord<T::Record>>, ChangeStreamError> {
+    let kind = match record.event_name() {
+        Some(OperationType::Insert) => ChangeKind::Insert,
+        Some(OperationType::Modify) => ChangeKind::Modify,
+        Some(OperationType::Remove) => ChangeKind::Remove,
+        _ => return Ok(None),
+    };
+    let Some(change) = record.dynamodb() else {
+        return Ok(None);
+    };
+    let decode = |image: Option<&HashMap<_, _>>| {
+        image.map(|image| table.decode(&to_item(image))).transpose()
+    };
+    Ok(Some(ChangeRecord {
+        kind,
+        shard_id: shard_id.to_string(),
+        sequence_number: change.sequence_number().unwrap_or_default().to_string(),
+        created_at: change
+            .approximate_creation_date_time()
+            .and_then(|created_at| created_at.to_millis().ok())
+            .map(|created_at| created_at as u64),
+        keys: change.keys().map(to_item).unwrap_or_default(),
+        new: decode(change.new_image())?,
+        old: decode(change.old_image())?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookticker_stream::bookticker::BookTicker;
+    use crate::bookticker_stream::ticker_db::TickerTableConfig;
+    use aws_sdk_dynamodbstreams::primitives::DateTime;
+    use aws_sdk_dynamodbstreams::types::{AttributeValue as StreamAttributeValue, StreamRecord};
+
+    fn shard(parent: Option<&str>, finished: bool) -> ShardState {
+        ShardState {
+            parent: parent.map(str::to_string),
+            checkpoint: ShardCheckpoint {
+                sequence_number: None,
+                finished,
+            },
+            start: ShardIteratorType::TrimHorizon,
+            iterator: None,
+        }
+    }
+
+    #[test]
+    fn reads_children_once_their_parent_is_finished_or_gone() {
+        let mut shards = HashMap::from([
+            ("a".to_string(), shard(None, false)),
+            ("a-child".to_string(), shard(Some("a"), false)),
+            ("b".to_string(), shard(None, true)),
+            ("b-child".to_string(), shard(Some("b"), false)),
+            ("orphan".to_string(), shard(Some("trimmed"), false)),
+        ]);
+        assert_eq!(
+            readable_shards(&shards, &VecDeque::new()),
+            vec!["a", "b-child", "orphan"]
+        );
+        // Already queued shards are not queued twice.
+        let ready = VecDeque::from(["a".to_string(), "orphan".to_string()]);
+        assert_eq!(readable_shards(&shards, &ready), vec!["b-child"]);
+
+        shards.get_mut("a").unwrap().checkpoint.finished = true;
+        assert_eq!(readable_shards(&shards, &ready), vec!["a-child", "b-child"]);
+    }
+
+    #[test]
+    fn starts_a_latest_first_run_at_the_latest_record_and_skips_closed_shards() {
+        let latest = StartPosition::Latest;
+        assert_eq!(
+            start_position(latest, true, false, false),
+            (ShardIteratorType::Latest, false)
+        );
+        assert_eq!(
+            start_position(latest, true, false, true),
+            (ShardIteratorType::Latest, true)
+        );
+        // Children of known shards hold changes made after the parent's.
+        assert_eq!(
+            start_position(latest, true, true, false),
+            (ShardIteratorType::TrimHorizon, false)
+        );
+        // Shards discovered after the first refresh are new.
+        assert_eq!(
+            start_position(latest, false, false, true),
+            (ShardIteratorType::TrimHorizon, false)
+        );
+    }
+
+    #[test]
+    fn starts_a_trim_horizon_run_at_the_oldest_record() {
+        for (first_run, parent_known, closed) in [
+            (true, false, false),
+            (true, false, true),
+            (false, true, true),
+        ] {
+            assert_eq!(
+                start_position(StartPosition::TrimHorizon, first_run, parent_known, closed),
+                (ShardIteratorType::TrimHorizon, false)
+            );
+        }
+    }
+
+    fn ticker(best_bid: &str) -> BookTicker {
+        BookTicker {
+            event: "bookTicker".to_string(),
+            update_id: 7,
+            symbol: "BTCUSDT".to_string(),
+            best_bid: best_bid.to_string(),
+            bid_qty: "1".to_string(),
+            best_ask: "101".to_string(),
+            ask_qty: "2".to_string(),
+            trans_time: 1_700_000_000_000,
+            event_time: 1_700_000_000_001,
+        }
+    }
+
+    fn image(
+        table: &TickerTableConfig,
+        ticker: &BookTicker,
+    ) -> HashMap<String, StreamAttributeValue> {
+        table
+            .ticker_to_item(ticker)
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    aws_sdk_dynamodb::types::AttributeValue::S(value) => {
+                        StreamAttributeValue::S(value)
+                    }
+                    aws_sdk_dynamodb::types::AttributeValue::N(value) => {
+                        StreamAttributeValue::N(value)
+                    }
+                    other => panic!("unexpected ticker attribute {:?}", other),
+                };
+                (name, value)
+            })
+            .collect()
+    }
+
+    fn record(
+        kind: OperationType,
+        new: Option<HashMap<String, StreamAttributeValue>>,
+        old: Option<HashMap<String, StreamAttributeValue>>,
+    ) -> Record {
+        Record::builder()
+            .event_name(kind)
+            .dynamodb(
+                StreamRecord::builder()
+                    .sequence_number("100")
+                    .approximate_creation_date_time(DateTime::from_secs(1_700_000_000))
+                    .keys(
+                        "PK",
+                        StreamAttributeValue::S("BTCUSDT#bookTicker".to_string()),
+                    )
+                    .set_new_image(new)
+                    .set_old_image(old)
+                    .build(),
+            )
+            .build()
+    }
+
+    #[test]
+    fn decodes_insert_modify_and_remove_images() {
+        let table = TickerTableConfig::default();
+        let before = image(&table, &ticker("100"));
+        let after = image(&table, &ticker("100.5"));
+
+        let insert = decode_record(
+            &table,
+            "shard-1",
+            &record(OperationType::Insert, Some(after.clone()), None),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(insert.kind, ChangeKind::Insert);
+        assert_eq!(insert.shard_id, "shard-1");
+        assert_eq!(insert.sequence_number, "100");
+        assert_eq!(insert.created_at, Some(1_700_000_000_000));
+        assert_eq!(
+            insert.keys["PK"],
+            aws_sdk_dynamodb::types::AttributeValue::S("BTCUSDT#bookTicker".to_string())
+        );
+        assert_eq!(insert.new.unwrap().best_bid, "100.5");
+        assert!(insert.old.is_none());
+
+        let modify = decode_record(
+            &table,
+            "shard-1",
+            &record(OperationType::Modify, Some(after), Some(before.clone())),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(modify.kind, ChangeKind::Modify);
+        assert_eq!(modify.new.unwrap().best_bid, "100.5");
+        assert_eq!(modify.old.unwrap().best_bid, "100");
+
+        let remove = decode_record(
+            &table,
+            "shard-1",
+            &record(OperationType::Remove, None, Some(before)),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(remove.kind, ChangeKind::Remove);
+        assert!(remove.new.is_none());
+        let old = remove.old.unwrap();
+        assert_eq!(
+            (old.symbol.as_str(), old.event_time),
+            ("BTCUSDT", 1_700_000_000_001)
+        );
+    }
+
+    #[test]
+    fn rejects_images_the_table_cannot_decode() {
+        let table = TickerTableConfig::default();
+        let mut broken = image(&table, &ticker("100"));
+        broken.remove(&table.table.sort_key);
+        assert!(decode_record(
+            &table,
+            "shard-1",
+            &record(OperationType::Insert, Some(broken), None)
+        )
+        .is_err());
+    }
+
+    /// Writes an order and reads its insert back through the stream, against
+    /// DynamoDB Local, e.g. `docker run -p 8000:8000 amazon/dynamodb-local`
+    /// and `AWS_ENDPOINT_URL=http://localhost:8000 AWS_REGION=us-east-1
+    /// AWS_ACCESS_KEY_ID=local AWS_SECRET_ACCESS_KEY=local cargo test --
+    /// --ignored reads_changes_from_dynamodb_local`.
+    #[tokio::test]
+    #[ignore = "needs DynamoDB Local"]
+    async fn reads_changes_from_dynamodb_local() {
+        use crate::aws_resources::clients::{get_ddb_client, get_ddb_streams_client};
+        use crate::order_stream::order_db::{OrderRecord, OrderTableConfig};
+        use futures::StreamExt;
+
+        let client = get_ddb_client().await.unwrap();
+        let streams_client = get_ddb_streams_client().await.unwrap();
+        let suffix = std::process::id();
+        let mut table = OrderTableConfig::default();
+        table.table.table_name = format!("ChangeStreamTestOrders{}", suffix);
+        ensure_table(&client, &table.table).await.unwrap();
+        let mut config = ChangeStreamConfig {
+            consumer: format!("test-{}", suffix),
+            start: StartPosition::TrimHorizon,
+            poll_interval: Duration::from_millis(100),
+            ..Default::default()
+        };
+        config.checkpoint_table.table.table_name = format!("ChangeStreamTestCheckpoints{}", suffix);
+
+        let order = OrderRecord {
+            symbol: "BTCUSDT".to_string(),
+            order_id: 42,
+            client_order_id: "test".to_string(),
+            side: "BUY".to_string(),
+            order_type: "LIMIT".to_string(),
+            execution_type: "NEW".to_string(),
+            status: "NEW".to_string(),
+            price: "100".to_string(),
+            quantity: "1".to_string(),
+            average_price: "0".to_string(),
+            last_filled_price: "0".to_string(),
+            last_filled_quantity: "0".to_string(),
+            filled_quantity: "0".to_string(),
+            commission: "0".to_string(),
+            commission_asset: "USDT".to_string(),
+            realized_profit: "0".to_string(),
+            trade_id: 0,
+            maker: false,
+            reduce_only: false,
+            event_time: 1_700_000_000_000,
+            trade_time: 1_700_000_000_000,
+        };
+        client
+            .put_item()
+            .table_name(&table.table.table_name)
+            .set_item(Some(table.order_to_item(&order)))
+            .send()
+            .await
+            .unwrap();
+
+        let reader = ChangeStreamReader::new(client.clone(), streams_client, table.clone(), config);
+        let records = reader.records().await.unwrap();
+        futures::pin_mut!(records);
+        let change = time::timeout(Duration::from_secs(30), async {
+            loop {
+                if let Ok(change) = records.next().await.unwrap() {
+                    return change;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(change.kind, ChangeKind::Insert);
+        assert_eq!(change.new.unwrap().order_id, 42);
+
+        for table_name in [
+            table.table.table_name.clone(),
+            format!("ChangeStreamTestCheckpoints{}", suffix),
+        ] {
+            let _ = client.delete_table().table_name(table_name).send().await;
+        }
+    }
+}
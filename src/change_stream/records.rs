@@ -0,0 +1,63 @@
+use crate::aws_resources::dynamodb_tables::TableConfig;
+use crate::aws_resources::item_attributes::ItemError;
+use crate::bookticker_stream::bookticker::BookTicker;
+use crate::bookticker_stream::ticker_db::TickerTableConfig;
+use crate::order_stream::order_db::{OrderRecord, OrderTableConfig};
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+/// A table whose stream records decode into one of the crate's types.
+pub trait ChangeTable: Clone + Send + Sync + 'static {
+    type Record: Send + 'static;
+
+    fn table(&self) -> &TableConfig;
+
+    fn decode(&self, item: &HashMap<String, AttributeValue>) -> Result<Self::Record, ItemError>;
+}
+
+impl ChangeTable for TickerTableConfig {
+    type Record = BookTicker;
+
+    fn table(&self) -> &TableConfig {
+        &self.table
+    }
+
+    fn decode(&self, item: &HashMap<String, AttributeValue>) -> Result<BookTicker, ItemError> {
+        self.item_to_ticker(item)
+    }
+}
+
+impl ChangeTable for OrderTableConfig {
+    type Record = OrderRecord;
+
+    fn table(&self) -> &TableConfig {
+        &self.table
+    }
+
+    fn decode(&self, item: &HashMap<String, AttributeValue>) -> Result<OrderRecord, ItemError> {
+        self.item_to_order(item)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Insert,
+    Modify,
+    /// Deleted by a request or expired through TTL.
+    Remove,
+}
+
+/// One item change read from a table's stream.
+#[derive(Debug, Clone)]
+pub struct ChangeRecord<R> {
+    pub kind: ChangeKind,
+    pub shard_id: String,
+    pub sequence_number: String,
+    /// When the change was made, in epoch milliseconds, to the second.
+    pub created_at: Option<u64>,
+    pub keys: HashMap<String, AttributeValue>,
+    /// Item after the change; `None` for removals.
+    pub new: Option<R>,
+    /// Item before the change; `None` for inserts.
+    pub old: Option<R>,
+}
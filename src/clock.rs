@@ -0,0 +1,128 @@
+use crate::async_binance::client_async::AsyncBinanceClient;
+use crate::async_binance::errors::CustomError;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// Simulated time in epoch milliseconds, or 0 while the wall clock is used.
+static SIMULATED_TIME_MS: AtomicU64 = AtomicU64::new(0);
+/// Set by `enable_simulated_time`; nothing else may simulate the clock.
+static SIMULATION_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Local wall-clock time in epoch milliseconds, or the simulated time while
+/// a replay is driving the clock.
+pub fn current_time_millis() -> u64 {
+    match SIMULATED_TIME_MS.load(Ordering::Relaxed) {
+        0 => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or_default(),
+        simulated => simulated,
+    }
+}
+
+/// Allows `set_simulated_time` in this process. The simulated clock is
+/// process-wide, so only a standalone replay binary, which runs no live
+/// streams, may call this.
+pub fn enable_simulated_time() {
+    SIMULATION_ENABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn simulated_time_enabled() -> bool {
+    SIMULATION_ENABLED.load(Ordering::SeqCst)
+}
+
+/// Makes `current_time_millis` return `time_ms` for the whole process until
+/// `use_wall_clock` is called.
+///
+/// # Panics
+///
+/// Unless `enable_simulated_time` was called, so a replay can never skew the
+/// clock of a live process.
+pub fn set_simulated_time(time_ms: u64) {
+    assert!(
+        simulated_time_enabled(),
+        "simulated time is only allowed in a standalone replay process"
+    );
+    SIMULATED_TIME_MS.store(time_ms.max(1), Ordering::Relaxed);
+}
+
+pub fn use_wall_clock() {
+    SIMULATED_TIME_MS.store(0, Ordering::Relaxed);
+}
+
+pub fn is_simulated() -> bool {
+    SIMULATED_TIME_MS.load(Ordering::Relaxed) != 0
+}
+
+/// Offset of `server_time` from the midpoint of a request sent at `sent_at`
+/// and answered at `received_at`, local times in epoch milliseconds.
+fn estimate_offset(sent_at: u64, server_time: u64, received_at: u64) -> i64 {
+    let midpoint = sent_at + received_at.saturating_sub(sent_at) / 2;
+    server_time as i64 - midpoint as i64
+}
+
+/// Exchange clock minus local clock, in milliseconds, estimated from the
+/// REST server time. Clones share the estimate.
+#[derive(Debug, Clone, Default)]
+pub struct ClockOffset {
+    offset_ms: Arc<AtomicI64>,
+}
+
+impl ClockOffset {
+    pub fn offset_ms(&self) -> i64 {
+        self.offset_ms.load(Ordering::Relaxed)
+    }
+
+    /// Local time `local_ms` expressed on the exchange clock.
+    pub fn to_exchange_time(&self, local_ms: u64) -> i64 {
+        local_ms as i64 + self.offset_ms()
+    }
+
+    /// Fetches the server time and updates the offset, assuming the server
+    /// stamped it halfway through the round trip.
+    pub async fn sync(&self, client: &AsyncBinanceClient) -> Result<i64, CustomError> {
+        let sent_at = current_time_millis();
+        let server_time = client.get_server_time().await?;
+        let received_at = current_time_millis();
+        let offset_ms = estimate_offset(sent_at, server_time, received_at);
+        self.offset_ms.store(offset_ms, Ordering::Relaxed);
+        Ok(offset_ms)
+    }
+
+    /// Resyncs every `interval`, keeping the last estimate when a request fails.
+    pub async fn keep_synced(&self, client: AsyncBinanceClient, interval: Duration) {
+        loop {
+            match self.sync(&client).await {
+                Ok(offset_ms) => info!("Exchange clock offset {} ms", offset_ms),
+                Err(e) => info!("Failed to sync exchange clock: {}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_the_offset_from_the_round_trip_midpoint() {
+        assert_eq!(estimate_offset(1_000, 1_550, 1_100), 500);
+        assert_eq!(estimate_offset(1_000, 900, 1_100), -150);
+        assert_eq!(estimate_offset(1_000, 1_000, 1_000), 0);
+        // A clock stepping back mid-request cannot underflow the midpoint.
+        assert_eq!(estimate_offset(1_000, 1_200, 990), 200);
+    }
+
+    #[test]
+    fn corrects_local_times_onto_the_exchange_clock() {
+        let offset = ClockOffset::default();
+        assert_eq!(offset.to_exchange_time(1_000), 1_000);
+        let clone = offset.clone();
+        clone.offset_ms.store(-250, Ordering::Relaxed);
+        assert_eq!(offset.offset_ms(), -250);
+        assert_eq!(offset.to_exchange_time(1_000), 750);
+    }
+}
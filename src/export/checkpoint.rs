@@ -0,0 +1,184 @@
+use crate::export::exporter::ExportError;
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::AttributeValue;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Key attribute of a `LastEvaluatedKey`; keys can only be strings, numbers
+/// or binary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum KeyValue {
+    S(String),
+    N(String),
+    B(Vec<u8>),
+}
+
+impl KeyValue {
+    fn from_attribute(name: &str, value: &AttributeValue) -> Result<KeyValue, ExportError> {
+        match value {
+            AttributeValue::S(value) => Ok(KeyValue::S(value.clone())),
+            AttributeValue::N(value) => Ok(KeyValue::N(value.clone())),
+            AttributeValue::B(value) => Ok(KeyValue::B(value.as_ref().to_vec())),
+            _ => Err(ExportError::Checkpoint(format!(
+                "unsupported key attribute {}",
+                name
+            ))),
+        }
+    }
+
+    fn to_attribute(&self) -> AttributeValue {
+        match self {
+            KeyValue::S(value) => AttributeValue::S(value.clone()),
+            KeyValue::N(value) => AttributeValue::N(value.clone()),
+            KeyValue::B(value) => AttributeValue::B(Blob::new(value.clone())),
+        }
+    }
+}
+
+/// Progress of one scan segment or query, saved after every roll of
+/// buffered rows into files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Segment count of the scan the checkpoint belongs to; resuming with a
+    /// different count would skip or repeat items.
+    pub total_segments: u32,
+    /// Key to resume from; `None` before the first page.
+    pub start_key: Option<HashMap<String, KeyValue>>,
+    /// Sequence number of the next file written.
+    pub next_file: u64,
+    pub items: u64,
+    pub done: bool,
+}
+
+impl Checkpoint {
+    pub fn path(dir: &Path, unit: &str) -> PathBuf {
+        dir.join("_checkpoints").join(format!("{}.json", unit))
+    }
+
+    /// The saved checkpoint, or a fresh one if there is none.
+    pub fn load(path: &Path, total_segments: u32) -> Result<Checkpoint, ExportError> {
+        let checkpoint = match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice::<Checkpoint>(&bytes)
+                .map_err(|e| ExportError::Checkpoint(e.to_string()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Ok(Checkpoint {
+                    total_segments,
+                    ..Default::default()
+                });
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if checkpoint.total_segments != total_segments {
+            return Err(ExportError::Checkpoint(format!(
+                "{} was written by a {}-segment export",
+                path.display(),
+                checkpoint.total_segments
+            )));
+        }
+        Ok(checkpoint)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ExportError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let partial = path.with_extension("json.part");
+        let bytes =
+            serde_json::to_vec_pretty(self).map_err(|e| ExportError::Checkpoint(e.to_string()))?;
+        fs::write(&partial, bytes)?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    pub fn start_key(&self) -> Option<HashMap<String, AttributeValue>> {
+        self.start_key.as_ref().map(|key| {
+            key.iter()
+                .map(|(name, value)| (name.clone(), value.to_attribute()))
+                .collect()
+        })
+    }
+
+    pub fn set_start_key(
+        &mut self,
+        key: Option<&HashMap<String, AttributeValue>>,
+    ) -> Result<(), ExportError> {
+        self.start_key = key
+            .map(|key| {
+                key.iter()
+                    .map(|(name, value)| Ok((name.clone(), KeyValue::from_attribute(name, value)?)))
+                    .collect::<Result<HashMap<_, _>, ExportError>>()
+            })
+            .transpose()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn round_trips_string_number_and_binary_keys() {
+        let dir = temp_dir("export-checkpoint");
+        let path = Checkpoint::path(&dir, "segment-0001");
+        let key = HashMap::from([
+            (
+                "PK".to_string(),
+                AttributeValue::S("BTCUSDT@bookTicker".to_string()),
+            ),
+            (
+                "SK".to_string(),
+                AttributeValue::N("1700000000000".to_string()),
+            ),
+            (
+                "blob".to_string(),
+                AttributeValue::B(Blob::new(vec![0, 1, 255])),
+            ),
+        ]);
+        let mut checkpoint = Checkpoint::load(&path, 4).unwrap();
+        assert!(checkpoint.start_key().is_none());
+        checkpoint.set_start_key(Some(&key)).unwrap();
+        checkpoint.next_file = 7;
+        checkpoint.items = 1234;
+        checkpoint.save(&path).unwrap();
+
+        let loaded = Checkpoint::load(&path, 4).unwrap();
+        assert_eq!(loaded.start_key(), Some(key));
+        assert_eq!(loaded.next_file, 7);
+        assert_eq!(loaded.items, 1234);
+        assert!(!loaded.done);
+        assert!(!path.with_extension("json.part").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_checkpoint_of_a_different_segment_count() {
+        let dir = temp_dir("export-checkpoint-segments");
+        let path = Checkpoint::path(&dir, "segment-0000");
+        Checkpoint::load(&path, 4).unwrap().save(&path).unwrap();
+        assert!(matches!(
+            Checkpoint::load(&path, 8),
+            Err(ExportError::Checkpoint(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_keys_that_cannot_be_resumed_from() {
+        let mut checkpoint = Checkpoint::default();
+        let key = HashMap::from([("PK".to_string(), AttributeValue::Bool(true))]);
+        assert!(matches!(
+            checkpoint.set_start_key(Some(&key)),
+            Err(ExportError::Checkpoint(_))
+        ));
+    }
+}
@@ -0,0 +1,52 @@
+use crate::aws_resources::item_attributes::ItemError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Utf8,
+    Float64,
+    UInt64,
+    Boolean,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Column {
+    pub name: &'static str,
+    pub kind: ColumnType,
+}
+
+impl Column {
+    pub const fn new(name: &'static str, kind: ColumnType) -> Self {
+        Column { name, kind }
+    }
+}
+
+/// One cell, matching its column's `ColumnType`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Utf8(String),
+    Float64(f64),
+    UInt64(u64),
+    Boolean(bool),
+}
+
+impl Value {
+    /// Parses a decimal attribute such as a price into a `Float64` cell.
+    pub fn decimal(column: &str, value: &str) -> Result<Value, ItemError> {
+        value
+            .parse()
+            .map(Value::Float64)
+            .map_err(|_| ItemError::InvalidAttribute {
+                attribute: column.to_string(),
+                value: value.to_string(),
+            })
+    }
+}
+
+/// An exported item with the fields used to partition the output.
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub symbol: String,
+    /// Epoch milliseconds; selects the `date=` partition.
+    pub event_time: u64,
+    pub values: Vec<Value>,
+}
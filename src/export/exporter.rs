@@ -0,0 +1,522 @@
+use crate::aws_resources::item_attributes::ItemError;
+use crate::export::checkpoint::Checkpoint;
+use crate::export::columns::{Row, Value};
+use crate::export::tables::ExportTable;
+use crate::export::writer::ExportFormat;
+use aws_sdk_dynamodb::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::info;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error(transparent)]
+    DynamoError(Box<aws_sdk_dynamodb::Error>),
+    #[error(transparent)]
+    Item(#[from] ItemError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error(transparent)]
+    Arrow(#[from] arrow_schema::ArrowError),
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error("invalid checkpoint: {0}")]
+    Checkpoint(String),
+    #[error("export task failed: {0}")]
+    Task(String),
+}
+
+impl From<aws_sdk_dynamodb::Error> for ExportError {
+    fn from(error: aws_sdk_dynamodb::Error) -> Self {
+        ExportError::DynamoError(Box::new(error))
+    }
+}
+
+/// Which items to export.
+#[derive(Debug, Clone)]
+pub enum ExportSelection {
+    /// The whole table, read with a parallel scan of `segments` segments.
+    Scan { segments: u32 },
+    /// One symbol's items with event times in `from..=to` milliseconds.
+    Query { symbol: String, from: u64, to: u64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportConfig {
+    pub out_dir: PathBuf,
+    pub format: ExportFormat,
+    pub selection: ExportSelection,
+    /// Items per page; DynamoDB also stops a page at 1 MB.
+    pub page_size: Option<i32>,
+    /// Rows in one file; a partition reaching it rolls the buffered rows
+    /// into files.
+    pub max_rows_per_file: usize,
+    /// Estimated size of the rows buffered across all partitions before
+    /// they are rolled into files.
+    pub max_buffered_bytes: usize,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        ExportConfig {
+            out_dir: PathBuf::from("export"),
+            format: ExportFormat::Parquet,
+            selection: ExportSelection::Scan { segments: 4 },
+            page_size: None,
+            max_rows_per_file: 1_000_000,
+            max_buffered_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportSummary {
+    pub items: u64,
+    pub files: u64,
+    /// Segments or queries already completed by an earlier run.
+    pub resumed: u64,
+}
+
+/// Part of an export with its own checkpoint.
+#[derive(Debug, Clone)]
+enum ExportUnit {
+    Segment { segment: u32, segments: u32 },
+    Query { symbol: String, from: u64, to: u64 },
+}
+
+impl ExportUnit {
+    fn label(&self) -> String {
+        match self {
+            ExportUnit::Segment { segment, .. } => format!("segment-{:04}", segment),
+            ExportUnit::Query { symbol, from, to } => format!("query-{}-{}-{}", symbol, from, to),
+        }
+    }
+
+    fn total_segments(&self) -> u32 {
+        match self {
+            ExportUnit::Segment { segments, .. } => *segments,
+            ExportUnit::Query { .. } => 1,
+        }
+    }
+}
+
+type Item = HashMap<String, AttributeValue>;
+
+/// Rows read by an export unit and not yet written, by `(date, symbol)`.
+#[derive(Debug, Default)]
+struct PartitionBuffer {
+    partitions: BTreeMap<(String, String), Vec<Row>>,
+    bytes: usize,
+    /// Rows in the largest partition.
+    largest: usize,
+}
+
+impl PartitionBuffer {
+    fn push(&mut self, row: Row) {
+        self.bytes += estimated_size(&row);
+        let rows = self
+            .partitions
+            .entry((event_date(row.event_time), row.symbol.clone()))
+            .or_default();
+        rows.push(row);
+        self.largest = self.largest.max(rows.len());
+    }
+
+    fn is_empty(&self) -> bool {
+        self.partitions.is_empty()
+    }
+
+    /// Whether the buffered rows should be rolled into files.
+    fn is_full(&self, config: &ExportConfig) -> bool {
+        self.largest >= config.max_rows_per_file || self.bytes >= config.max_buffered_bytes
+    }
+
+    fn take(&mut self) -> BTreeMap<(String, String), Vec<Row>> {
+        self.bytes = 0;
+        self.largest = 0;
+        std::mem::take(&mut self.partitions)
+    }
+}
+
+/// Exports a table into `<out_dir>/<table>/date=<YYYY-MM-DD>/symbol=<symbol>/`
+/// files. Rows are buffered per partition across pages and rolled into
+/// files once a partition reaches `max_rows_per_file` or the buffer
+/// reaches `max_buffered_bytes`. Each roll writes every buffered partition,
+/// so the checkpoint saved after it points past the last page read, and
+/// running the same export again resumes there; files of a roll that was
+/// not checkpointed are rewritten under the same names.
+#[derive(Debug, Clone)]
+pub struct Exporter<T: ExportTable> {
+    client: aws_sdk_dynamodb::Client,
+    table: T,
+    config: ExportConfig,
+}
+
+impl<T: ExportTable> Exporter<T> {
+    pub fn new(client: aws_sdk_dynamodb::Client, table: T, config: ExportConfig) -> Self {
+        Exporter {
+            client,
+            table,
+            config,
+        }
+    }
+
+    pub async fn run(&self) -> Result<ExportSummary, ExportError> {
+        let units = match &self.config.selection {
+            ExportSelection::Scan { segments } => {
+                let segments = (*segments).max(1);
+                (0..segments)
+                    .map(|segment| ExportUnit::Segment { segment, segments })
+                    .collect()
+            }
+            ExportSelection::Query { symbol, from, to } => vec![ExportUnit::Query {
+                symbol: symbol.to_uppercase(),
+                from: *from,
+                to: *to,
+            }],
+        };
+        let tasks: Vec<_> = units
+            .into_iter()
+            .map(|unit| {
+                let exporter = self.clone();
+                tokio::spawn(async move { exporter.export_unit(unit).await })
+            })
+            .collect();
+        let mut summary = ExportSummary::default();
+        for task in tasks {
+            let unit = task.await.map_err(|e| ExportError::Task(e.to_string()))??;
+            summary.items += unit.items;
+            summary.files += unit.files;
+            summary.resumed += unit.resumed;
+        }
+        info!(
+            "Exported {} {} items to {} files",
+            summary.items,
+            self.table.name(),
+            summary.files
+        );
+        Ok(summary)
+    }
+
+    async fn export_unit(&self, unit: ExportUnit) -> Result<ExportSummary, ExportError> {
+        let dir = self.config.out_dir.join(self.table.name());
+        let label = unit.label();
+        let checkpoint_path = Checkpoint::path(&dir, &label);
+        let mut checkpoint = Checkpoint::load(&checkpoint_path, unit.total_segments())?;
+        let mut summary = ExportSummary::default();
+        if checkpoint.done {
+            summary.resumed = 1;
+            return Ok(summary);
+        }
+        if checkpoint.start_key.is_some() {
+            info!(
+                "Resuming {} export {} after {} items",
+                self.table.name(),
+                label,
+                checkpoint.items
+            );
+        }
+        let mut buffer = PartitionBuffer::default();
+        loop {
+            let (items, last_key) = self.fetch_page(&unit, checkpoint.start_key()).await?;
+            for item in &items {
+                buffer.push(self.table.item_to_row(item)?);
+            }
+            checkpoint.items += items.len() as u64;
+            summary.items += items.len() as u64;
+            checkpoint.set_start_key(last_key.as_ref())?;
+            checkpoint.done = last_key.is_none();
+            if !(buffer.is_full(&self.config) || checkpoint.done) {
+                continue;
+            }
+            if !buffer.is_empty() {
+                summary.files += self
+                    .roll(&dir, &label, &mut checkpoint, &mut buffer)
+                    .await?;
+            }
+            checkpoint.save(&checkpoint_path)?;
+            if checkpoint.done {
+                return Ok(summary);
+            }
+        }
+    }
+
+    /// Writes every buffered partition into files of at most
+    /// `max_rows_per_file` rows and returns how many were written.
+    async fn roll(
+        &self,
+        dir: &Path,
+        label: &str,
+        checkpoint: &mut Checkpoint,
+        buffer: &mut PartitionBuffer,
+    ) -> Result<u64, ExportError> {
+        let max_rows = self.config.max_rows_per_file.max(1);
+        let mut files = Vec::new();
+        for ((date, symbol), rows) in buffer.take() {
+            let partition = dir
+                .join(format!("date={}", date))
+                .join(format!("symbol={}", symbol));
+            let mut rows = rows.into_iter().peekable();
+            while rows.peek().is_some() {
+                let path = partition.join(format!(
+                    "part-{}-{:06}.{}",
+                    label,
+                    checkpoint.next_file,
+                    self.config.format.extension()
+                ));
+                checkpoint.next_file += 1;
+                files.push((path, rows.by_ref().take(max_rows).collect::<Vec<_>>()));
+            }
+        }
+        let file_count = files.len() as u64;
+        let format = self.config.format;
+        let columns = self.table.columns();
+        tokio::task::spawn_blocking(move || {
+            files
+                .iter()
+                .try_for_each(|(path, rows)| format.write(path, columns, rows))
+        })
+        .await
+        .map_err(|e| ExportError::Task(e.to_string()))??;
+        Ok(file_count)
+    }
+
+    async fn fetch_page(
+        &self,
+        unit: &ExportUnit,
+        start_key: Option<Item>,
+    ) -> Result<(Vec<Item>, Option<Item>), ExportError> {
+        let table = self.table.table();
+        match unit {
+            ExportUnit::Segment { segment, segments } => {
+                let output = self
+                    .client
+                    .scan()
+                    .table_name(&table.table_name)
+                    .segment(*segment as i32)
+                    .total_segments(*segments as i32)
+                    .set_exclusive_start_key(start_key)
+                    .set_limit(self.config.page_size)
+                    .send()
+                    .await
+                    .map_err(aws_sdk_dynamodb::Error::from)?;
+                Ok((output.items.unwrap_or_default(), output.last_evaluated_key))
+            }
+            ExportUnit::Query { symbol, from, to } => {
+                let (lower, upper) = self.table.sort_key_range(*from, *to);
+                let output = self
+                    .client
+                    .query()
+                    .table_name(&table.table_name)
+                    .key_condition_expression("#pk = :pk AND #sk BETWEEN :from AND :to")
+                    .expression_attribute_names("#pk", &table.partition_key)
+                    .expression_attribute_names("#sk", &table.sort_key)
+                    .expression_attribute_values(":pk", self.table.partition_key_value(symbol))
+                    .expression_attribute_values(":from", lower)
+                    .expression_attribute_values(":to", upper)
+                    .set_exclusive_start_key(start_key)
+                    .set_limit(self.config.page_size)
+                    .send()
+                    .await
+                    .map_err(aws_sdk_dynamodb::Error::from)?;
+                Ok((output.items.unwrap_or_default(), output.last_evaluated_key))
+            }
+        }
+    }
+}
+
+/// UTC date of an epoch millisecond timestamp as `YYYY-MM-DD`.
+fn event_date(event_time: u64) -> String {
+    DateTime::from_millis(event_time as i64)
+        .fmt(DateTimeFormat::DateTime)
+        .map(|formatted| formatted[..10].to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Rough in-memory size of a row, for `max_buffered_bytes`.
+fn estimated_size(row: &Row) -> usize {
+    row.symbol.len()
+        + row
+            .values
+            .iter()
+            .map(|value| match value {
+                Value::Utf8(value) => value.len(),
+                Value::Float64(_) | Value::UInt64(_) => 8,
+                Value::Boolean(_) => 1,
+            })
+            .sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookticker_stream::ticker_db::TickerTableConfig;
+    use aws_sdk_dynamodb::config::{BehaviorVersion, Region};
+    use std::fs;
+
+    fn row(symbol: &str, event_time: u64) -> Row {
+        Row {
+            symbol: symbol.to_string(),
+            event_time,
+            values: vec![
+                Value::Utf8(symbol.to_string()),
+                Value::Utf8("bookTicker".to_string()),
+                Value::UInt64(event_time),
+                Value::UInt64(event_time),
+                Value::UInt64(1),
+                Value::Float64(1.5),
+                Value::Float64(2.0),
+                Value::Float64(1.6),
+                Value::Float64(3.0),
+            ],
+        }
+    }
+
+    fn exporter(out_dir: PathBuf, max_rows_per_file: usize) -> Exporter<TickerTableConfig> {
+        let client = aws_sdk_dynamodb::Client::from_conf(
+            aws_sdk_dynamodb::Config::builder()
+                .behavior_version(BehaviorVersion::latest())
+                .region(Region::new("us-east-1"))
+                .build(),
+        );
+        Exporter::new(
+            client,
+            TickerTableConfig::default(),
+            ExportConfig {
+                out_dir,
+                format: ExportFormat::Csv,
+                max_rows_per_file,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut files = Vec::new();
+        for date in fs::read_dir(dir).unwrap() {
+            let date = date.unwrap().path();
+            if !date
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("date=")
+            {
+                continue;
+            }
+            for symbol in fs::read_dir(&date).unwrap() {
+                for file in fs::read_dir(symbol.unwrap().path()).unwrap() {
+                    let path = file.unwrap().path();
+                    files.push(path.strip_prefix(dir).unwrap().display().to_string());
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn formats_event_dates_in_utc() {
+        assert_eq!(event_date(0), "1970-01-01");
+        assert_eq!(event_date(1_700_000_000_000), "2023-11-14");
+        assert_eq!(event_date(1_699_999_199_999), "2023-11-14");
+        assert_eq!(event_date(1_699_919_999_999), "2023-11-13");
+    }
+
+    #[test]
+    fn tracks_the_largest_partition_and_buffered_size() {
+        let mut buffer = PartitionBuffer::default();
+        assert!(buffer.is_empty());
+        buffer.push(row("BTCUSDT", 1_700_000_000_000));
+        buffer.push(row("BTCUSDT", 1_700_000_000_001));
+        buffer.push(row("ETHUSDT", 1_700_000_000_002));
+        // Next day, so a partition of its own.
+        buffer.push(row("BTCUSDT", 1_700_086_400_000));
+        assert_eq!(buffer.largest, 2);
+        // Symbol twice, event name, seven numbers.
+        assert_eq!(buffer.bytes, 4 * (7 + 7 + 10 + 7 * 8));
+        let partitions = buffer.take();
+        assert_eq!(partitions.len(), 3);
+        assert_eq!(
+            partitions[&("2023-11-14".to_string(), "BTCUSDT".to_string())].len(),
+            2
+        );
+        assert!(buffer.is_empty());
+        assert_eq!((buffer.largest, buffer.bytes), (0, 0));
+    }
+
+    #[test]
+    fn is_full_once_a_partition_or_the_buffer_reaches_its_limit() {
+        let config = ExportConfig {
+            max_rows_per_file: 3,
+            max_buffered_bytes: 5 * 80,
+            ..Default::default()
+        };
+        let mut buffer = PartitionBuffer::default();
+        buffer.push(row("BTCUSDT", 1_700_000_000_000));
+        buffer.push(row("BTCUSDT", 1_700_000_000_001));
+        assert!(!buffer.is_full(&config));
+        buffer.push(row("BTCUSDT", 1_700_000_000_002));
+        assert!(buffer.is_full(&config));
+
+        // Rows of 80 bytes in partitions of their own.
+        let mut buffer = PartitionBuffer::default();
+        for symbol in ["AAAUSDT", "BBBUSDT", "CCCUSDT", "DDDUSDT"] {
+            buffer.push(row(symbol, 1_700_000_000_000));
+        }
+        assert!(!buffer.is_full(&config));
+        buffer.push(row("EEEUSDT", 1_700_000_000_000));
+        assert_eq!(buffer.largest, 1);
+        assert!(buffer.is_full(&config));
+    }
+
+    #[tokio::test]
+    async fn rolls_partitions_into_files_named_stably_across_resumes() {
+        let out_dir = std::env::temp_dir().join(format!("export-roll-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&out_dir);
+        let exporter = exporter(out_dir.clone(), 2);
+        let dir = out_dir.join("tickers");
+        let fill = || {
+            let mut buffer = PartitionBuffer::default();
+            for offset in 0..3 {
+                buffer.push(row("BTCUSDT", 1_700_000_000_000 + offset));
+            }
+            buffer.push(row("ETHUSDT", 1_700_000_000_000));
+            buffer
+        };
+        let saved = Checkpoint {
+            total_segments: 1,
+            next_file: 5,
+            ..Default::default()
+        };
+
+        let mut checkpoint = saved.clone();
+        let written = exporter
+            .roll(&dir, "segment-0000", &mut checkpoint, &mut fill())
+            .await
+            .unwrap();
+        assert_eq!(written, 3);
+        assert_eq!(checkpoint.next_file, 8);
+        let expected = vec![
+            "date=2023-11-14/symbol=BTCUSDT/part-segment-0000-000005.csv",
+            "date=2023-11-14/symbol=BTCUSDT/part-segment-0000-000006.csv",
+            "date=2023-11-14/symbol=ETHUSDT/part-segment-0000-000007.csv",
+        ];
+        assert_eq!(files(&dir), expected);
+        let first = fs::read_to_string(dir.join(expected[0])).unwrap();
+        assert_eq!(first.lines().count(), 3);
+
+        // A run resumed from the same checkpoint rewrites the same files.
+        let mut resumed = saved;
+        exporter
+            .roll(&dir, "segment-0000", &mut resumed, &mut fill())
+            .await
+            .unwrap();
+        assert_eq!(resumed.next_file, 8);
+        assert_eq!(files(&dir), expected);
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
@@ -0,0 +1,5 @@
+pub mod checkpoint;
+pub mod columns;
+pub mod exporter;
+pub mod tables;
+pub mod writer;
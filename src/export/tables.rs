@@ -0,0 +1,166 @@
+use crate::aws_resources::dynamodb_tables::TableConfig;
+use crate::aws_resources::item_attributes::ItemError;
+use crate::bookticker_stream::ticker_db::TickerTableConfig;
+use crate::bookticker_stream::ticker_query::BOOK_TICKER_EVENT;
+use crate::export::columns::{Column, ColumnType, Row, Value};
+use crate::order_stream::order_db::OrderTableConfig;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+/// A DynamoDB table the exporter knows how to turn into typed rows.
+pub trait ExportTable: Clone + Send + Sync + 'static {
+    /// Directory the table's files are written under.
+    fn name(&self) -> &'static str;
+
+    fn table(&self) -> &TableConfig;
+
+    fn columns(&self) -> &'static [Column];
+
+    /// Cells in `columns` order.
+    fn item_to_row(&self, item: &HashMap<String, AttributeValue>) -> Result<Row, ItemError>;
+
+    /// Partition key value holding `symbol`'s items.
+    fn partition_key_value(&self, symbol: &str) -> AttributeValue;
+
+    /// Sort key bounds of the items with event times in `from..=to`.
+    fn sort_key_range(&self, from: u64, to: u64) -> (AttributeValue, AttributeValue);
+}
+
+const TICKER_COLUMNS: &[Column] = &[
+    Column::new("symbol", ColumnType::Utf8),
+    Column::new("event", ColumnType::Utf8),
+    Column::new("event_time", ColumnType::UInt64),
+    Column::new("trans_time", ColumnType::UInt64),
+    Column::new("update_id", ColumnType::UInt64),
+    Column::new("best_bid", ColumnType::Float64),
+    Column::new("bid_qty", ColumnType::Float64),
+    Column::new("best_ask", ColumnType::Float64),
+    Column::new("ask_qty", ColumnType::Float64),
+];
+
+impl ExportTable for TickerTableConfig {
+    fn name(&self) -> &'static str {
+        "tickers"
+    }
+
+    fn table(&self) -> &TableConfig {
+        &self.table
+    }
+
+    fn columns(&self) -> &'static [Column] {
+        TICKER_COLUMNS
+    }
+
+    fn item_to_row(&self, item: &HashMap<String, AttributeValue>) -> Result<Row, ItemError> {
+        let ticker = self.item_to_ticker(item)?;
+        let values = vec![
+            Value::Utf8(ticker.symbol.clone()),
+            Value::Utf8(ticker.event),
+            Value::UInt64(ticker.event_time),
+            Value::UInt64(ticker.trans_time),
+            Value::UInt64(ticker.update_id),
+            Value::decimal("best_bid", &ticker.best_bid)?,
+            Value::decimal("bid_qty", &ticker.bid_qty)?,
+            Value::decimal("best_ask", &ticker.best_ask)?,
+            Value::decimal("ask_qty", &ticker.ask_qty)?,
+        ];
+        Ok(Row {
+            symbol: ticker.symbol,
+            event_time: ticker.event_time,
+            values,
+        })
+    }
+
+    fn partition_key_value(&self, symbol: &str) -> AttributeValue {
+        AttributeValue::S(TickerTableConfig::partition_key_value(
+            self,
+            &symbol.to_uppercase(),
+            BOOK_TICKER_EVENT,
+        ))
+    }
+
+    fn sort_key_range(&self, from: u64, to: u64) -> (AttributeValue, AttributeValue) {
+        (self.sort_key_value(from), self.sort_key_value(to))
+    }
+}
+
+const ORDER_COLUMNS: &[Column] = &[
+    Column::new("symbol", ColumnType::Utf8),
+    Column::new("order_id", ColumnType::UInt64),
+    Column::new("client_order_id", ColumnType::Utf8),
+    Column::new("side", ColumnType::Utf8),
+    Column::new("order_type", ColumnType::Utf8),
+    Column::new("execution_type", ColumnType::Utf8),
+    Column::new("status", ColumnType::Utf8),
+    Column::new("price", ColumnType::Float64),
+    Column::new("quantity", ColumnType::Float64),
+    Column::new("average_price", ColumnType::Float64),
+    Column::new("last_filled_price", ColumnType::Float64),
+    Column::new("last_filled_quantity", ColumnType::Float64),
+    Column::new("filled_quantity", ColumnType::Float64),
+    Column::new("commission", ColumnType::Float64),
+    Column::new("commission_asset", ColumnType::Utf8),
+    Column::new("realized_profit", ColumnType::Float64),
+    Column::new("trade_id", ColumnType::UInt64),
+    Column::new("maker", ColumnType::Boolean),
+    Column::new("reduce_only", ColumnType::Boolean),
+    Column::new("event_time", ColumnType::UInt64),
+    Column::new("trade_time", ColumnType::UInt64),
+];
+
+impl ExportTable for OrderTableConfig {
+    fn name(&self) -> &'static str {
+        "orders"
+    }
+
+    fn table(&self) -> &TableConfig {
+        &self.table
+    }
+
+    fn columns(&self) -> &'static [Column] {
+        ORDER_COLUMNS
+    }
+
+    fn item_to_row(&self, item: &HashMap<String, AttributeValue>) -> Result<Row, ItemError> {
+        let order = self.item_to_order(item)?;
+        let values = vec![
+            Value::Utf8(order.symbol.clone()),
+            Value::UInt64(order.order_id),
+            Value::Utf8(order.client_order_id),
+            Value::Utf8(order.side),
+            Value::Utf8(order.order_type),
+            Value::Utf8(order.execution_type),
+            Value::Utf8(order.status),
+            Value::decimal("price", &order.price)?,
+            Value::decimal("quantity", &order.quantity)?,
+            Value::decimal("average_price", &order.average_price)?,
+            Value::decimal("last_filled_price", &order.last_filled_price)?,
+            Value::decimal("last_filled_quantity", &order.last_filled_quantity)?,
+            Value::decimal("filled_quantity", &order.filled_quantity)?,
+            Value::decimal("commission", &order.commission)?,
+            Value::Utf8(order.commission_asset),
+            Value::decimal("realized_profit", &order.realized_profit)?,
+            Value::UInt64(order.trade_id),
+            Value::Boolean(order.maker),
+            Value::Boolean(order.reduce_only),
+            Value::UInt64(order.event_time),
+            Value::UInt64(order.trade_time),
+        ];
+        Ok(Row {
+            symbol: order.symbol,
+            event_time: order.event_time,
+            values,
+        })
+    }
+
+    fn partition_key_value(&self, symbol: &str) -> AttributeValue {
+        AttributeValue::S(symbol.to_uppercase())
+    }
+
+    fn sort_key_range(&self, from: u64, to: u64) -> (AttributeValue, AttributeValue) {
+        (
+            AttributeValue::S(self.sort_key_lower_bound(from)),
+            AttributeValue::S(self.sort_key_upper_bound(to)),
+        )
+    }
+}
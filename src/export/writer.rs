@@ -0,0 +1,122 @@
+use crate::export::columns::{Column, ColumnType, Row, Value};
+use crate::export::exporter::ExportError;
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Parquet,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Option<ExportFormat> {
+        match format {
+            "parquet" => Some(ExportFormat::Parquet),
+            "csv" => Some(ExportFormat::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Parquet => "parquet",
+            ExportFormat::Csv => "csv",
+        }
+    }
+
+    /// Writes `rows` to `path` through a temporary file, so a file at `path`
+    /// is always complete.
+    pub fn write(&self, path: &Path, columns: &[Column], rows: &[Row]) -> Result<(), ExportError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let partial = path.with_extension(format!("{}.part", self.extension()));
+        match self {
+            ExportFormat::Parquet => write_parquet(&partial, columns, rows)?,
+            ExportFormat::Csv => write_csv(&partial, columns, rows)?,
+        }
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+}
+
+fn write_parquet(path: &Path, columns: &[Column], rows: &[Row]) -> Result<(), ExportError> {
+    let schema = Arc::new(Schema::new(
+        columns
+            .iter()
+            .map(|column| {
+                let data_type = match column.kind {
+                    ColumnType::Utf8 => DataType::Utf8,
+                    ColumnType::Float64 => DataType::Float64,
+                    ColumnType::UInt64 => DataType::UInt64,
+                    ColumnType::Boolean => DataType::Boolean,
+                };
+                Field::new(column.name, data_type, false)
+            })
+            .collect::<Vec<_>>(),
+    ));
+    let arrays: Vec<ArrayRef> = columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            let cells = rows.iter().map(move |row| &row.values[index]);
+            let array: ArrayRef =
+                match column.kind {
+                    ColumnType::Utf8 => Arc::new(StringArray::from_iter_values(cells.map(
+                        |cell| match cell {
+                            Value::Utf8(value) => value.as_str(),
+                            _ => "",
+                        },
+                    ))),
+                    ColumnType::Float64 => Arc::new(Float64Array::from_iter_values(cells.map(
+                        |cell| match cell {
+                            Value::Float64(value) => *value,
+                            _ => f64::NAN,
+                        },
+                    ))),
+                    ColumnType::UInt64 => Arc::new(UInt64Array::from_iter_values(cells.map(
+                        |cell| match cell {
+                            Value::UInt64(value) => *value,
+                            _ => 0,
+                        },
+                    ))),
+                    ColumnType::Boolean => Arc::new(BooleanArray::from(
+                        cells
+                            .map(|cell| matches!(cell, Value::Boolean(true)))
+                            .collect::<Vec<_>>(),
+                    )),
+                };
+            array
+        })
+        .collect();
+    let batch = RecordBatch::try_new(Arc::clone(&schema), arrays)?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn write_csv(path: &Path, columns: &[Column], rows: &[Row]) -> Result<(), ExportError> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(columns.iter().map(|column| column.name))?;
+    for row in rows {
+        writer.write_record(row.values.iter().map(|value| match value {
+            Value::Utf8(value) => value.clone(),
+            Value::Float64(value) => value.to_string(),
+            Value::UInt64(value) => value.to_string(),
+            Value::Boolean(value) => value.to_string(),
+        }))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
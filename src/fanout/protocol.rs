@@ -0,0 +1,59 @@
+use crate::bookticker_stream::bookticker::BestPrices;
+use serde::{Deserialize, Serialize};
+
+/// Subscribes every symbol when passed in `symbols`.
+pub const ALL_SYMBOLS: &str = "*";
+
+/// Requests a client sends as JSON text frames, e.g.
+/// `{"op":"subscribe","symbols":["BTCUSDT"]}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ClientRequest {
+    Subscribe {
+        symbols: Vec<String>,
+    },
+    Unsubscribe {
+        symbols: Vec<String>,
+    },
+    /// Authenticates the session as the account owning `token`.
+    Auth {
+        token: String,
+    },
+    /// Requires a prior `auth`.
+    SubscribeUserData,
+    UnsubscribeUserData,
+}
+
+impl ClientRequest {
+    pub fn op(&self) -> &'static str {
+        match self {
+            ClientRequest::Subscribe { .. } => "subscribe",
+            ClientRequest::Unsubscribe { .. } => "unsubscribe",
+            ClientRequest::Auth { .. } => "auth",
+            ClientRequest::SubscribeUserData => "subscribe_user_data",
+            ClientRequest::UnsubscribeUserData => "unsubscribe_user_data",
+        }
+    }
+}
+
+/// Messages sent to clients as JSON text frames.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Book ticker with prices and quantities parsed to numbers.
+    Quote {
+        symbol: String,
+        #[serde(flatten)]
+        prices: BestPrices,
+    },
+    UserData {
+        account: String,
+        event: serde_json::Value,
+    },
+    Ack {
+        op: &'static str,
+    },
+    Error {
+        message: String,
+    },
+}
@@ -0,0 +1,517 @@
+use crate::bookticker_stream::bookticker::BookTickerStream;
+use crate::fanout::protocol::{ClientRequest, ServerMessage, ALL_SYMBOLS};
+use crate::order_stream::messages::UserDataUpdate;
+use crate::order_stream::order_update::UserDataStream;
+use futures::{SinkExt, StreamExt};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{accept_async, WebSocketStream};
+use tracing::info;
+
+pub const DEFAULT_FANOUT_ADDR: &str = "127.0.0.1:8081";
+
+#[derive(Debug, Clone)]
+pub struct FanoutConfig {
+    pub addr: SocketAddr,
+    /// Quotes, across every symbol, a client may fall behind before it is
+    /// disconnected.
+    pub client_buffer: usize,
+    /// Account name by access token, for user data subscriptions.
+    pub tokens: HashMap<String, String>,
+}
+
+impl Default for FanoutConfig {
+    fn default() -> Self {
+        FanoutConfig {
+            addr: DEFAULT_FANOUT_ADDR
+                .parse()
+                .expect("default address is valid"),
+            client_buffer: 4096,
+            tokens: HashMap::new(),
+        }
+    }
+}
+
+impl FanoutConfig {
+    /// `FANOUT_ADDR` overrides the listen address. `FANOUT_TOKENS` lists
+    /// `account=token` pairs separated by commas.
+    pub fn from_env() -> Self {
+        let mut config = FanoutConfig::default();
+        if let Some(addr) = std::env::var("FANOUT_ADDR")
+            .ok()
+            .and_then(|addr| addr.parse().ok())
+        {
+            config.addr = addr;
+        }
+        if let Ok(tokens) = std::env::var("FANOUT_TOKENS") {
+            config.tokens = tokens
+                .split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(account, token)| (token.trim().to_string(), account.trim().to_string()))
+                .collect();
+        }
+        config
+    }
+}
+
+#[derive(Debug, Default)]
+struct FanoutCounters {
+    clients: AtomicU64,
+    accepted: AtomicU64,
+    slow_consumers: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct FanoutStatsSnapshot {
+    /// Clients currently connected.
+    pub clients: u64,
+    pub accepted: u64,
+    /// Clients disconnected for falling behind.
+    pub slow_consumers: u64,
+}
+
+/// Local websocket server re-broadcasting quotes from a `BookTickerStream`,
+/// and optionally user data events, so several processes can share one set
+/// of Binance connections. Every client gets its own bounded buffer and is
+/// disconnected when it fills up rather than slowing the others down.
+#[derive(Debug, Clone)]
+pub struct FanoutServer {
+    config: FanoutConfig,
+    book_ticker: BookTickerStream,
+    user_data: HashMap<String, UserDataStream>,
+    counters: Arc<FanoutCounters>,
+    /// Accepted quotes, each encoded once for every client.
+    quotes: broadcast::Sender<Arc<EncodedQuote>>,
+}
+
+/// A `ServerMessage::Quote` serialized once and shared by every client.
+#[derive(Debug)]
+struct EncodedQuote {
+    symbol: String,
+    text: String,
+}
+
+/// Per-client subscription state.
+#[derive(Default)]
+struct ClientSession {
+    symbols: HashSet<String>,
+    all_symbols: bool,
+    quotes: Option<broadcast::Receiver<Arc<EncodedQuote>>>,
+    account: Option<String>,
+    user_data: Option<broadcast::Receiver<Arc<UserDataUpdate>>>,
+}
+
+impl ClientSession {
+    fn wants(&self, symbol: &str) -> bool {
+        self.all_symbols || self.symbols.contains(symbol)
+    }
+}
+
+/// A frame to send to a client.
+enum Reply {
+    Message(ServerMessage),
+    Quote(Arc<EncodedQuote>),
+}
+
+/// Why a client was disconnected by the server.
+enum Disconnect {
+    SlowConsumer,
+    Closed,
+}
+
+impl FanoutServer {
+    pub fn new(book_ticker: BookTickerStream, config: FanoutConfig) -> Self {
+        let (quotes, _) = broadcast::channel(config.client_buffer.max(1));
+        FanoutServer {
+            quotes,
+            config,
+            book_ticker,
+            user_data: HashMap::new(),
+            counters: Arc::new(FanoutCounters::default()),
+        }
+    }
+
+    /// Offers `stream`'s events to clients authenticated as `account`.
+    pub fn with_user_data(mut self, account: impl Into<String>, stream: UserDataStream) -> Self {
+        self.user_data.insert(account.into(), stream);
+        self
+    }
+
+    pub fn stats(&self) -> FanoutStatsSnapshot {
+        FanoutStatsSnapshot {
+            clients: self.counters.clients.load(Ordering::Relaxed),
+            accepted: self.counters.accepted.load(Ordering::Relaxed),
+            slow_consumers: self.counters.slow_consumers.load(Ordering::Relaxed),
+        }
+    }
+
+    pub async fn serve(&self) -> std::io::Result<()> {
+        let listener = TcpListener::bind(self.config.addr).await?;
+        info!("Fan-out server listening on {}", self.config.addr);
+        let _encoder = self.spawn_quote_encoder();
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let server = self.clone();
+            tokio::spawn(async move { server.handle_client(stream, peer).await });
+        }
+    }
+
+    /// Serializes every accepted quote once for all clients.
+    fn spawn_quote_encoder(&self) -> JoinHandle<()> {
+        let mut accepted = self.book_ticker.quotes();
+        let quotes = self.quotes.clone();
+        tokio::spawn(async move {
+            loop {
+                let quote = match accepted.recv().await {
+                    Ok(quote) => quote,
+                    Err(RecvError::Lagged(skipped)) => {
+                        info!(
+                            "Fan-out skipped {} quotes it could not encode in time",
+                            skipped
+                        );
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if quotes.receiver_count() == 0 {
+                    continue;
+                }
+                let message = ServerMessage::Quote {
+                    symbol: quote.symbol.clone(),
+                    prices: quote.prices.clone(),
+                };
+                match serde_json::to_string(&message) {
+                    Ok(text) => {
+                        let _ = quotes.send(Arc::new(EncodedQuote {
+                            symbol: quote.symbol.clone(),
+                            text,
+                        }));
+                    }
+                    Err(e) => info!("Failed to encode fan-out quote: {}", e),
+                }
+            }
+        })
+    }
+
+    async fn handle_client(&self, stream: TcpStream, peer: SocketAddr) {
+        let websocket = match accept_async(stream).await {
+            Ok(websocket) => websocket,
+            Err(e) => {
+                info!("Fan-out handshake with {} failed: {}", peer, e);
+                return;
+            }
+        };
+        self.counters.accepted.fetch_add(1, Ordering::Relaxed);
+        self.counters.clients.fetch_add(1, Ordering::Relaxed);
+        info!("Fan-out client {} connected", peer);
+        let disconnect = self.run_client(websocket).await;
+        self.counters.clients.fetch_sub(1, Ordering::Relaxed);
+        match disconnect {
+            Disconnect::SlowConsumer => {
+                self.counters.slow_consumers.fetch_add(1, Ordering::Relaxed);
+                info!("Fan-out client {} disconnected: too slow", peer);
+            }
+            Disconnect::Closed => info!("Fan-out client {} disconnected", peer),
+        }
+    }
+
+    async fn run_client(&self, websocket: WebSocketStream<TcpStream>) -> Disconnect {
+        let (mut write, mut read) = websocket.split();
+        let mut session = ClientSession::default();
+        loop {
+            let reply = tokio::select! {
+                message = read.next() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        Ok(Reply::Message(self.handle_request(&text, &mut session).await))
+                    }
+                    Some(Ok(Message::Ping(payload))) => {
+                        if write.send(Message::Pong(payload)).await.is_err() {
+                            return Disconnect::Closed;
+                        }
+                        continue;
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return Disconnect::Closed,
+                    Some(Ok(_)) => continue,
+                },
+                quote = next_quote(&mut session.quotes) => match quote {
+                    Ok(quote) if session.wants(&quote.symbol) => Ok(Reply::Quote(quote)),
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(_)) => Err(Disconnect::SlowConsumer),
+                    Err(RecvError::Closed) => {
+                        session.quotes = None;
+                        continue;
+                    }
+                },
+                update = next_user_data(&mut session.user_data) => match update {
+                    Ok(update) => Ok(Reply::Message(ServerMessage::UserData {
+                        account: session.account.clone().unwrap_or_default(),
+                        event: serde_json::to_value(&*update).unwrap_or_default(),
+                    })),
+                    Err(RecvError::Lagged(_)) => Err(Disconnect::SlowConsumer),
+                    Err(RecvError::Closed) => {
+                        session.user_data = None;
+                        continue;
+                    }
+                },
+            };
+            let (reply, disconnect) = match reply {
+                Ok(reply) => (reply, None),
+                Err(disconnect) => (
+                    Reply::Message(ServerMessage::Error {
+                        message: "slow consumer".to_string(),
+                    }),
+                    Some(disconnect),
+                ),
+            };
+            let text = match reply {
+                Reply::Quote(quote) => quote.text.clone(),
+                Reply::Message(message) => match serde_json::to_string(&message) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        info!("Failed to encode fan-out message: {}", e);
+                        continue;
+                    }
+                },
+            };
+            if write.send(Message::Text(text)).await.is_err() {
+                return Disconnect::Closed;
+            }
+            if let Some(disconnect) = disconnect {
+                let _ = write.close().await;
+                return disconnect;
+            }
+        }
+    }
+
+    async fn handle_request(&self, text: &str, session: &mut ClientSession) -> ServerMessage {
+        let request = match serde_json::from_str::<ClientRequest>(text) {
+            Ok(request) => request,
+            Err(e) => {
+                return ServerMessage::Error {
+                    message: format!("invalid request: {}", e),
+                };
+            }
+        };
+        let op = request.op();
+        match request {
+            ClientRequest::Subscribe { symbols } => {
+                for symbol in symbols {
+                    if symbol == ALL_SYMBOLS {
+                        session.all_symbols = true;
+                    } else {
+                        session.symbols.insert(symbol.to_uppercase());
+                    }
+                }
+                self.resubscribe(session);
+            }
+            ClientRequest::Unsubscribe { symbols } => {
+                for symbol in symbols {
+                    if symbol == ALL_SYMBOLS {
+                        session.all_symbols = false;
+                    } else {
+                        session.symbols.remove(&symbol.to_uppercase());
+                    }
+                }
+                self.resubscribe(session);
+            }
+            ClientRequest::Auth { token } => {
+                // Events of the previous account must not go out under the
+                // new one.
+                session.user_data = None;
+                match self.config.tokens.get(&token) {
+                    Some(account) => session.account = Some(account.clone()),
+                    None => {
+                        session.account = None;
+                        return ServerMessage::Error {
+                            message: "invalid token".to_string(),
+                        };
+                    }
+                }
+            }
+            ClientRequest::SubscribeUserData => {
+                let stream = session
+                    .account
+                    .as_ref()
+                    .and_then(|account| self.user_data.get(account));
+                match stream {
+                    Some(stream) => session.user_data = Some(stream.events()),
+                    None => {
+                        return ServerMessage::Error {
+                            message: "no user data stream for this session".to_string(),
+                        };
+                    }
+                }
+            }
+            ClientRequest::UnsubscribeUserData => session.user_data = None,
+        }
+        ServerMessage::Ack { op }
+    }
+
+    /// Receives quotes while the client has any symbol subscribed, filtered
+    /// by `ClientSession::wants`.
+    fn resubscribe(&self, session: &mut ClientSession) {
+        if !session.all_symbols && session.symbols.is_empty() {
+            session.quotes = None;
+        } else if session.quotes.is_none() {
+            session.quotes = Some(self.quotes.subscribe());
+        }
+    }
+}
+
+/// Next quote, or never if the client has no quote subscription.
+async fn next_quote(
+    quotes: &mut Option<broadcast::Receiver<Arc<EncodedQuote>>>,
+) -> Result<Arc<EncodedQuote>, RecvError> {
+    match quotes {
+        Some(quotes) => quotes.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn next_user_data(
+    user_data: &mut Option<broadcast::Receiver<Arc<UserDataUpdate>>>,
+) -> Result<Arc<UserDataUpdate>, RecvError> {
+    match user_data {
+        Some(user_data) => user_data.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> FanoutServer {
+        let config = FanoutConfig {
+            tokens: HashMap::from([
+                ("alice-token".to_string(), "alice".to_string()),
+                ("bob-token".to_string(), "bob".to_string()),
+            ]),
+            ..Default::default()
+        };
+        FanoutServer::new(BookTickerStream::new(), config)
+            .with_user_data("alice", UserDataStream::new("alice-key".to_string()))
+            .with_user_data("bob", UserDataStream::new("bob-key".to_string()))
+    }
+
+    async fn request(server: &FanoutServer, session: &mut ClientSession, text: &str) -> String {
+        let reply = server.handle_request(text, session).await;
+        serde_json::to_string(&reply).unwrap()
+    }
+
+    #[test]
+    fn parses_client_requests() {
+        let subscribe: ClientRequest =
+            serde_json::from_str(r#"{"op":"subscribe","symbols":["btcusdt","*"]}"#).unwrap();
+        assert!(matches!(
+            &subscribe,
+            ClientRequest::Subscribe { symbols } if symbols == &["btcusdt", ALL_SYMBOLS]
+        ));
+        assert_eq!(subscribe.op(), "subscribe");
+        let auth: ClientRequest = serde_json::from_str(r#"{"op":"auth","token":"t"}"#).unwrap();
+        assert!(matches!(&auth, ClientRequest::Auth { token } if token == "t"));
+        let user_data: ClientRequest =
+            serde_json::from_str(r#"{"op":"subscribe_user_data"}"#).unwrap();
+        assert_eq!(user_data.op(), "subscribe_user_data");
+        assert!(serde_json::from_str::<ClientRequest>(r#"{"op":"trade"}"#).is_err());
+        assert!(serde_json::from_str::<ClientRequest>(r#"{"op":"subscribe"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn answers_invalid_requests_with_an_error() {
+        let server = server();
+        let mut session = ClientSession::default();
+        let reply = request(&server, &mut session, "not json").await;
+        assert!(reply.starts_with(r#"{"type":"error","message":"invalid request"#));
+    }
+
+    #[tokio::test]
+    async fn subscribes_quotes_only_while_any_symbol_is_wanted() {
+        let server = server();
+        let mut session = ClientSession::default();
+        assert!(!session.wants("BTCUSDT"));
+        let reply = request(
+            &server,
+            &mut session,
+            r#"{"op":"subscribe","symbols":["btcusdt"]}"#,
+        )
+        .await;
+        assert_eq!(reply, r#"{"type":"ack","op":"subscribe"}"#);
+        assert!(session.wants("BTCUSDT"));
+        assert!(!session.wants("ETHUSDT"));
+        assert!(session.quotes.is_some());
+
+        request(
+            &server,
+            &mut session,
+            r#"{"op":"subscribe","symbols":["*"]}"#,
+        )
+        .await;
+        assert!(session.wants("ETHUSDT"));
+        request(
+            &server,
+            &mut session,
+            r#"{"op":"unsubscribe","symbols":["*","BTCUSDT"]}"#,
+        )
+        .await;
+        assert!(!session.wants("BTCUSDT"));
+        assert!(session.quotes.is_none());
+    }
+
+    #[tokio::test]
+    async fn user_data_requires_a_valid_token() {
+        let server = server();
+        let mut session = ClientSession::default();
+        let reply = request(&server, &mut session, r#"{"op":"subscribe_user_data"}"#).await;
+        assert!(reply.contains(r#""type":"error""#));
+        let reply = request(&server, &mut session, r#"{"op":"auth","token":"nope"}"#).await;
+        assert_eq!(reply, r#"{"type":"error","message":"invalid token"}"#);
+        assert!(session.account.is_none());
+
+        request(
+            &server,
+            &mut session,
+            r#"{"op":"auth","token":"alice-token"}"#,
+        )
+        .await;
+        assert_eq!(session.account.as_deref(), Some("alice"));
+        let reply = request(&server, &mut session, r#"{"op":"subscribe_user_data"}"#).await;
+        assert_eq!(reply, r#"{"type":"ack","op":"subscribe_user_data"}"#);
+        assert!(session.user_data.is_some());
+    }
+
+    #[tokio::test]
+    async fn reauthenticating_drops_the_previous_account() {
+        let server = server();
+        let mut session = ClientSession::default();
+        request(
+            &server,
+            &mut session,
+            r#"{"op":"auth","token":"alice-token"}"#,
+        )
+        .await;
+        request(&server, &mut session, r#"{"op":"subscribe_user_data"}"#).await;
+
+        request(
+            &server,
+            &mut session,
+            r#"{"op":"auth","token":"bob-token"}"#,
+        )
+        .await;
+        assert_eq!(session.account.as_deref(), Some("bob"));
+        assert!(session.user_data.is_none());
+
+        request(&server, &mut session, r#"{"op":"subscribe_user_data"}"#).await;
+        request(&server, &mut session, r#"{"op":"auth","token":"nope"}"#).await;
+        assert!(session.account.is_none());
+        assert!(session.user_data.is_none());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::bookticker_stream::bookticker::{BestPrices, BookTickerStream, ConnectionStatus};
+use crate::bookticker_stream::feeds::FeedStatsSnapshot;
+use crate::bookticker_stream::staleness::StaleSymbol;
+use crate::clock::current_time_millis;
+use crate::websocket::feed::Feed;
+use crate::websocket::frame_errors::FrameMetricsSnapshot;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tracing::info;
+
+pub const DEFAULT_HTTP_API_ADDR: &str = "127.0.0.1:8080";
+
+#[derive(Debug, Clone)]
+pub struct HttpApiConfig {
+    pub addr: SocketAddr,
+}
+
+impl Default for HttpApiConfig {
+    fn default() -> Self {
+        HttpApiConfig {
+            addr: DEFAULT_HTTP_API_ADDR
+                .parse()
+                .expect("default address is valid"),
+        }
+    }
+}
+
+impl HttpApiConfig {
+    /// `HTTP_API_ADDR` overrides the listen address, e.g. `0.0.0.0:8080`.
+    pub fn from_env() -> Self {
+        let mut config = HttpApiConfig::default();
+        if let Some(addr) = std::env::var("HTTP_API_ADDR")
+            .ok()
+            .and_then(|addr| addr.parse().ok())
+        {
+            config.addr = addr;
+        }
+        config
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("no quote for {0}")]
+    UnknownSymbol(String),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ApiError::UnknownSymbol(_) => StatusCode::NOT_FOUND,
+        };
+        let body = Json(ErrorBody {
+            error: self.to_string(),
+        });
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Quote {
+    pub symbol: String,
+    #[serde(flatten)]
+    pub prices: BestPrices,
+    /// Milliseconds since the quote was received.
+    pub age_ms: u64,
+}
+
+impl Quote {
+    fn new(symbol: &str, prices: &BestPrices, now: u64) -> Self {
+        Quote {
+            symbol: symbol.to_string(),
+            prices: prices.clone(),
+            age_ms: now.saturating_sub(prices.received_at),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamsResponse {
+    pub connections: Vec<ConnectionStatus>,
+    pub stale_symbols: Vec<StaleSymbol>,
+    pub feeds: BTreeMap<Feed, FeedStatsSnapshot>,
+    pub frames: FrameMetricsSnapshot,
+}
+
+/// `GET /quotes`, `GET /quotes/{symbol}` and `GET /streams`, answered from
+/// `stream`.
+pub fn router(stream: BookTickerStream) -> Router {
+    Router::new()
+        .route("/quotes", get(quotes))
+        .route("/quotes/{symbol}", get(quote))
+        .route("/streams", get(streams))
+        .with_state(stream)
+}
+
+pub async fn serve(stream: BookTickerStream, config: HttpApiConfig) -> std::io::Result<()> {
+    let listener = TcpListener::bind(config.addr).await?;
+    info!("HTTP API listening on {}", config.addr);
+    axum::serve(listener, router(stream)).await
+}
+
+async fn quotes(State(stream): State<BookTickerStream>) -> Json<BTreeMap<String, Quote>> {
+    let now = current_time_millis();
+    let book_ticker = stream.book_ticker.lock().await;
+    Json(
+        book_ticker
+            .iter()
+            .map(|(symbol, prices)| (symbol.clone(), Quote::new(symbol, prices, now)))
+            .collect(),
+    )
+}
+
+/// Symbols are matched case-insensitively.
+async fn quote(
+    State(stream): State<BookTickerStream>,
+    Path(symbol): Path<String>,
+) -> Result<Json<Quote>, ApiError> {
+    let symbol = symbol.to_uppercase();
+    let book_ticker = stream.book_ticker.lock().await;
+    match book_ticker.get(&symbol) {
+        Some(prices) => Ok(Json(Quote::new(&symbol, prices, current_time_millis()))),
+        None => Err(ApiError::UnknownSymbol(symbol)),
+    }
+}
+
+async fn streams(State(stream): State<BookTickerStream>) -> Json<StreamsResponse> {
+    Json(StreamsResponse {
+        connections: stream.connection_status().await,
+        stale_symbols: stream.stale_symbols().await,
+        feeds: stream.feed_stats(),
+        frames: stream.frame_metrics(),
+    })
+}
@@ -0,0 +1,4 @@
+pub mod reader;
+pub mod record;
+pub mod replay;
+pub mod writer;
@@ -0,0 +1,95 @@
+use crate::journal::record::JournalRecord;
+use crate::journal::writer::SEGMENT_EXTENSION;
+use flate2::read::MultiGzDecoder;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Lines};
+use std::path::{Path, PathBuf};
+
+/// Journal segments in `dir` written with `prefix`, oldest first. Segments
+/// still being written, or left behind by a crash, are only included with
+/// `include_partial`; they may end in a truncated record.
+pub fn list_segments(dir: &Path, prefix: &str, include_partial: bool) -> io::Result<Vec<PathBuf>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(key) = segment_key(name, prefix, include_partial) else {
+            continue;
+        };
+        segments.push((key, path));
+    }
+    segments.sort();
+    Ok(segments.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Opening time and sequence number parsed from a segment file name.
+fn segment_key(name: &str, prefix: &str, include_partial: bool) -> Option<(u64, u64)> {
+    let name = match name.strip_suffix(".part") {
+        Some(name) if include_partial => name,
+        Some(_) => return None,
+        None => name,
+    };
+    let stem = name
+        .strip_prefix(prefix)?
+        .strip_prefix('-')?
+        .strip_suffix(SEGMENT_EXTENSION)?
+        .strip_suffix('.')?;
+    let (opened_at, sequence) = stem.split_once('-')?;
+    Some((opened_at.parse().ok()?, sequence.parse().ok()?))
+}
+
+/// Reads records from a list of segments in order. A record that fails to
+/// decode is returned as an error and reading continues with the next line;
+/// an I/O error, such as a truncated segment, skips the rest of that segment.
+pub struct JournalReader {
+    segments: VecDeque<PathBuf>,
+    current: Option<Lines<BufReader<MultiGzDecoder<File>>>>,
+}
+
+impl JournalReader {
+    pub fn new(segments: Vec<PathBuf>) -> Self {
+        JournalReader {
+            segments: segments.into(),
+            current: None,
+        }
+    }
+
+    pub fn open(dir: &Path, prefix: &str, include_partial: bool) -> io::Result<Self> {
+        Ok(Self::new(list_segments(dir, prefix, include_partial)?))
+    }
+}
+
+impl Iterator for JournalReader {
+    type Item = io::Result<JournalRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let lines = match self.current.as_mut() {
+                Some(lines) => lines,
+                None => {
+                    let path = self.segments.pop_front()?;
+                    match File::open(&path) {
+                        Ok(file) => self
+                            .current
+                            .insert(BufReader::new(MultiGzDecoder::new(file)).lines()),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+            };
+            match lines.next() {
+                Some(Ok(line)) if line.is_empty() => continue,
+                Some(Ok(line)) => {
+                    return Some(serde_json::from_str(&line).map_err(io::Error::from));
+                }
+                Some(Err(e)) => {
+                    self.current = None;
+                    return Some(Err(e));
+                }
+                None => self.current = None,
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use crate::websocket::feed::Feed;
+use serde::{Deserialize, Serialize};
+
+/// Stream a journaled frame was received on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalSource {
+    BookTicker,
+    UserData,
+}
+
+/// One raw text frame as it came off the socket. Segments hold one record
+/// per line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalRecord {
+    /// Local wall-clock receive time in epoch milliseconds.
+    pub received_at: u64,
+    pub source: JournalSource,
+    pub connection: usize,
+    pub feed: Feed,
+    /// The frame exactly as received, kept as a string so frames that failed
+    /// to decode are preserved too.
+    pub frame: String,
+}
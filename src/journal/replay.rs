@@ -0,0 +1,208 @@
+use crate::bookticker_stream::bookticker::BookTickerStream;
+use crate::bookticker_stream::borrowed::decode_book_ticker;
+use crate::bookticker_stream::connection::bookticker_stream_name;
+use crate::clock::{set_simulated_time, simulated_time_enabled, use_wall_clock};
+use crate::journal::reader::JournalReader;
+use crate::journal::record::{JournalRecord, JournalSource};
+use crate::order_stream::order_update::UserDataStream;
+use crate::websocket::connection::{ConnectionState, FrameHandler};
+use crate::websocket::feed::Feed;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+use tokio::time::{self, Duration, Instant};
+use tracing::info;
+
+/// Records read ahead of the replay.
+const READ_AHEAD: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Frames are delivered with the gaps they were received with.
+    RealTime,
+    /// Gaps are divided by the factor, e.g. `10.0` replays ten times faster.
+    Accelerated(f64),
+    /// Frames are delivered back to back.
+    AsFastAsPossible,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayConfig {
+    pub dir: PathBuf,
+    pub prefix: String,
+    pub speed: ReplaySpeed,
+    /// Skips frames received before this time, in epoch milliseconds.
+    pub from: Option<u64>,
+    /// Stops at the first frame received after this time.
+    pub to: Option<u64>,
+    /// Also replays segments that were never completed.
+    pub include_partial: bool,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        ReplayConfig {
+            dir: PathBuf::from("journal"),
+            prefix: "frames".to_string(),
+            speed: ReplaySpeed::AsFastAsPossible,
+            from: None,
+            to: None,
+            include_partial: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReplayStats {
+    pub replayed: u64,
+    /// Frames whose source has no stream attached to the replayer.
+    pub skipped: u64,
+    /// Records that could not be read back.
+    pub unreadable: u64,
+    /// Receive time of the last frame replayed.
+    pub last_received_at: Option<u64>,
+}
+
+/// Feeds journaled frames through the same handlers the live connections
+/// use. While it runs, `current_time_millis` follows the receive time of
+/// each frame, so quote timestamps, staleness and bars see the recorded
+/// timeline. That clock is process-wide, so replays only run in a process
+/// that called `clock::enable_simulated_time` and runs no live streams,
+/// such as the `replay` binary.
+pub struct Replayer {
+    config: ReplayConfig,
+    book_ticker: Option<BookTickerStream>,
+    user_data: Option<UserDataStream>,
+}
+
+impl Replayer {
+    pub fn new(config: ReplayConfig) -> Self {
+        Replayer {
+            config,
+            book_ticker: None,
+            user_data: None,
+        }
+    }
+
+    pub fn with_book_ticker(mut self, stream: BookTickerStream) -> Self {
+        self.book_ticker = Some(stream);
+        self
+    }
+
+    pub fn with_user_data(mut self, stream: UserDataStream) -> Self {
+        self.user_data = Some(stream);
+        self
+    }
+
+    /// Replays every selected frame, then hands the clock back to the wall
+    /// clock.
+    pub async fn run(&self) -> io::Result<ReplayStats> {
+        if !simulated_time_enabled() {
+            return Err(io::Error::other(
+                "replay needs a standalone process with simulated time enabled",
+            ));
+        }
+        let reader = JournalReader::open(
+            &self.config.dir,
+            &self.config.prefix,
+            self.config.include_partial,
+        )?;
+        let (sender, mut records) = mpsc::channel(READ_AHEAD);
+        let read_task = tokio::task::spawn_blocking(move || {
+            for record in reader {
+                if sender.blocking_send(record).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut stats = ReplayStats::default();
+        let mut states: HashMap<(usize, Feed), ConnectionState> = HashMap::new();
+        let mut timeline: Option<(u64, Instant)> = None;
+        while let Some(record) = records.recv().await {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    info!("Skipping unreadable journal record: {}", e);
+                    stats.unreadable += 1;
+                    continue;
+                }
+            };
+            if self
+                .config
+                .from
+                .is_some_and(|from| record.received_at < from)
+            {
+                continue;
+            }
+            if self.config.to.is_some_and(|to| record.received_at > to) {
+                break;
+            }
+            let (first_received_at, started) =
+                *timeline.get_or_insert((record.received_at, Instant::now()));
+            if let Some(offset) = self.pace(record.received_at.saturating_sub(first_received_at)) {
+                time::sleep_until(started + offset).await;
+            }
+            set_simulated_time(record.received_at);
+            if self.dispatch(&record, &mut states).await {
+                stats.replayed += 1;
+                stats.last_received_at = Some(record.received_at);
+            } else {
+                stats.skipped += 1;
+            }
+        }
+        drop(records);
+        let _ = read_task.await;
+        use_wall_clock();
+        info!(
+            "Replayed {} frames, skipped {}, unreadable {}",
+            stats.replayed, stats.skipped, stats.unreadable
+        );
+        Ok(stats)
+    }
+
+    /// Wall-clock offset from the start of the replay at which a frame
+    /// received `elapsed_ms` after the first one is delivered.
+    fn pace(&self, elapsed_ms: u64) -> Option<Duration> {
+        let elapsed = Duration::from_millis(elapsed_ms);
+        match self.config.speed {
+            ReplaySpeed::RealTime => Some(elapsed),
+            ReplaySpeed::Accelerated(factor) if factor > 0.0 => Some(elapsed.div_f64(factor)),
+            ReplaySpeed::Accelerated(_) | ReplaySpeed::AsFastAsPossible => None,
+        }
+    }
+
+    /// Returns whether a stream was attached for the record's source.
+    async fn dispatch(
+        &self,
+        record: &JournalRecord,
+        states: &mut HashMap<(usize, Feed), ConnectionState>,
+    ) -> bool {
+        match record.source {
+            JournalSource::BookTicker => {
+                let Some(stream) = &self.book_ticker else {
+                    return false;
+                };
+                let state = states
+                    .entry((record.connection, record.feed))
+                    .or_insert_with(|| {
+                        ConnectionState::new(record.connection, record.feed, bookticker_stream_name)
+                    });
+                // Quotes are only accepted for subscribed symbols, and the
+                // journal does not record subscriptions.
+                if let Some(frame) = decode_book_ticker(&record.frame, &mut Vec::new()) {
+                    stream.track_symbol(frame.data.symbol);
+                }
+                stream.handle_text(&record.frame, state).await;
+                true
+            }
+            JournalSource::UserData => {
+                let Some(stream) = &self.user_data else {
+                    return false;
+                };
+                stream.handle_user_data_update(&record.frame).await;
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use crate::clock::current_time_millis;
+use crate::journal::reader::list_segments;
+use crate::journal::record::{JournalRecord, JournalSource};
+use crate::websocket::feed::Feed;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// Suffix of completed segments; open segments carry an extra `.part`.
+pub const SEGMENT_EXTENSION: &str = "jsonl.gz";
+
+#[derive(Debug, Clone)]
+pub struct JournalConfig {
+    pub dir: PathBuf,
+    /// File name prefix of every segment.
+    pub prefix: String,
+    /// A segment is closed once this many uncompressed bytes were written.
+    pub max_segment_bytes: u64,
+    /// A segment is closed once it has been open this long.
+    pub max_segment_age: Duration,
+    /// Buffered frames are flushed to disk at least this often.
+    pub flush_interval: Duration,
+    /// Frames waiting to be written; further frames are dropped.
+    pub queue_capacity: usize,
+    /// gzip level, 0 to 9.
+    pub compression_level: u32,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        JournalConfig {
+            dir: PathBuf::from("journal"),
+            prefix: "frames".to_string(),
+            max_segment_bytes: 256 * 1024 * 1024,
+            max_segment_age: Duration::from_secs(3600),
+            flush_interval: Duration::from_secs(1),
+            queue_capacity: 100_000,
+            compression_level: 6,
+        }
+    }
+}
+
+impl JournalConfig {
+    /// Enabled when `JOURNAL_DIR` is set. `JOURNAL_MAX_SEGMENT_MB` and
+    /// `JOURNAL_MAX_SEGMENT_SECS` override the rotation limits.
+    pub fn from_env() -> Option<Self> {
+        let dir = std::env::var("JOURNAL_DIR").ok()?;
+        let mut config = JournalConfig {
+            dir: PathBuf::from(dir),
+            ..Default::default()
+        };
+        if let Some(megabytes) = std::env::var("JOURNAL_MAX_SEGMENT_MB")
+            .ok()
+            .and_then(|megabytes| megabytes.parse::<u64>().ok())
+        {
+            config.max_segment_bytes = megabytes * 1024 * 1024;
+        }
+        if let Some(seconds) = std::env::var("JOURNAL_MAX_SEGMENT_SECS")
+            .ok()
+            .and_then(|seconds| seconds.parse::<u64>().ok())
+        {
+            config.max_segment_age = Duration::from_secs(seconds);
+        }
+        Some(config)
+    }
+}
+
+#[derive(Debug, Default)]
+struct JournalCounters {
+    recorded: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+    segments: AtomicU64,
+    bytes: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JournalStatsSnapshot {
+    /// Frames written to a segment.
+    pub recorded: u64,
+    /// Frames never queued because the writer could not keep up.
+    pub dropped: u64,
+    /// Frames lost to I/O errors.
+    pub failed: u64,
+    /// Segments completed.
+    pub segments: u64,
+    /// Uncompressed bytes written.
+    pub bytes: u64,
+}
+
+/// Handle to a background thread appending raw frames to gzip-compressed,
+/// line-delimited JSON segments under `JournalConfig::dir`. Segments are
+/// written as `<prefix>-<opened at ms>-<sequence>.jsonl.gz.part` and renamed
+/// without `.part` once complete, so only finished segments get archived.
+/// Call `close` before exiting; segments a crash leaves open are completed
+/// the next time the journal starts.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    sender: mpsc::SyncSender<JournalRecord>,
+    counters: Arc<JournalCounters>,
+    closed: Arc<AtomicBool>,
+}
+
+impl Journal {
+    pub fn spawn(config: JournalConfig) -> (Journal, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::sync_channel(config.queue_capacity.max(1));
+        let counters = Arc::new(JournalCounters::default());
+        let closed = Arc::new(AtomicBool::new(false));
+        let task = {
+            let counters = Arc::clone(&counters);
+            let closed = Arc::clone(&closed);
+            tokio::task::spawn_blocking(move || run_journal(config, receiver, counters, closed))
+        };
+        (
+            Journal {
+                sender,
+                counters,
+                closed,
+            },
+            task,
+        )
+    }
+
+    /// Stops recording: frames already queued are written, the open segment
+    /// is completed and the task returned by `spawn` finishes. Later frames
+    /// are dropped.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    /// Queues `frame`, stamped with the current local time, without waiting.
+    pub fn record(&self, source: JournalSource, connection: usize, feed: Feed, frame: &str) {
+        if self.closed.load(Ordering::Relaxed) {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let record = JournalRecord {
+            received_at: current_time_millis(),
+            source,
+            connection,
+            feed,
+            frame: frame.to_string(),
+        };
+        if self.sender.try_send(record).is_err() {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> JournalStatsSnapshot {
+        JournalStatsSnapshot {
+            recorded: self.counters.recorded.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+            segments: self.counters.segments.load(Ordering::Relaxed),
+            bytes: self.counters.bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct Segment {
+    encoder: GzEncoder<BufWriter<File>>,
+    partial_path: PathBuf,
+    path: PathBuf,
+    opened_at: Instant,
+    bytes: u64,
+}
+
+impl Segment {
+    fn open(config: &JournalConfig, sequence: u64) -> io::Result<Segment> {
+        fs::create_dir_all(&config.dir)?;
+        let name = format!(
+            "{}-{}-{:06}.{}",
+            config.prefix,
+            current_time_millis(),
+            sequence,
+            SEGMENT_EXTENSION
+        );
+        let path = config.dir.join(name);
+        let mut partial_path = path.clone().into_os_string();
+        partial_path.push(".part");
+        let partial_path = PathBuf::from(partial_path);
+        let file = File::create(&partial_path)?;
+        Ok(Segment {
+            encoder: GzEncoder::new(
+                BufWriter::new(file),
+                Compression::new(config.compression_level.min(9)),
+            ),
+            partial_path,
+            path,
+            opened_at: Instant::now(),
+            bytes: 0,
+        })
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        self.encoder.write_all(line)?;
+        self.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    fn is_full(&self, config: &JournalConfig) -> bool {
+        self.bytes >= config.max_segment_bytes || self.opened_at.elapsed() >= config.max_segment_age
+    }
+
+    fn close(self) -> io::Result<PathBuf> {
+        let mut writer = self.encoder.finish()?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&self.partial_path, &self.path)?;
+        Ok(self.path)
+    }
+}
+
+fn run_journal(
+    config: JournalConfig,
+    receiver: mpsc::Receiver<JournalRecord>,
+    counters: Arc<JournalCounters>,
+    closed: Arc<AtomicBool>,
+) {
+    recover_segments(&config);
+    let mut segment: Option<Segment> = None;
+    let mut sequence = 0;
+    let mut line = Vec::new();
+    let mut last_flush = Instant::now();
+    loop {
+        if closed.load(Ordering::SeqCst) {
+            while let Ok(record) = receiver.try_recv() {
+                journal_record(
+                    &config,
+                    &mut segment,
+                    &mut sequence,
+                    &mut line,
+                    &record,
+                    &counters,
+                );
+            }
+            close_segment(segment.take(), &counters);
+            info!("Journal closed, writer stopped");
+            break;
+        }
+        let timeout = config.flush_interval.saturating_sub(last_flush.elapsed());
+        match receiver.recv_timeout(timeout) {
+            Ok(record) => journal_record(
+                &config,
+                &mut segment,
+                &mut sequence,
+                &mut line,
+                &record,
+                &counters,
+            ),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => closed.store(true, Ordering::SeqCst),
+        }
+        if segment.as_ref().is_some_and(|s| s.is_full(&config)) {
+            close_segment(segment.take(), &counters);
+        }
+        if last_flush.elapsed() >= config.flush_interval {
+            if let Some(Err(e)) = segment.as_mut().map(|open| open.encoder.flush()) {
+                info!("Failed to flush journal segment: {}", e);
+                segment = None;
+            }
+            last_flush = Instant::now();
+        }
+    }
+}
+
+fn journal_record(
+    config: &JournalConfig,
+    segment: &mut Option<Segment>,
+    sequence: &mut u64,
+    line: &mut Vec<u8>,
+    record: &JournalRecord,
+    counters: &JournalCounters,
+) {
+    if let Err(e) = write_record(config, segment, sequence, line, record) {
+        info!("Failed to journal frame: {}", e);
+        counters.failed.fetch_add(1, Ordering::Relaxed);
+        // Start a fresh segment rather than appending after a partial write.
+        *segment = None;
+        return;
+    }
+    counters.recorded.fetch_add(1, Ordering::Relaxed);
+    counters
+        .bytes
+        .fetch_add(line.len() as u64, Ordering::Relaxed);
+}
+
+fn write_record(
+    config: &JournalConfig,
+    segment: &mut Option<Segment>,
+    sequence: &mut u64,
+    line: &mut Vec<u8>,
+    record: &JournalRecord,
+) -> io::Result<()> {
+    line.clear();
+    serde_json::to_writer(&mut *line, record)?;
+    line.push(b'\n');
+    let open = match segment {
+        Some(open) => open,
+        None => {
+            *sequence += 1;
+            segment.insert(Segment::open(config, *sequence)?)
+        }
+    };
+    open.write_line(line)
+}
+
+fn close_segment(segment: Option<Segment>, counters: &JournalCounters) {
+    let Some(segment) = segment else {
+        return;
+    };
+    match segment.close() {
+        Ok(path) => {
+            counters.segments.fetch_add(1, Ordering::Relaxed);
+            info!("Journal segment {} complete", path.display());
+        }
+        Err(e) => info!("Failed to close journal segment: {}", e),
+    }
+}
+
+/// Completes segments left open by a process that did not close its journal,
+/// keeping every whole line and dropping the truncated tail.
+fn recover_segments(config: &JournalConfig) {
+    let Ok(segments) = list_segments(&config.dir, &config.prefix, true) else {
+        return;
+    };
+    for partial_path in segments {
+        let Some(path) = partial_path
+            .to_str()
+            .and_then(|path| path.strip_suffix(".part"))
+            .map(PathBuf::from)
+        else {
+            continue;
+        };
+        match recover_segment(config, &partial_path, &path) {
+            Ok(lines) => info!(
+                "Recovered {} frames from unfinished journal segment {}",
+                lines,
+                path.display()
+            ),
+            Err(e) => info!(
+                "Failed to recover journal segment {}: {}",
+                partial_path.display(),
+                e
+            ),
+        }
+    }
+}
+
+fn recover_segment(config: &JournalConfig, partial_path: &Path, path: &Path) -> io::Result<u64> {
+    let mut recovering = path.to_path_buf().into_os_string();
+    recovering.push(".recovering");
+    let recovering = PathBuf::from(recovering);
+    let mut reader = BufReader::new(MultiGzDecoder::new(File::open(partial_path)?));
+    let mut encoder = GzEncoder::new(
+        BufWriter::new(File::create(&recovering)?),
+        Compression::new(config.compression_level.min(9)),
+    );
+    let mut line = Vec::new();
+    let mut lines = 0;
+    loop {
+        line.clear();
+        // A crash cuts the gzip stream short, which reads as an error or a
+        // last line without its newline.
+        match reader.read_until(b'\n', &mut line) {
+            Ok(_) if line.ends_with(b"\n") => {
+                encoder.write_all(&line)?;
+                lines += 1;
+            }
+            _ => break,
+        }
+    }
+    let mut writer = encoder.finish()?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    fs::rename(&recovering, path)?;
+    fs::remove_file(partial_path)?;
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::reader::JournalReader;
+
+    fn record(frame: &str) -> JournalRecord {
+        JournalRecord {
+            received_at: 1,
+            source: JournalSource::BookTicker,
+            connection: 0,
+            feed: Feed::A,
+            frame: frame.to_string(),
+        }
+    }
+
+    #[test]
+    fn recovers_whole_lines_of_an_unfinished_segment() {
+        let dir = std::env::temp_dir().join(format!("journal-recover-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config = JournalConfig {
+            dir: dir.clone(),
+            ..Default::default()
+        };
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        for frame in ["first", "second"] {
+            serde_json::to_writer(&mut encoder, &record(frame)).unwrap();
+            encoder.write_all(b"\n").unwrap();
+        }
+        encoder.write_all(b"{\"received_at\":").unwrap();
+        let mut bytes = encoder.finish().unwrap();
+        // Drop the gzip trailer, as a crash before `finish` would.
+        bytes.truncate(bytes.len() - 8);
+        let partial_path = dir.join(format!("frames-1-000001.{}.part", SEGMENT_EXTENSION));
+        fs::write(&partial_path, bytes).unwrap();
+
+        recover_segments(&config);
+
+        assert!(!partial_path.exists());
+        let segments = list_segments(&dir, "frames", true).unwrap();
+        assert_eq!(segments.len(), 1);
+        let frames: Vec<String> = JournalReader::new(segments)
+            .map(|record| record.unwrap().frame)
+            .collect();
+        assert_eq!(frames, vec!["first", "second"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
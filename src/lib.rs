@@ -1,4 +1,13 @@
 pub mod async_binance;
 pub mod aws_resources;
 pub mod bookticker_stream;
+pub mod change_stream;
+pub mod clock;
+pub mod export;
+pub mod fanout;
+pub mod http_api;
+pub mod journal;
+pub mod market_streams;
+pub mod order_book;
 pub mod order_stream;
+pub mod websocket;
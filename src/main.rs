@@ -1,20 +1,74 @@
 pub mod bookticker_stream;
-use bookticker_stream::bookticker::BookTickerStream;
+use bookticker_stream::bar_db::{BarTableConfig, DynamoBarSink};
+use bookticker_stream::bars::{spawn_bar_aggregator, BarAggregatorConfig};
+use bookticker_stream::bookticker::{BookTickerStream, BookTickerStreamConfig};
+use bookticker_stream::feeds::SecondaryFeedConfig;
+use bookticker_stream::ticker_db::TickerTableConfig;
+use bookticker_stream::ticker_writer::{TickerWriter, TickerWriterConfig};
 use tracing::{info, Level};
-use tracing_subscriber;
 
 pub mod async_binance;
 pub mod aws_resources;
+pub mod change_stream;
+pub mod clock;
+pub mod export;
+pub mod fanout;
+pub mod http_api;
+pub mod journal;
+pub mod market_streams;
+pub mod order_book;
 pub mod order_stream;
+pub mod websocket;
 use async_binance::client_async::AsyncBinanceClient;
-use aws_resources::clients::get_ssm_client;
+use aws_resources::clients::{get_ddb_client, get_ssm_client};
+use aws_resources::dynamodb_tables::ensure_table;
 use aws_resources::ssm_params::get_param_value;
+use aws_resources::write_rate::{WriteRate, WriteRateConfig};
+use fanout::server::{FanoutConfig, FanoutServer};
+use http_api::server::HttpApiConfig;
+use journal::writer::{Journal, JournalConfig};
+use market_streams::streams::{MarketStream, MarketStreams};
+use order_book::depth_stream::{DepthStream, DepthStreamConfig};
+use order_stream::order_db::OrderTableConfig;
 use order_stream::order_update::UserDataStream;
+use order_stream::order_writer::persist_orders;
+use std::cmp::Reverse;
+
+/// Account name fan-out clients authenticate as to receive this process's
+/// user data stream, see `FANOUT_TOKENS`.
+const FANOUT_ACCOUNT: &str = "default";
+
+/// Resolves on ctrl-c or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            info!("Failed to listen for ctrl-c: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                info!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 10)]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt().with_max_level(Level::INFO).init();
-    let bookticker_partition: usize = 4;
     let ssm_client = get_ssm_client().await?;
     let binance_api_key = get_param_value(&ssm_client, "binance-api-key".to_string()).await?;
     let binance_secret_key = get_param_value(&ssm_client, "binance-secret-key".to_string()).await?;
@@ -25,8 +79,55 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Some(30),
     );
     let listen_key: String = binance_future_client.get_listen_key().await?;
+    // Local order books, e.g. ORDER_BOOK_SYMBOLS=BTCUSDT,ETHUSDT
+    let order_book_symbols: Vec<String> = std::env::var("ORDER_BOOK_SYMBOLS")
+        .map(|symbols| {
+            symbols
+                .split(',')
+                .filter(|symbol| !symbol.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    let depth_stream =
+        DepthStream::new(binance_future_client.clone(), DepthStreamConfig::default());
+    let _depth_connections = depth_stream.listen(order_book_symbols);
+    let market_streams = MarketStreams::new();
+    market_streams
+        .subscribe(&[MarketStream::AllMarkPrices])
+        .await;
     let coins_name = binance_future_client.get_available_coins_name().await;
-    let bookticker_stream = BookTickerStream::new();
+    let (journal, journal_task) = JournalConfig::from_env().map(Journal::spawn).unzip();
+    let mut bookticker_stream = BookTickerStream::with_config(BookTickerStreamConfig {
+        secondary_feed: SecondaryFeedConfig::from_env(),
+        ..Default::default()
+    });
+    if let Some(journal) = &journal {
+        bookticker_stream = bookticker_stream.with_journal(journal.clone());
+    }
+    let ddb_client = get_ddb_client().await?;
+    let ticker_table = TickerTableConfig::from_env();
+    ensure_table(&ddb_client, &ticker_table.table).await?;
+    let bar_table = BarTableConfig::from_env();
+    ensure_table(&ddb_client, &bar_table.table).await?;
+    let order_table = OrderTableConfig::from_env();
+    ensure_table(&ddb_client, &order_table.table).await?;
+    let write_rate = WriteRate::new(WriteRateConfig::from_env());
+    let bar_aggregator_task = spawn_bar_aggregator(
+        &bookticker_stream,
+        BarAggregatorConfig::default(),
+        DynamoBarSink::new(ddb_client.clone(), bar_table).with_write_rate(write_rate.clone()),
+    )
+    .await;
+    let (ticker_writer, ticker_writer_task) = TickerWriter::spawn(
+        ddb_client.clone(),
+        TickerWriterConfig {
+            table: ticker_table,
+            write_rate: write_rate.clone(),
+            ..Default::default()
+        },
+    );
+    let ticker_consumer_task = ticker_writer.consume(&bookticker_stream).await;
     // let urls: Vec<String> = vec![
     //     "wss://fstream.binance.com/stream?streams=btcusdt@bookTicker/ethusdt@bookTicker"
     //         .to_string(),
@@ -36,12 +137,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let bookticker_task = {
         let bookticker_stream_clone = bookticker_stream.clone();
         tokio::spawn(async move {
-            if let Err(e) = bookticker_stream_clone
-                .listen_all_coins_bookticker(coins_name, bookticker_partition)
-                .await
-            {
-                eprintln!("An error occurred: {}", e);
-            }
+            let e = bookticker_stream_clone
+                .listen_all_coins_bookticker(coins_name)
+                .await;
+            eprintln!("An error occurred: {}", e);
         })
     };
 
@@ -54,9 +153,119 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         })
     };
 
-    let user_data_stream = UserDataStream {
-        listen_key: listen_key.clone(),
+    let staleness_task = {
+        let bookticker_stream_clone = bookticker_stream.clone();
+        tokio::spawn(async move {
+            bookticker_stream_clone.monitor_staleness().await;
+        })
+    };
+
+    let clock_sync_task = {
+        let bookticker_stream_clone = bookticker_stream.clone();
+        let client = binance_future_client.clone();
+        tokio::spawn(async move {
+            bookticker_stream_clone.sync_clock(client).await;
+        })
     };
+
+    let http_api_task = {
+        let bookticker_stream_clone = bookticker_stream.clone();
+        tokio::spawn(async move {
+            if let Err(e) =
+                http_api::server::serve(bookticker_stream_clone, HttpApiConfig::from_env()).await
+            {
+                info!("HTTP API stopped: {}", e);
+            }
+        })
+    };
+
+    let feed_stats_stream = bookticker_stream.clone();
+    let journal_stats = journal.clone();
+    let write_rate_stats = write_rate.clone();
+    let ticker_writer_stats_task = tokio::spawn(async move {
+        let interval = tokio::time::Duration::from_secs(60);
+        loop {
+            tokio::time::sleep(interval).await;
+            let stats = ticker_writer.stats();
+            info!(
+                "Ticker writer: written {}, dropped {}, retried {}, failed {}, shed {}",
+                stats.written, stats.dropped, stats.retried, stats.failed, stats.shed
+            );
+            let rate = write_rate_stats.snapshot();
+            info!(
+                "DynamoDB writes: rate {:.0} items/s, written {}, throttled {}, failed {}, shed {}",
+                rate.rate, rate.written, rate.throttled, rate.failed, rate.shed
+            );
+            if let Some(journal) = &journal_stats {
+                let stats = journal.stats();
+                info!(
+                    "Journal: recorded {}, dropped {}, failed {}, segments {}, bytes {}",
+                    stats.recorded, stats.dropped, stats.failed, stats.segments, stats.bytes
+                );
+            }
+            let frames = feed_stats_stream.frame_metrics();
+            info!(
+                "Book Ticker frames: acks {}, rejected {}, malformed {}, unrouted {}, invalid numbers {}",
+                frames.control_acks,
+                frames.control_rejected,
+                frames.malformed,
+                frames.unrouted,
+                frames.invalid_number
+            );
+            for (feed, stats) in feed_stats_stream.feed_stats() {
+                info!(
+                    "Feed {}: messages {}, win rate {:.3}, latency mean {:.1} ms, recent {:.1} ms",
+                    feed,
+                    stats.messages,
+                    stats.win_rate,
+                    stats.mean_latency_ms,
+                    stats.recent_latency_ms
+                );
+            }
+            let latency = feed_stats_stream.take_interval_latency();
+            for connection in &latency.connections {
+                let stats = &connection.latency;
+                info!(
+                    "Connection {}{} latency: exchange p50 {} p99 {} max {} ms, store p50 {} p99 {} us",
+                    connection.connection,
+                    connection.feed,
+                    stats.exchange_to_receive_ms.p50,
+                    stats.exchange_to_receive_ms.p99,
+                    stats.exchange_to_receive_ms.max,
+                    stats.receive_to_store_us.p50,
+                    stats.receive_to_store_us.p99
+                );
+            }
+            let mut slowest: Vec<_> = latency.symbols.iter().collect();
+            slowest.sort_by_key(|(_, stats)| Reverse(stats.exchange_to_receive_ms.p99));
+            for (symbol, stats) in slowest.into_iter().take(5) {
+                info!(
+                    "Slow symbol {}: exchange p50 {} p99 {} ms (clock offset {} ms)",
+                    symbol,
+                    stats.exchange_to_receive_ms.p50,
+                    stats.exchange_to_receive_ms.p99,
+                    latency.clock_offset_ms
+                );
+            }
+        }
+    });
+
+    let (order_sink, order_writer_task) = persist_orders(ddb_client, order_table, write_rate);
+    let mut user_data_stream = UserDataStream::new(listen_key.clone()).with_order_sink(order_sink);
+    if let Some(journal) = &journal {
+        user_data_stream = user_data_stream.with_journal(journal.clone());
+    }
+    let fanout_server = FanoutServer::new(bookticker_stream.clone(), FanoutConfig::from_env())
+        .with_user_data(FANOUT_ACCOUNT, user_data_stream.clone());
+    let fanout_task = {
+        let fanout_server = fanout_server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = fanout_server.serve().await {
+                info!("Fan-out server stopped: {}", e);
+            }
+        })
+    };
+
     let user_data_listener_task = {
         let user_data_stream_clone = user_data_stream.clone();
         tokio::spawn(async move {
@@ -85,11 +294,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
-    let _ = tokio::try_join!(
-        bookticker_task,
-        printer_task,
-        user_data_listener_task,
-        keep_listen_key_alive_task
-    );
+    let tasks = async {
+        let _ = tokio::try_join!(
+            bookticker_task,
+            printer_task,
+            staleness_task,
+            clock_sync_task,
+            http_api_task,
+            fanout_task,
+            ticker_writer_task,
+            ticker_consumer_task,
+            ticker_writer_stats_task,
+            bar_aggregator_task,
+            order_writer_task,
+            user_data_listener_task,
+            keep_listen_key_alive_task
+        );
+    };
+    tokio::select! {
+        _ = tasks => {}
+        _ = shutdown_signal() => info!("Shutting down"),
+    }
+    // Complete the open journal segment so nothing is left as `.part`.
+    if let (Some(journal), Some(journal_task)) = (journal, journal_task) {
+        journal.close();
+        let _ = journal_task.await;
+    }
     Ok(())
 }
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+/// `<symbol>@markPrice@1s`, also the elements of `!markPrice@arr@1s`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MarkPrice {
+    #[serde(rename = "e")]
+    pub event: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "p")]
+    pub mark_price: String,
+    #[serde(rename = "i")]
+    pub index_price: String,
+    /// Estimated settle price, only meaningful in the last hour before
+    /// settlement.
+    #[serde(rename = "P")]
+    pub estimated_settle_price: String,
+    #[serde(rename = "r")]
+    pub funding_rate: String,
+    #[serde(rename = "T")]
+    pub next_funding_time: u64,
+}
+
+/// `<symbol>@kline_<interval>`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct KlineEvent {
+    #[serde(rename = "e")]
+    pub event: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "k")]
+    pub kline: Kline,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Kline {
+    #[serde(rename = "t")]
+    pub open_time: u64,
+    #[serde(rename = "T")]
+    pub close_time: u64,
+    #[serde(rename = "i")]
+    pub interval: String,
+    #[serde(rename = "f")]
+    pub first_trade_id: i64,
+    #[serde(rename = "L")]
+    pub last_trade_id: i64,
+    #[serde(rename = "o")]
+    pub open: String,
+    #[serde(rename = "c")]
+    pub close: String,
+    #[serde(rename = "h")]
+    pub high: String,
+    #[serde(rename = "l")]
+    pub low: String,
+    #[serde(rename = "v")]
+    pub volume: String,
+    #[serde(rename = "n")]
+    pub trades: u64,
+    /// Whether the kline is closed; open klines are updated in place.
+    #[serde(rename = "x")]
+    pub closed: bool,
+    #[serde(rename = "q")]
+    pub quote_volume: String,
+    #[serde(rename = "V")]
+    pub taker_buy_volume: String,
+    #[serde(rename = "Q")]
+    pub taker_buy_quote_volume: String,
+}
+
+/// `<symbol>@aggTrade`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AggTrade {
+    #[serde(rename = "e")]
+    pub event: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "a")]
+    pub agg_trade_id: u64,
+    #[serde(rename = "p")]
+    pub price: String,
+    #[serde(rename = "q")]
+    pub qty: String,
+    #[serde(rename = "f")]
+    pub first_trade_id: u64,
+    #[serde(rename = "l")]
+    pub last_trade_id: u64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    /// Whether the buyer was the maker, i.e. the aggressor sold.
+    #[serde(rename = "m")]
+    pub buyer_is_maker: bool,
+}
+
+/// `<symbol>@miniTicker`, rolling 24-hour statistics.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MiniTicker {
+    #[serde(rename = "e")]
+    pub event: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "c")]
+    pub close: String,
+    #[serde(rename = "o")]
+    pub open: String,
+    #[serde(rename = "h")]
+    pub high: String,
+    #[serde(rename = "l")]
+    pub low: String,
+    #[serde(rename = "v")]
+    pub volume: String,
+    #[serde(rename = "q")]
+    pub quote_volume: String,
+}
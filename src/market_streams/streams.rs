@@ -0,0 +1,315 @@
+use crate::market_streams::models::{AggTrade, KlineEvent, MarkPrice, MiniTicker};
+use crate::websocket::connection::{
+    ConnectionDriver, ConnectionHandle, ConnectionState, FrameHandler,
+};
+use crate::websocket::control::MAX_STREAMS_PER_CONNECTION;
+use crate::websocket::feed::Feed;
+use crate::websocket::frame_errors::{FrameError, FrameErrorReporter, FrameMetricsSnapshot};
+use crate::websocket::reconnect::ReconnectPolicy;
+use crate::websocket::rotation::RotationPolicy;
+use crate::websocket::router::{Routed, RouterStatsSnapshot, StreamRouter};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::Duration;
+
+pub const MARKET_WS_URL: &str = "wss://fstream.binance.com/stream";
+pub const ALL_MARK_PRICES_STREAM: &str = "!markPrice@arr@1s";
+const MARKET_EVENT_CAPACITY: usize = 4096;
+/// At most one log line per frame error kind in this interval.
+const FRAME_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A subscribable market data stream.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MarketStream {
+    MarkPrice(String),
+    /// Symbol and interval, e.g. `1m` or `1h`.
+    Kline(String, String),
+    AggTrade(String),
+    MiniTicker(String),
+    /// Mark price and funding of every symbol, once per second.
+    AllMarkPrices,
+}
+
+impl MarketStream {
+    pub fn name(&self) -> String {
+        match self {
+            MarketStream::MarkPrice(symbol) => format!("{}@markPrice@1s", symbol.to_lowercase()),
+            MarketStream::Kline(symbol, interval) => {
+                format!("{}@kline_{}", symbol.to_lowercase(), interval)
+            }
+            MarketStream::AggTrade(symbol) => format!("{}@aggTrade", symbol.to_lowercase()),
+            MarketStream::MiniTicker(symbol) => format!("{}@miniTicker", symbol.to_lowercase()),
+            MarketStream::AllMarkPrices => ALL_MARK_PRICES_STREAM.to_string(),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<MarketStream> {
+        if name == ALL_MARK_PRICES_STREAM {
+            return Some(MarketStream::AllMarkPrices);
+        }
+        let (symbol, stream) = name.split_once('@')?;
+        let symbol = symbol.to_uppercase();
+        match stream {
+            "markPrice@1s" => Some(MarketStream::MarkPrice(symbol)),
+            "aggTrade" => Some(MarketStream::AggTrade(symbol)),
+            "miniTicker" => Some(MarketStream::MiniTicker(symbol)),
+            _ => stream
+                .strip_prefix("kline_")
+                .map(|interval| MarketStream::Kline(symbol, interval.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for MarketStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MarketEvent {
+    MarkPrice(MarkPrice),
+    Kline(KlineEvent),
+    AggTrade(AggTrade),
+    MiniTicker(MiniTicker),
+}
+
+/// Decoded payload of one frame, before it is applied to the stores.
+#[derive(Debug)]
+enum MarketMessage {
+    MarkPrice(MarkPrice),
+    AllMarkPrices(Vec<MarkPrice>),
+    Kline(KlineEvent),
+    AggTrade(AggTrade),
+    MiniTicker(MiniTicker),
+}
+
+#[derive(Debug, Clone)]
+pub struct MarketStreamsConfig {
+    pub url: String,
+    pub reconnect: ReconnectPolicy,
+    pub rotation: RotationPolicy,
+    pub max_streams_per_connection: usize,
+}
+
+impl Default for MarketStreamsConfig {
+    fn default() -> Self {
+        MarketStreamsConfig {
+            url: MARKET_WS_URL.to_string(),
+            reconnect: ReconnectPolicy::default(),
+            rotation: RotationPolicy::default(),
+            max_streams_per_connection: MAX_STREAMS_PER_CONNECTION,
+        }
+    }
+}
+
+/// Mark price, kline, aggregate trade and mini ticker streams, each kept in
+/// its own latest-value store and published as `MarketEvent`s.
+#[derive(Debug, Clone)]
+pub struct MarketStreams {
+    pub mark_prices: Arc<Mutex<HashMap<String, MarkPrice>>>,
+    /// Latest kline per symbol and interval, closed or not.
+    pub klines: Arc<Mutex<HashMap<(String, String), KlineEvent>>>,
+    pub agg_trades: Arc<Mutex<HashMap<String, AggTrade>>>,
+    pub mini_tickers: Arc<Mutex<HashMap<String, MiniTicker>>>,
+    events: broadcast::Sender<MarketEvent>,
+    config: MarketStreamsConfig,
+    connections: Arc<Mutex<Vec<ConnectionHandle>>>,
+    router: StreamRouter<MarketMessage>,
+    frame_errors: FrameErrorReporter,
+}
+
+impl Default for MarketStreams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MarketStreams {
+    pub fn new() -> Self {
+        Self::with_config(MarketStreamsConfig::default())
+    }
+
+    pub fn with_config(config: MarketStreamsConfig) -> Self {
+        let (events, _) = broadcast::channel(MARKET_EVENT_CAPACITY);
+        MarketStreams {
+            mark_prices: Arc::new(Mutex::new(HashMap::new())),
+            klines: Arc::new(Mutex::new(HashMap::new())),
+            agg_trades: Arc::new(Mutex::new(HashMap::new())),
+            mini_tickers: Arc::new(Mutex::new(HashMap::new())),
+            events,
+            config,
+            connections: Arc::new(Mutex::new(Vec::new())),
+            router: StreamRouter::new()
+                .on("markPrice@1s", MarketMessage::MarkPrice)
+                .on(ALL_MARK_PRICES_STREAM, MarketMessage::AllMarkPrices)
+                .on("kline_*", MarketMessage::Kline)
+                .on("aggTrade", MarketMessage::AggTrade)
+                .on("miniTicker", MarketMessage::MiniTicker),
+            frame_errors: FrameErrorReporter::new("Market", FRAME_ERROR_LOG_INTERVAL),
+        }
+    }
+
+    /// Every update accepted into the stores.
+    pub fn events(&self) -> broadcast::Receiver<MarketEvent> {
+        self.events.subscribe()
+    }
+
+    /// Counts of routed, control, unrouted and malformed frames.
+    pub fn router_stats(&self) -> RouterStatsSnapshot {
+        self.router.stats()
+    }
+
+    /// Control acks and frame errors seen on the market stream connections.
+    pub fn frame_metrics(&self) -> FrameMetricsSnapshot {
+        self.frame_errors.snapshot()
+    }
+
+    /// Subscribes `streams`, filling the least loaded connection first and
+    /// opening connections as `max_streams_per_connection` is reached.
+    pub async fn subscribe(&self, streams: &[MarketStream]) {
+        let mut connections = self.connections.lock().await;
+        let max_streams = self.config.max_streams_per_connection.max(1);
+        let names: BTreeSet<String> = streams
+            .iter()
+            .map(MarketStream::name)
+            .filter(|name| !connections.iter().any(|c| c.contains(name)))
+            .collect();
+        let mut additions: Vec<Vec<String>> = vec![Vec::new(); connections.len()];
+        for name in names {
+            let target = (0..connections.len())
+                .filter(|index| {
+                    connections[*index].stream_count() + additions[*index].len() < max_streams
+                })
+                .min_by_key(|index| connections[*index].stream_count() + additions[*index].len());
+            match target {
+                Some(index) => additions[index].push(name),
+                None => {
+                    let handle = self.driver().spawn(connections.len(), Vec::new());
+                    connections.push(handle);
+                    additions.push(vec![name]);
+                }
+            }
+        }
+        for (connection, added) in connections.iter().zip(additions) {
+            connection.add_symbols(added);
+        }
+    }
+
+    pub async fn unsubscribe(&self, streams: &[MarketStream]) {
+        let names: Vec<String> = streams.iter().map(MarketStream::name).collect();
+        let connections = self.connections.lock().await;
+        for connection in connections.iter() {
+            let owned: Vec<String> = names
+                .iter()
+                .filter(|name| connection.contains(name))
+                .cloned()
+                .collect();
+            connection.remove_symbols(owned);
+        }
+    }
+
+    /// Streams currently subscribed across all connections.
+    pub async fn subscriptions(&self) -> Vec<MarketStream> {
+        let connections = self.connections.lock().await;
+        connections
+            .iter()
+            .flat_map(|connection| connection.symbols())
+            .filter_map(|name| MarketStream::parse(&name))
+            .collect()
+    }
+
+    fn driver(&self) -> ConnectionDriver<MarketStreams> {
+        ConnectionDriver {
+            name: "Market".to_string(),
+            stream_name: str::to_string,
+            feeds: vec![(Feed::A, self.config.url.clone())],
+            reconnect: self.config.reconnect.clone(),
+            rotation: self.config.rotation.clone(),
+            handler: self.clone(),
+            failures: None,
+        }
+    }
+
+    async fn on_mark_price(&self, mark_price: MarkPrice) {
+        let mut mark_prices = self.mark_prices.lock().await;
+        if is_older(
+            mark_prices.get(&mark_price.symbol).map(|m| m.event_time),
+            mark_price.event_time,
+        ) {
+            return;
+        }
+        mark_prices.insert(mark_price.symbol.clone(), mark_price.clone());
+        let _ = self.events.send(MarketEvent::MarkPrice(mark_price));
+    }
+
+    async fn on_kline(&self, kline: KlineEvent) {
+        let key = (kline.symbol.clone(), kline.kline.interval.clone());
+        let mut klines = self.klines.lock().await;
+        if is_older(klines.get(&key).map(|k| k.event_time), kline.event_time) {
+            return;
+        }
+        klines.insert(key, kline.clone());
+        let _ = self.events.send(MarketEvent::Kline(kline));
+    }
+
+    async fn on_agg_trade(&self, trade: AggTrade) {
+        let mut agg_trades = self.agg_trades.lock().await;
+        if agg_trades
+            .get(&trade.symbol)
+            .is_some_and(|latest| latest.agg_trade_id >= trade.agg_trade_id)
+        {
+            return;
+        }
+        agg_trades.insert(trade.symbol.clone(), trade.clone());
+        let _ = self.events.send(MarketEvent::AggTrade(trade));
+    }
+
+    async fn on_mini_ticker(&self, ticker: MiniTicker) {
+        let mut mini_tickers = self.mini_tickers.lock().await;
+        if is_older(
+            mini_tickers.get(&ticker.symbol).map(|t| t.event_time),
+            ticker.event_time,
+        ) {
+            return;
+        }
+        mini_tickers.insert(ticker.symbol.clone(), ticker.clone());
+        let _ = self.events.send(MarketEvent::MiniTicker(ticker));
+    }
+}
+
+/// Whether an update at `event_time` is older than the stored one. Equal
+/// times are treated as duplicates, e.g. from overlapping connections.
+fn is_older(stored: Option<u64>, event_time: u64) -> bool {
+    stored.is_some_and(|stored| stored >= event_time)
+}
+
+impl FrameHandler for MarketStreams {
+    async fn handle_text(&self, text: &str, state: &mut ConnectionState) {
+        match self.router.route(text) {
+            Routed::Message(MarketMessage::MarkPrice(mark_price)) => {
+                self.on_mark_price(mark_price).await;
+            }
+            Routed::Message(MarketMessage::AllMarkPrices(mark_prices)) => {
+                for mark_price in mark_prices {
+                    self.on_mark_price(mark_price).await;
+                }
+            }
+            Routed::Message(MarketMessage::Kline(kline)) => self.on_kline(kline).await,
+            Routed::Message(MarketMessage::AggTrade(trade)) => self.on_agg_trade(trade).await,
+            Routed::Message(MarketMessage::MiniTicker(ticker)) => {
+                self.on_mini_ticker(ticker).await;
+            }
+            Routed::Control(response) => match state.handle_response(response) {
+                Ok(()) => self.frame_errors.record_ack(),
+                Err(e) => self.frame_errors.record(&e),
+            },
+            Routed::Unrouted(stream) => self.frame_errors.record(&FrameError::Unrouted(stream)),
+            Routed::Malformed(reason) => {
+                self.frame_errors.record(&FrameError::Malformed(reason));
+            }
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use crate::async_binance::models::DepthSnapshot;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::num::ParseFloatError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum OrderBookError {
+    #[error("invalid price level: {0}")]
+    InvalidLevel(#[from] ParseFloatError),
+    /// The stream skipped updates; the book has to be rebuilt from a snapshot.
+    #[error("{symbol}: expected update after {expected}, got {first_update_id}..{final_update_id} (pu {previous_update_id})")]
+    Gap {
+        symbol: String,
+        expected: u64,
+        first_update_id: u64,
+        final_update_id: u64,
+        previous_update_id: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+/// Price key ordered by `f64::total_cmp`, so it can index a `BTreeMap`.
+#[derive(Debug, Clone, Copy)]
+pub struct Price(pub f64);
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Level {
+    pub price: f64,
+    pub qty: f64,
+}
+
+/// One level of cumulative depth: total quantity from the best price up to
+/// and including `price`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CumulativeLevel {
+    pub price: f64,
+    pub qty: f64,
+    pub notional: f64,
+}
+
+/// `<symbol>@depth@100ms` event. Quantities are absolute; zero removes the level.
+#[derive(Deserialize, Debug, Clone)]
+pub struct DepthUpdate {
+    #[serde(rename = "e")]
+    pub event: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "T")]
+    pub trans_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    #[serde(rename = "pu")]
+    pub previous_update_id: u64,
+    #[serde(rename = "b")]
+    pub bids: Vec<(String, String)>,
+    #[serde(rename = "a")]
+    pub asks: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyOutcome {
+    Applied,
+    /// The event ends before the snapshot and is already reflected in it.
+    Outdated,
+}
+
+/// Local order book for one symbol, built from a REST snapshot and kept up
+/// to date with diff depth events following Binance's sequencing rules.
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    pub symbol: String,
+    pub last_update_id: u64,
+    pub event_time: u64,
+    bids: BTreeMap<Price, f64>,
+    asks: BTreeMap<Price, f64>,
+    /// Whether an event has been applied on top of the snapshot yet.
+    bridged: bool,
+}
+
+impl OrderBook {
+    pub fn from_snapshot(symbol: &str, snapshot: &DepthSnapshot) -> Result<Self, OrderBookError> {
+        let mut book = OrderBook {
+            symbol: symbol.to_string(),
+            last_update_id: snapshot.lastUpdateId,
+            event_time: snapshot.E,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            bridged: false,
+        };
+        book.set_levels(Side::Bid, &snapshot.bids)?;
+        book.set_levels(Side::Ask, &snapshot.asks)?;
+        Ok(book)
+    }
+
+    /// Applies a diff depth event:
+    /// - events with `u` < the snapshot's `lastUpdateId` are dropped;
+    /// - the first applied event must have `U` <= `lastUpdateId` <= `u`;
+    /// - every later event's `pu` must equal the previous event's `u`.
+    ///
+    /// Any other event means updates were missed and returns `Gap`.
+    pub fn apply(&mut self, update: &DepthUpdate) -> Result<ApplyOutcome, OrderBookError> {
+        if !self.bridged {
+            if update.final_update_id < self.last_update_id {
+                return Ok(ApplyOutcome::Outdated);
+            }
+            if update.first_update_id > self.last_update_id {
+                return Err(self.gap(update));
+            }
+        } else if update.previous_update_id != self.last_update_id {
+            return Err(self.gap(update));
+        }
+        self.set_levels(Side::Bid, &update.bids)?;
+        self.set_levels(Side::Ask, &update.asks)?;
+        self.last_update_id = update.final_update_id;
+        self.event_time = update.event_time;
+        self.bridged = true;
+        Ok(ApplyOutcome::Applied)
+    }
+
+    pub fn best_bid(&self) -> Option<Level> {
+        self.levels(Side::Bid, 1).into_iter().next()
+    }
+
+    pub fn best_ask(&self) -> Option<Level> {
+        self.levels(Side::Ask, 1).into_iter().next()
+    }
+
+    /// Up to `depth` levels of `side`, best price first.
+    pub fn levels(&self, side: Side, depth: usize) -> Vec<Level> {
+        self.iter_side(side)
+            .take(depth)
+            .map(|(price, qty)| Level { price, qty })
+            .collect()
+    }
+
+    /// Running totals of quantity and notional over the best `depth` levels.
+    pub fn cumulative_depth(&self, side: Side, depth: usize) -> Vec<CumulativeLevel> {
+        let mut qty = 0.0;
+        let mut notional = 0.0;
+        self.iter_side(side)
+            .take(depth)
+            .map(|(price, level_qty)| {
+                qty += level_qty;
+                notional += price * level_qty;
+                CumulativeLevel {
+                    price,
+                    qty,
+                    notional,
+                }
+            })
+            .collect()
+    }
+
+    /// Average price of filling `size` against `side`, walking from the best
+    /// level. Use `Side::Ask` to buy and `Side::Bid` to sell. `None` if the
+    /// book is not deep enough.
+    pub fn vwap(&self, side: Side, size: f64) -> Option<f64> {
+        if size <= 0.0 {
+            return None;
+        }
+        let mut remaining = size;
+        let mut notional = 0.0;
+        for (price, qty) in self.iter_side(side) {
+            let fill = qty.min(remaining);
+            notional += price * fill;
+            remaining -= fill;
+            if remaining <= 0.0 {
+                return Some(notional / size);
+            }
+        }
+        None
+    }
+
+    fn iter_side(&self, side: Side) -> Box<dyn Iterator<Item = (f64, f64)> + '_> {
+        match side {
+            Side::Bid => Box::new(self.bids.iter().rev().map(|(p, q)| (p.0, *q))),
+            Side::Ask => Box::new(self.asks.iter().map(|(p, q)| (p.0, *q))),
+        }
+    }
+
+    fn set_levels(
+        &mut self,
+        side: Side,
+        levels: &[(String, String)],
+    ) -> Result<(), OrderBookError> {
+        let book = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        for (price, qty) in levels {
+            let price = Price(price.parse()?);
+            let qty: f64 = qty.parse()?;
+            if qty == 0.0 {
+                book.remove(&price);
+            } else {
+                book.insert(price, qty);
+            }
+        }
+        Ok(())
+    }
+
+    fn gap(&self, update: &DepthUpdate) -> OrderBookError {
+        OrderBookError::Gap {
+            symbol: self.symbol.clone(),
+            expected: self.last_update_id,
+            first_update_id: update.first_update_id,
+            final_update_id: update.final_update_id,
+            previous_update_id: update.previous_update_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(levels: &[(&str, &str)]) -> Vec<(String, String)> {
+        levels
+            .iter()
+            .map(|(price, qty)| (price.to_string(), qty.to_string()))
+            .collect()
+    }
+
+    fn snapshot(last_update_id: u64) -> DepthSnapshot {
+        DepthSnapshot {
+            lastUpdateId: last_update_id,
+            E: 1,
+            T: 1,
+            bids: levels(&[("99.5", "2"), ("99", "5")]),
+            asks: levels(&[("100", "1"), ("100.5", "4")]),
+        }
+    }
+
+    fn update(first: u64, last: u64, previous: u64, bids: &[(&str, &str)]) -> DepthUpdate {
+        DepthUpdate {
+            event: "depthUpdate".to_string(),
+            event_time: last,
+            trans_time: last,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: first,
+            final_update_id: last,
+            previous_update_id: previous,
+            bids: levels(bids),
+            asks: Vec::new(),
+        }
+    }
+
+    fn book(last_update_id: u64) -> OrderBook {
+        OrderBook::from_snapshot("BTCUSDT", &snapshot(last_update_id)).unwrap()
+    }
+
+    #[test]
+    fn drops_events_before_the_snapshot() {
+        let mut book = book(100);
+        let outcome = book.apply(&update(90, 99, 89, &[("99.5", "0")])).unwrap();
+        assert_eq!(outcome, ApplyOutcome::Outdated);
+        assert_eq!(book.last_update_id, 100);
+        assert_eq!(book.best_bid().unwrap().price, 99.5);
+    }
+
+    #[test]
+    fn first_event_must_span_the_snapshot() {
+        let mut book = book(100);
+        let outcome = book.apply(&update(95, 105, 94, &[("99.5", "3")])).unwrap();
+        assert_eq!(outcome, ApplyOutcome::Applied);
+        assert_eq!(book.last_update_id, 105);
+        assert_eq!(book.best_bid().unwrap().qty, 3.0);
+    }
+
+    #[test]
+    fn first_event_after_the_snapshot_is_a_gap() {
+        let mut book = book(100);
+        let error = book.apply(&update(101, 110, 100, &[])).unwrap_err();
+        assert!(matches!(
+            error,
+            OrderBookError::Gap {
+                expected: 100,
+                first_update_id: 101,
+                ..
+            }
+        ));
+        assert_eq!(book.last_update_id, 100);
+    }
+
+    #[test]
+    fn later_events_chain_on_previous_update_id() {
+        let mut book = book(100);
+        book.apply(&update(95, 105, 94, &[])).unwrap();
+        book.apply(&update(106, 110, 105, &[("99.5", "0")]))
+            .unwrap();
+        assert_eq!(book.last_update_id, 110);
+        assert_eq!(book.best_bid().unwrap().price, 99.0);
+        // Once bridged, even an event overlapping the book must chain.
+        let error = book.apply(&update(108, 112, 107, &[])).unwrap_err();
+        assert!(matches!(
+            error,
+            OrderBookError::Gap {
+                expected: 110,
+                previous_update_id: 107,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn gap_leaves_the_book_unchanged() {
+        let mut book = book(100);
+        book.apply(&update(95, 105, 94, &[])).unwrap();
+        assert!(book
+            .apply(&update(120, 125, 119, &[("99.5", "0")]))
+            .is_err());
+        assert_eq!(book.last_update_id, 105);
+        assert_eq!(book.best_bid().unwrap().price, 99.5);
+    }
+
+    #[test]
+    fn resyncs_from_a_new_snapshot_after_a_gap() {
+        let mut book = book(100);
+        book.apply(&update(95, 105, 94, &[])).unwrap();
+        let missed = update(120, 125, 119, &[("99", "0")]);
+        assert!(book.apply(&missed).is_err());
+
+        // Buffered events are replayed on top of a fresh snapshot.
+        let mut book = OrderBook::from_snapshot("BTCUSDT", &snapshot(122)).unwrap();
+        assert_eq!(book.apply(&missed).unwrap(), ApplyOutcome::Applied);
+        book.apply(&update(126, 130, 125, &[("98", "7")])).unwrap();
+        assert_eq!(book.last_update_id, 130);
+        let bids: Vec<f64> = book
+            .levels(Side::Bid, 10)
+            .iter()
+            .map(|level| level.price)
+            .collect();
+        assert_eq!(bids, vec![99.5, 98.0]);
+    }
+
+    #[test]
+    fn zero_quantity_removes_a_level() {
+        let mut book = book(100);
+        book.apply(&update(100, 101, 99, &[("99.5", "0"), ("99.7", "1")]))
+            .unwrap();
+        assert_eq!(book.best_bid().unwrap().price, 99.7);
+        assert_eq!(book.levels(Side::Bid, 10).len(), 2);
+    }
+}
@@ -0,0 +1,414 @@
+use crate::async_binance::client_async::AsyncBinanceClient;
+use crate::async_binance::errors::CustomError;
+use crate::async_binance::models::DepthSnapshot;
+use crate::order_book::book::{
+    CumulativeLevel, DepthUpdate, Level, OrderBook, OrderBookError, Side,
+};
+use crate::websocket::connection::{
+    ConnectionDriver, ConnectionHandle, ConnectionState, FrameHandler,
+};
+use crate::websocket::control::{ControlRateLimiter, MAX_STREAMS_PER_CONNECTION};
+use crate::websocket::feed::Feed;
+use crate::websocket::reconnect::ReconnectPolicy;
+use crate::websocket::rotation::RotationPolicy;
+use crate::websocket::router::{Routed, RouterStatsSnapshot, StreamRouter};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::info;
+
+pub const DEPTH_WS_URL: &str = "wss://fstream.binance.com/stream";
+
+pub fn depth_stream_name(symbol: &str) -> String {
+    format!("{}@depth@100ms", symbol.to_lowercase())
+}
+
+#[derive(Debug, Clone)]
+pub struct DepthStreamConfig {
+    pub url: String,
+    /// Levels requested in each REST snapshot. Binance accepts 5, 10, 20,
+    /// 50, 100, 500 or 1000; 1000 costs 20 request weight.
+    pub snapshot_limit: u32,
+    /// Snapshot requests per second across all connections, so a reconnect
+    /// resyncing every symbol stays within the REST weight limit.
+    pub snapshots_per_second: u32,
+    /// Events buffered per symbol while waiting for its snapshot.
+    pub max_buffered_events: usize,
+    pub reconnect: ReconnectPolicy,
+    pub rotation: RotationPolicy,
+}
+
+impl Default for DepthStreamConfig {
+    fn default() -> Self {
+        DepthStreamConfig {
+            url: DEPTH_WS_URL.to_string(),
+            snapshot_limit: 1000,
+            snapshots_per_second: 1,
+            max_buffered_events: 1000,
+            reconnect: ReconnectPolicy::default(),
+            rotation: RotationPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum BookState {
+    /// Waiting for snapshot `snapshot`; events are buffered until it
+    /// arrives. Snapshots requested for an earlier sync are ignored.
+    Syncing {
+        buffer: VecDeque<DepthUpdate>,
+        snapshot: u64,
+    },
+    Live(OrderBook),
+}
+
+/// Local order books maintained from `@depth@100ms` diff streams and REST
+/// snapshots, resyncing a symbol whenever its sequence breaks.
+#[derive(Clone)]
+pub struct DepthStream {
+    client: AsyncBinanceClient,
+    config: DepthStreamConfig,
+    books: Arc<Mutex<HashMap<String, BookState>>>,
+    snapshot_limiter: Arc<Mutex<ControlRateLimiter>>,
+    next_snapshot: Arc<AtomicU64>,
+    router: StreamRouter<DepthUpdate>,
+}
+
+impl DepthStream {
+    pub fn new(client: AsyncBinanceClient, config: DepthStreamConfig) -> Self {
+        DepthStream {
+            client,
+            snapshot_limiter: Arc::new(Mutex::new(ControlRateLimiter::new(
+                config.snapshots_per_second,
+            ))),
+            config,
+            books: Arc::new(Mutex::new(HashMap::new())),
+            next_snapshot: Arc::new(AtomicU64::new(0)),
+            router: StreamRouter::new().on("depth@100ms", |update: DepthUpdate| update),
+        }
+    }
+
+    /// Maintains books for `symbols`, one connection per
+    /// `MAX_STREAMS_PER_CONNECTION` symbols.
+    pub fn listen(&self, symbols: Vec<String>) -> Vec<ConnectionHandle> {
+        let symbols: Vec<String> = symbols.iter().map(|s| s.to_uppercase()).collect();
+        let driver = ConnectionDriver {
+            name: "Depth".to_string(),
+            stream_name: depth_stream_name,
+            feeds: vec![(Feed::A, self.config.url.clone())],
+            reconnect: self.config.reconnect.clone(),
+            rotation: self.config.rotation.clone(),
+            handler: self.clone(),
+            failures: None,
+        };
+        symbols
+            .chunks(MAX_STREAMS_PER_CONNECTION)
+            .enumerate()
+            .map(|(id, chunk)| driver.spawn(id, chunk.to_vec()))
+            .collect()
+    }
+
+    /// Counts of routed, unrouted and malformed frames.
+    pub fn router_stats(&self) -> RouterStatsSnapshot {
+        self.router.stats()
+    }
+
+    pub async fn is_synced(&self, symbol: &str) -> bool {
+        matches!(
+            self.books.lock().await.get(symbol),
+            Some(BookState::Live(_))
+        )
+    }
+
+    /// Copy of the current book, `None` while the symbol is resyncing.
+    pub async fn order_book(&self, symbol: &str) -> Option<OrderBook> {
+        match self.books.lock().await.get(symbol) {
+            Some(BookState::Live(book)) => Some(book.clone()),
+            _ => None,
+        }
+    }
+
+    pub async fn levels(&self, symbol: &str, side: Side, depth: usize) -> Option<Vec<Level>> {
+        self.with_book(symbol, |book| book.levels(side, depth))
+            .await
+    }
+
+    pub async fn cumulative_depth(
+        &self,
+        symbol: &str,
+        side: Side,
+        depth: usize,
+    ) -> Option<Vec<CumulativeLevel>> {
+        self.with_book(symbol, |book| book.cumulative_depth(side, depth))
+            .await
+    }
+
+    /// Average fill price for `size` on `side`; see `OrderBook::vwap`.
+    pub async fn vwap(&self, symbol: &str, side: Side, size: f64) -> Option<f64> {
+        self.with_book(symbol, |book| book.vwap(side, size))
+            .await
+            .flatten()
+    }
+
+    async fn with_book<T>(&self, symbol: &str, query: impl FnOnce(&OrderBook) -> T) -> Option<T> {
+        match self.books.lock().await.get(symbol) {
+            Some(BookState::Live(book)) => Some(query(book)),
+            _ => None,
+        }
+    }
+
+    /// Discards the book of `symbol` and requests a new snapshot, keeping
+    /// `buffer` to replay on top of it.
+    fn resync(
+        &self,
+        books: &mut HashMap<String, BookState>,
+        symbol: &str,
+        buffer: VecDeque<DepthUpdate>,
+    ) {
+        let snapshot = self.next_snapshot.fetch_add(1, Ordering::Relaxed);
+        books.insert(symbol.to_string(), BookState::Syncing { buffer, snapshot });
+        self.request_snapshot(symbol, snapshot);
+    }
+
+    fn request_snapshot(&self, symbol: &str, snapshot: u64) {
+        let stream = self.clone();
+        let symbol = symbol.to_string();
+        tokio::spawn(async move {
+            stream.snapshot_limiter.lock().await.acquire().await;
+            let result = stream
+                .client
+                .get_depth_snapshot(&symbol, stream.config.snapshot_limit)
+                .await;
+            stream.handle_snapshot(&symbol, snapshot, result).await;
+        });
+    }
+
+    async fn apply_update(&self, update: DepthUpdate) {
+        let symbol = update.symbol.clone();
+        let mut books = self.books.lock().await;
+        let Some(state) = books.get_mut(&symbol) else {
+            return;
+        };
+        match state {
+            BookState::Syncing { buffer, .. } => {
+                // Both connections deliver the same events during a rotation.
+                if buffer
+                    .back()
+                    .is_some_and(|last| update.final_update_id <= last.final_update_id)
+                {
+                    return;
+                }
+                if buffer.len() >= self.config.max_buffered_events {
+                    buffer.pop_front();
+                }
+                buffer.push_back(update);
+            }
+            BookState::Live(book) => {
+                // Already in the book, e.g. seen on the other connection
+                // during a rotation.
+                if update.final_update_id <= book.last_update_id {
+                    return;
+                }
+                match book.apply(&update) {
+                    Ok(_) => {}
+                    Err(e @ OrderBookError::Gap { .. }) => {
+                        info!("Resyncing order book: {}", e);
+                        self.resync(&mut books, &symbol, VecDeque::from([update]));
+                    }
+                    // The event may have been applied in part, so the book
+                    // is rebuilt, without the event.
+                    Err(e) => {
+                        info!("Dropping depth update for {}, resyncing: {}", symbol, e);
+                        self.resync(&mut books, &symbol, VecDeque::new());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Builds the book from `result` and replays the buffered events on top
+    /// of it. If the snapshot is too old for the buffer, or an event had to
+    /// be dropped, another is requested.
+    async fn handle_snapshot(
+        &self,
+        symbol: &str,
+        snapshot: u64,
+        result: Result<DepthSnapshot, CustomError>,
+    ) {
+        let mut books = self.books.lock().await;
+        let Some(BookState::Syncing {
+            buffer,
+            snapshot: awaited,
+        }) = books.get_mut(symbol)
+        else {
+            return;
+        };
+        if *awaited != snapshot {
+            return;
+        }
+        let depth = match result {
+            Ok(depth) => depth,
+            Err(e) => {
+                info!("Failed to fetch depth snapshot for {}: {}", symbol, e);
+                self.request_snapshot(symbol, snapshot);
+                return;
+            }
+        };
+        let mut book = match OrderBook::from_snapshot(symbol, &depth) {
+            Ok(book) => book,
+            Err(e) => {
+                info!("Invalid depth snapshot for {}: {}", symbol, e);
+                self.request_snapshot(symbol, snapshot);
+                return;
+            }
+        };
+        match replay(&mut book, buffer) {
+            Ok(()) => {}
+            Err(e @ OrderBookError::Gap { .. }) => {
+                info!(
+                    "Depth snapshot for {} is too old ({}), refetching",
+                    symbol, e
+                );
+                self.request_snapshot(symbol, snapshot);
+                return;
+            }
+            Err(e) => {
+                info!("Dropped depth update for {}, refetching: {}", symbol, e);
+                self.request_snapshot(symbol, snapshot);
+                return;
+            }
+        }
+        info!(
+            "Order book for {} synced at update {}",
+            symbol, book.last_update_id
+        );
+        books.insert(symbol.to_string(), BookState::Live(book));
+    }
+}
+
+/// Applies the buffered events to `book`, removing each one applied. On a
+/// gap the remaining events are kept for a newer snapshot; an event that
+/// cannot be applied is removed too, but the book may hold part of it, so it
+/// has to be rebuilt either way.
+fn replay(book: &mut OrderBook, buffer: &mut VecDeque<DepthUpdate>) -> Result<(), OrderBookError> {
+    while let Some(update) = buffer.front() {
+        match book.apply(update) {
+            Ok(_) => {}
+            Err(e @ OrderBookError::Gap { .. }) => return Err(e),
+            Err(e) => {
+                buffer.pop_front();
+                return Err(e);
+            }
+        }
+        buffer.pop_front();
+    }
+    Ok(())
+}
+
+impl FrameHandler for DepthStream {
+    async fn handle_text(&self, text: &str, state: &mut ConnectionState) {
+        match self.router.route(text) {
+            Routed::Message(update) => self.apply_update(update).await,
+            Routed::Control(response) => {
+                if let Err(e) = state.handle_response(response) {
+                    info!("Depth Stream: {}", e);
+                }
+            }
+            Routed::Unrouted(stream) => {
+                info!("Received message for unknown stream {}", stream);
+            }
+            Routed::Malformed(reason) => {
+                info!("Failed to deserialize depth update: {}", reason);
+            }
+        }
+    }
+
+    /// Events may have been missed while disconnected, so every book is
+    /// rebuilt from a new snapshot.
+    async fn on_connected(&self, state: &mut ConnectionState) {
+        let mut books = self.books.lock().await;
+        for symbol in state.symbols() {
+            self.resync(&mut books, &symbol, VecDeque::new());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(levels: &[(&str, &str)]) -> Vec<(String, String)> {
+        levels
+            .iter()
+            .map(|(price, qty)| (price.to_string(), qty.to_string()))
+            .collect()
+    }
+
+    fn book(last_update_id: u64) -> OrderBook {
+        let snapshot = DepthSnapshot {
+            lastUpdateId: last_update_id,
+            E: 1,
+            T: 1,
+            bids: levels(&[("99.5", "2")]),
+            asks: levels(&[("100", "1")]),
+        };
+        OrderBook::from_snapshot("BTCUSDT", &snapshot).unwrap()
+    }
+
+    fn update(first: u64, last: u64, previous: u64, bids: &[(&str, &str)]) -> DepthUpdate {
+        DepthUpdate {
+            event: "depthUpdate".to_string(),
+            event_time: last,
+            trans_time: last,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: first,
+            final_update_id: last,
+            previous_update_id: previous,
+            bids: levels(bids),
+            asks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn replays_the_whole_buffer_onto_a_snapshot() {
+        let mut book = book(100);
+        let mut buffer = VecDeque::from([
+            update(90, 99, 89, &[]),
+            update(95, 105, 94, &[("99", "3")]),
+            update(106, 110, 105, &[]),
+        ]);
+        replay(&mut book, &mut buffer).unwrap();
+        assert!(buffer.is_empty());
+        assert_eq!(book.last_update_id, 110);
+    }
+
+    #[test]
+    fn keeps_the_buffer_when_the_snapshot_is_too_old() {
+        let mut book = book(100);
+        let mut buffer = VecDeque::from([update(120, 125, 119, &[]), update(126, 130, 125, &[])]);
+        assert!(matches!(
+            replay(&mut book, &mut buffer),
+            Err(OrderBookError::Gap { .. })
+        ));
+        assert_eq!(buffer.len(), 2);
+    }
+
+    #[test]
+    fn drops_an_event_that_cannot_be_applied() {
+        let mut stale = book(100);
+        let mut buffer = VecDeque::from([
+            update(95, 105, 94, &[("n/a", "1")]),
+            update(106, 110, 105, &[]),
+        ]);
+        assert!(matches!(
+            replay(&mut stale, &mut buffer),
+            Err(OrderBookError::InvalidLevel(_))
+        ));
+        // A newer snapshot then skips past it instead of failing again.
+        assert_eq!(buffer.len(), 1);
+        let mut rebuilt = book(108);
+        replay(&mut rebuilt, &mut buffer).unwrap();
+        assert_eq!(rebuilt.last_update_id, 110);
+    }
+}
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "e", rename_all = "SCREAMING_SNAKE_CASE")]
+#[allow(clippy::large_enum_variant)]
 pub enum UserDataUpdate {
     ListenKeyExpired(ListenKeyExpiredEvent),
     AccountUpdate(BalancePositionUpdateEvent),
@@ -1,3 +1,4 @@
 pub mod messages;
 pub mod order_db;
 pub mod order_update;
+pub mod order_writer;
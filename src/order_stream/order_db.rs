@@ -0,0 +1,219 @@
+use crate::aws_resources::dynamodb_tables::{KeyAttributeType, TableBillingMode, TableConfig};
+use crate::aws_resources::item_attributes::{
+    bool_attribute, number_attribute, string_attribute, ItemError,
+};
+use crate::order_stream::messages::OrderTradeUpdateEvent;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub const ORDER_TABLE_NAME: &str = "TestOrderTable";
+const DEFAULT_RETENTION_DAYS: u64 = 365;
+
+/// Items are keyed by `<partition_key>` = symbol and `<sort_key>` =
+/// `<event time ms, 13 digits>#<order id>#<status>`, so a symbol's order
+/// updates sort by time.
+#[derive(Debug, Clone)]
+pub struct OrderTableConfig {
+    pub table: TableConfig,
+}
+
+impl Default for OrderTableConfig {
+    fn default() -> Self {
+        OrderTableConfig {
+            table: TableConfig {
+                table_name: ORDER_TABLE_NAME.to_string(),
+                partition_key: "PK".to_string(),
+                sort_key: "SK".to_string(),
+                sort_key_type: KeyAttributeType::String,
+                ttl_attribute: Some("expires_at".to_string()),
+                retention: Some(Duration::from_secs(DEFAULT_RETENTION_DAYS * 24 * 3600)),
+                billing_mode: TableBillingMode::PayPerRequest,
+                stream_enabled: true,
+            },
+        }
+    }
+}
+
+/// One `ORDER_TRADE_UPDATE` as stored in the order table.
+#[derive(Debug, Clone)]
+pub struct OrderRecord {
+    pub symbol: String,
+    pub order_id: u64,
+    pub client_order_id: String,
+    pub side: String,
+    pub order_type: String,
+    pub execution_type: String,
+    pub status: String,
+    pub price: String,
+    pub quantity: String,
+    pub average_price: String,
+    pub last_filled_price: String,
+    pub last_filled_quantity: String,
+    pub filled_quantity: String,
+    pub commission: String,
+    pub commission_asset: String,
+    pub realized_profit: String,
+    pub trade_id: u64,
+    pub maker: bool,
+    pub reduce_only: bool,
+    pub event_time: u64,
+    pub trade_time: u64,
+}
+
+impl OrderRecord {
+    pub fn from_update(update: &OrderTradeUpdateEvent) -> Self {
+        let order = &update.o;
+        OrderRecord {
+            symbol: order.s.clone(),
+            order_id: order.i,
+            client_order_id: order.c.clone(),
+            side: order.S.clone(),
+            order_type: order.o.clone(),
+            execution_type: order.x.clone(),
+            status: order.X.clone(),
+            price: order.p.clone(),
+            quantity: order.q.clone(),
+            average_price: order.ap.clone(),
+            last_filled_price: order.L.clone(),
+            last_filled_quantity: order.l.clone(),
+            filled_quantity: order.z.clone(),
+            commission: order.n.clone(),
+            commission_asset: order.N.clone(),
+            realized_profit: order.rp.clone(),
+            trade_id: order.t,
+            maker: order.m,
+            reduce_only: order.R,
+            event_time: update.E,
+            trade_time: order.T,
+        }
+    }
+}
+
+impl OrderTableConfig {
+    /// Reads `ORDER_TABLE_NAME`, falling back to the default.
+    pub fn from_env() -> Self {
+        let mut config = OrderTableConfig::default();
+        if let Ok(table_name) = std::env::var("ORDER_TABLE_NAME") {
+            config.table.table_name = table_name;
+        }
+        config
+    }
+
+    pub fn sort_key_value(&self, record: &OrderRecord) -> String {
+        format!(
+            "{:013}#{}#{}",
+            record.event_time, record.order_id, record.status
+        )
+    }
+
+    /// Lowest sort key of any update at or after `event_time`.
+    pub fn sort_key_lower_bound(&self, event_time: u64) -> String {
+        format!("{:013}", event_time)
+    }
+
+    /// Highest sort key of any update at or before `event_time`.
+    pub fn sort_key_upper_bound(&self, event_time: u64) -> String {
+        format!("{:013}~", event_time)
+    }
+
+    pub fn order_to_item(&self, record: &OrderRecord) -> HashMap<String, AttributeValue> {
+        let string = |value: &str| AttributeValue::S(value.to_string());
+        let number = |value: &str| AttributeValue::N(value.to_string());
+        let mut fields = HashMap::from([
+            (self.table.partition_key.clone(), string(&record.symbol)),
+            (
+                self.table.sort_key.clone(),
+                AttributeValue::S(self.sort_key_value(record)),
+            ),
+            ("symbol".to_string(), string(&record.symbol)),
+            ("order_id".to_string(), number(&record.order_id.to_string())),
+            (
+                "client_order_id".to_string(),
+                string(&record.client_order_id),
+            ),
+            ("side".to_string(), string(&record.side)),
+            ("order_type".to_string(), string(&record.order_type)),
+            ("execution_type".to_string(), string(&record.execution_type)),
+            ("status".to_string(), string(&record.status)),
+            ("price".to_string(), number(&record.price)),
+            ("quantity".to_string(), number(&record.quantity)),
+            ("average_price".to_string(), number(&record.average_price)),
+            (
+                "last_filled_price".to_string(),
+                number(&record.last_filled_price),
+            ),
+            (
+                "last_filled_quantity".to_string(),
+                number(&record.last_filled_quantity),
+            ),
+            (
+                "filled_quantity".to_string(),
+                number(&record.filled_quantity),
+            ),
+            ("commission".to_string(), number(&record.commission)),
+            (
+                "commission_asset".to_string(),
+                string(&record.commission_asset),
+            ),
+            (
+                "realized_profit".to_string(),
+                number(&record.realized_profit),
+            ),
+            ("trade_id".to_string(), number(&record.trade_id.to_string())),
+            ("maker".to_string(), AttributeValue::Bool(record.maker)),
+            (
+                "reduce_only".to_string(),
+                AttributeValue::Bool(record.reduce_only),
+            ),
+            (
+                "event_time".to_string(),
+                number(&record.event_time.to_string()),
+            ),
+            (
+                "trade_time".to_string(),
+                number(&record.trade_time.to_string()),
+            ),
+        ]);
+        if let (Some(ttl_attribute), Some(expires_at)) = (
+            &self.table.ttl_attribute,
+            self.table.expires_at(record.event_time),
+        ) {
+            fields.insert(
+                ttl_attribute.clone(),
+                AttributeValue::N(expires_at.to_string()),
+            );
+        }
+        fields
+    }
+
+    pub fn item_to_order(
+        &self,
+        item: &HashMap<String, AttributeValue>,
+    ) -> Result<OrderRecord, ItemError> {
+        let string = |name: &str| string_attribute(item, name).map(str::to_string);
+        Ok(OrderRecord {
+            symbol: string("symbol")?,
+            order_id: number_attribute(item, "order_id")?,
+            client_order_id: string("client_order_id")?,
+            side: string("side")?,
+            order_type: string("order_type")?,
+            execution_type: string("execution_type")?,
+            status: string("status")?,
+            price: string("price")?,
+            quantity: string("quantity")?,
+            average_price: string("average_price")?,
+            last_filled_price: string("last_filled_price")?,
+            last_filled_quantity: string("last_filled_quantity")?,
+            filled_quantity: string("filled_quantity")?,
+            commission: string("commission")?,
+            commission_asset: string("commission_asset")?,
+            realized_profit: string("realized_profit")?,
+            trade_id: number_attribute(item, "trade_id")?,
+            maker: bool_attribute(item, "maker")?,
+            reduce_only: bool_attribute(item, "reduce_only")?,
+            event_time: number_attribute(item, "event_time")?,
+            trade_time: number_attribute(item, "trade_time")?,
+        })
+    }
+}
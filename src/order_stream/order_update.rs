@@ -1,32 +1,83 @@
+use crate::journal::record::JournalSource;
+use crate::journal::writer::Journal;
 use crate::order_stream::messages::UserDataUpdate;
+use crate::websocket::feed::Feed;
+use crate::websocket::reconnect::{ReconnectPolicy, ReconnectStatus, Reconnector};
 use futures::{SinkExt, StreamExt};
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::Instant;
 use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::Message;
 use tracing::info;
 
+const USER_DATA_EVENT_CAPACITY: usize = 1024;
+
 #[derive(Clone, Debug)]
 pub struct UserDataStream {
     pub listen_key: String,
+    pub reconnector: Reconnector,
+    journal: Option<Journal>,
+    events: broadcast::Sender<Arc<UserDataUpdate>>,
+    orders: Option<mpsc::UnboundedSender<Arc<UserDataUpdate>>>,
 }
 
 impl UserDataStream {
+    pub fn new(listen_key: String) -> Self {
+        UserDataStream {
+            listen_key,
+            reconnector: Reconnector::new("User Data Stream", ReconnectPolicy::default()),
+            journal: None,
+            events: broadcast::channel(USER_DATA_EVENT_CAPACITY).0,
+            orders: None,
+        }
+    }
+
+    /// Every update decoded from the stream.
+    pub fn events(&self) -> broadcast::Receiver<Arc<UserDataUpdate>> {
+        self.events.subscribe()
+    }
+
+    /// Records every frame received, before it is decoded, to `journal`.
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Also sends every `ORDER_TRADE_UPDATE` to `orders`, which unlike
+    /// `events` never drops one for a slow receiver.
+    pub fn with_order_sink(mut self, orders: mpsc::UnboundedSender<Arc<UserDataUpdate>>) -> Self {
+        self.orders = Some(orders);
+        self
+    }
+
+    pub fn reconnect_status(&self) -> ReconnectStatus {
+        self.reconnector.status()
+    }
+
     pub async fn listen_user_data(&self) -> Result<(), Box<dyn std::error::Error + Send>> {
         loop {
             let url = format!("wss://fstream.binance.com/ws/{}", self.listen_key);
             let (ws_stream, _) = match connect_async(&url).await {
                 Ok(stream) => {
                     info!("Listen to User Data Stream");
+                    self.reconnector.on_connected();
                     stream
                 }
                 Err(e) => {
                     info!("Failed to connect: {}, retrying...", e);
+                    self.reconnector.wait_after_failure().await;
                     continue;
                 }
             };
+            let connected_at = Instant::now();
             let (mut write, mut read) = ws_stream.split();
             while let Some(message) = read.next().await {
                 match message {
                     Ok(Message::Text(text)) => {
+                        if let Some(journal) = &self.journal {
+                            journal.record(JournalSource::UserData, 0, Feed::A, &text);
+                        }
                         self.handle_user_data_update(&text).await;
                     }
                     Ok(Message::Ping(payload)) => {
@@ -46,14 +97,23 @@ impl UserDataStream {
                     }
                 }
             }
-            info!("Use Data Connection lost, retrying immediately...");
-            continue;
+            info!("Use Data Connection lost, reconnecting...");
+            self.reconnector
+                .wait_after_disconnect(connected_at.elapsed())
+                .await;
         }
     }
-    async fn handle_user_data_update(&self, text: &str) {
+    pub async fn handle_user_data_update(&self, text: &str) {
         match serde_json::from_str::<UserDataUpdate>(text) {
             Ok(update) => {
-                self.process_update(update).await;
+                let update = Arc::new(update);
+                if let (Some(orders), UserDataUpdate::OrderTradeUpdate(_)) =
+                    (&self.orders, update.as_ref())
+                {
+                    let _ = orders.send(Arc::clone(&update));
+                }
+                let _ = self.events.send(Arc::clone(&update));
+                self.process_update(&update).await;
             }
             Err(e) => {
                 info!("Failed to deserialize message: {}\n, text: {}\n", e, text);
@@ -61,7 +121,7 @@ impl UserDataStream {
         }
     }
 
-    async fn process_update(&self, update: UserDataUpdate) {
+    async fn process_update(&self, update: &UserDataUpdate) {
         match update {
             UserDataUpdate::OrderTradeUpdate(order_update) => {
                 info!("Received OrderTradeUpdate: {:?}", order_update);
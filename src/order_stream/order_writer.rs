@@ -0,0 +1,57 @@
+use crate::aws_resources::batch_write::{put_item_with_retry, BatchRetryConfig};
+use crate::aws_resources::write_rate::{WriteError, WritePriority, WriteRate};
+use crate::order_stream::messages::UserDataUpdate;
+use crate::order_stream::order_db::{OrderRecord, OrderTableConfig};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// Writes an order update at `WritePriority::High`, so under throttling it
+/// waits for capacity instead of being shed like tickers.
+pub async fn put_order_to_db(
+    client: &aws_sdk_dynamodb::Client,
+    config: &OrderTableConfig,
+    record: &OrderRecord,
+    rate: &WriteRate,
+) -> Result<(), WriteError> {
+    put_item_with_retry(
+        client,
+        &config.table.table_name,
+        config.order_to_item(record),
+        &BatchRetryConfig::default(),
+        rate,
+        WritePriority::High,
+    )
+    .await?;
+    info!(
+        "Stored order update {} {} for {}",
+        record.order_id, record.status, record.symbol
+    );
+    Ok(())
+}
+
+/// Stores every order update sent to the returned sender, which is meant
+/// for `UserDataStream::with_order_sink`. The queue is unbounded so a
+/// throttled table delays orders instead of losing them.
+pub fn persist_orders(
+    client: aws_sdk_dynamodb::Client,
+    config: OrderTableConfig,
+    rate: WriteRate,
+) -> (mpsc::UnboundedSender<Arc<UserDataUpdate>>, JoinHandle<()>) {
+    let (sender, mut updates) = mpsc::unbounded_channel::<Arc<UserDataUpdate>>();
+    let task = tokio::spawn(async move {
+        while let Some(update) = updates.recv().await {
+            if let UserDataUpdate::OrderTradeUpdate(event) = update.as_ref() {
+                let record = OrderRecord::from_update(event);
+                if let Err(e) = put_order_to_db(&client, &config, &record, &rate).await {
+                    info!(
+                        "Failed to store order update {} for {}: {}",
+                        record.order_id, record.symbol, e
+                    );
+                }
+            }
+        }
+    });
+    (sender, task)
+}
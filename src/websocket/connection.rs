@@ -0,0 +1,529 @@
+use crate::websocket::control::{
+    ControlMethod, ControlRateLimiter, ControlRequest, ControlResponse, MAX_STREAMS_PER_CONNECTION,
+};
+use crate::websocket::feed::Feed;
+use crate::websocket::frame_errors::FrameError;
+use crate::websocket::reconnect::{ReconnectPolicy, Reconnector};
+use crate::websocket::rotation::RotationPolicy;
+use crate::websocket::session::WsSession;
+use futures::{future, StreamExt};
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{self, Duration, Instant};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tracing::info;
+
+/// How often `FrameHandler::on_tick` runs on each connection.
+pub const TICK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long `ConnectionHandle::list_subscriptions` waits for a feed to
+/// answer; a reconnecting feed only reads its commands once it is back.
+pub const LIST_SUBSCRIPTIONS_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum ConnectionCommand {
+    Subscribe(Vec<String>),
+    Unsubscribe(Vec<String>),
+    ListSubscriptions(oneshot::Sender<Vec<String>>),
+}
+
+/// One websocket carrying a partition's symbols. A partition has one feed,
+/// or two when redundant feeds are enabled.
+#[derive(Debug, Clone)]
+pub struct FeedHandle {
+    pub feed: Feed,
+    pub url: String,
+    commands: mpsc::UnboundedSender<ConnectionCommand>,
+    pub reconnector: Reconnector,
+}
+
+/// Registry entry for one partition of subscribed symbols. The symbol set is the
+/// desired state: it is resubscribed in full whenever a feed's connection is
+/// (re)opened, and changed at runtime through `ConnectionCommand`s sent to
+/// every feed.
+#[derive(Debug, Clone)]
+pub struct ConnectionHandle {
+    pub id: usize,
+    symbols: Arc<Mutex<BTreeSet<String>>>,
+    pub feeds: Vec<FeedHandle>,
+}
+
+impl ConnectionHandle {
+    pub fn new(id: usize, symbols: Vec<String>) -> ConnectionHandle {
+        ConnectionHandle {
+            id,
+            symbols: Arc::new(Mutex::new(symbols.into_iter().collect())),
+            feeds: Vec::new(),
+        }
+    }
+
+    /// Registers a feed; the returned receiver drives its connection task.
+    pub fn add_feed(
+        &mut self,
+        feed: Feed,
+        url: String,
+        reconnector: Reconnector,
+    ) -> mpsc::UnboundedReceiver<ConnectionCommand> {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        self.feeds.push(FeedHandle {
+            feed,
+            url,
+            commands,
+            reconnector,
+        });
+        receiver
+    }
+
+    pub fn symbols(&self) -> Vec<String> {
+        self.symbols.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn stream_count(&self) -> usize {
+        self.symbols.lock().unwrap().len()
+    }
+
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.symbols.lock().unwrap().contains(symbol)
+    }
+
+    pub fn add_symbols(&self, symbols: Vec<String>) {
+        let added: Vec<String> = {
+            let mut current = self.symbols.lock().unwrap();
+            symbols
+                .into_iter()
+                .filter(|symbol| current.insert(symbol.clone()))
+                .collect()
+        };
+        if !added.is_empty() {
+            self.broadcast(|| ConnectionCommand::Subscribe(added.clone()));
+        }
+    }
+
+    pub fn remove_symbols(&self, symbols: Vec<String>) {
+        let removed: Vec<String> = {
+            let mut current = self.symbols.lock().unwrap();
+            symbols
+                .into_iter()
+                .filter(|symbol| current.remove(symbol))
+                .collect()
+        };
+        if !removed.is_empty() {
+            self.broadcast(|| ConnectionCommand::Unsubscribe(removed.clone()));
+        }
+    }
+
+    /// Asks Binance which streams this partition is subscribed to on every
+    /// feed at once, using the first feed that answers. Returns `None` when
+    /// every feed is down or none answers within `LIST_SUBSCRIPTIONS_TIMEOUT`.
+    pub async fn list_subscriptions(&self) -> Option<Vec<String>> {
+        let replies: Vec<_> = self
+            .feeds
+            .iter()
+            .filter_map(|feed| {
+                let (sender, receiver) = oneshot::channel();
+                feed.commands
+                    .send(ConnectionCommand::ListSubscriptions(sender))
+                    .ok()
+                    .map(|_| receiver)
+            })
+            .collect();
+        if replies.is_empty() {
+            return None;
+        }
+        match time::timeout(LIST_SUBSCRIPTIONS_TIMEOUT, future::select_ok(replies)).await {
+            Ok(Ok((streams, _))) => Some(streams),
+            _ => None,
+        }
+    }
+
+    fn broadcast(&self, command: impl Fn() -> ConnectionCommand) {
+        for feed in &self.feeds {
+            let _ = feed.commands.send(command());
+        }
+    }
+}
+
+/// State owned by the task driving one connection.
+#[derive(Debug)]
+pub struct ConnectionState {
+    /// Id of the `ConnectionHandle` this task serves.
+    pub connection_id: usize,
+    pub feed: Feed,
+    /// Messages received per symbol since the last rate sample.
+    pub message_counts: HashMap<String, u64>,
+    /// Scratch space for decoders that parse a frame in place.
+    pub scratch: Vec<u8>,
+    /// The handle's desired symbol set; empty when replaying a journal.
+    symbols: Arc<Mutex<BTreeSet<String>>>,
+    /// Maps a symbol in the handle's set to the stream it subscribes.
+    stream_name: fn(&str) -> String,
+    limiter: ControlRateLimiter,
+    next_request_id: u64,
+    pending_lists: HashMap<u64, oneshot::Sender<Vec<String>>>,
+}
+
+impl ConnectionState {
+    pub fn new(connection_id: usize, feed: Feed, stream_name: fn(&str) -> String) -> Self {
+        ConnectionState {
+            connection_id,
+            feed,
+            message_counts: HashMap::new(),
+            scratch: Vec::new(),
+            symbols: Arc::new(Mutex::new(BTreeSet::new())),
+            stream_name,
+            limiter: ControlRateLimiter::default(),
+            next_request_id: 0,
+            pending_lists: HashMap::new(),
+        }
+    }
+
+    /// Tracks the symbol set of `handle`, which this task serves.
+    pub fn with_handle(mut self, handle: &ConnectionHandle) -> Self {
+        self.symbols = handle.symbols.clone();
+        self
+    }
+
+    /// Symbols this connection should carry.
+    pub fn symbols(&self) -> Vec<String> {
+        self.symbols.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Subscribes `session` to every symbol in the handle's desired set.
+    pub async fn subscribe_all(&mut self, session: &mut WsSession, handle: &ConnectionHandle) {
+        let symbols = handle.symbols();
+        self.send(session, ControlMethod::Subscribe, &symbols).await;
+    }
+
+    /// Drops outstanding `LIST_SUBSCRIPTIONS` requests of a lost session, so
+    /// their callers see the reply channel close instead of waiting forever.
+    pub fn end_session(&mut self) {
+        self.pending_lists.clear();
+    }
+
+    pub async fn execute(&mut self, session: &mut WsSession, command: ConnectionCommand) {
+        match command {
+            ConnectionCommand::Subscribe(symbols) => {
+                self.send(session, ControlMethod::Subscribe, &symbols).await;
+            }
+            ConnectionCommand::Unsubscribe(symbols) => {
+                self.send(session, ControlMethod::Unsubscribe, &symbols)
+                    .await;
+            }
+            ConnectionCommand::ListSubscriptions(reply) => {
+                if let Some(id) = self
+                    .send(session, ControlMethod::ListSubscriptions, &[])
+                    .await
+                {
+                    self.pending_lists.insert(id, reply);
+                }
+            }
+        }
+    }
+
+    /// Applies the reply to a control request, e.g. `{"result":null,"id":3}`.
+    pub fn handle_response(&mut self, response: ControlResponse) -> Result<(), FrameError> {
+        if let Some(error) = response.error {
+            if let Some(id) = response.id {
+                self.pending_lists.remove(&id);
+            }
+            return Err(FrameError::ControlRejected {
+                id: response.id,
+                code: error.code,
+                msg: error.msg,
+            });
+        }
+        let Some(reply) = response.id.and_then(|id| self.pending_lists.remove(&id)) else {
+            return Ok(());
+        };
+        let streams = match response.result {
+            Some(serde_json::Value::Array(streams)) => streams
+                .into_iter()
+                .filter_map(|stream| stream.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let _ = reply.send(streams);
+        Ok(())
+    }
+
+    /// Sends one control message per `MAX_STREAMS_PER_CONNECTION` symbols,
+    /// rate limited. Returns the id of the last message sent.
+    async fn send(
+        &mut self,
+        session: &mut WsSession,
+        method: ControlMethod,
+        symbols: &[String],
+    ) -> Option<u64> {
+        let streams: Vec<String> = symbols
+            .iter()
+            .map(|symbol| (self.stream_name)(symbol))
+            .collect();
+        let chunks: Vec<&[String]> = if streams.is_empty() {
+            if method != ControlMethod::ListSubscriptions {
+                return None;
+            }
+            vec![&[]]
+        } else {
+            streams.chunks(MAX_STREAMS_PER_CONNECTION).collect()
+        };
+        let mut last_id = None;
+        for params in chunks {
+            self.limiter.acquire().await;
+            self.next_request_id += 1;
+            let request = ControlRequest {
+                method,
+                params,
+                id: self.next_request_id,
+            };
+            if let Err(e) = session.send_control(&request).await {
+                info!("Failed to send {:?}: {}", method, e);
+                return None;
+            }
+            last_id = Some(self.next_request_id);
+        }
+        last_id
+    }
+}
+
+/// A connection task ended. They loop forever, so this means it panicked.
+#[derive(Error, Debug, Clone)]
+#[error("{name} connection {connection}{feed} task failed: {reason}")]
+pub struct ConnectionTaskError {
+    pub name: String,
+    pub connection: usize,
+    pub feed: Feed,
+    pub reason: String,
+}
+
+/// Receives the text frames of a combined-stream connection.
+pub trait FrameHandler: Clone + Send + Sync + 'static {
+    /// Handles one text frame. Replies to control requests should be passed
+    /// to `ConnectionState::handle_response`.
+    fn handle_text(
+        &self,
+        text: &str,
+        state: &mut ConnectionState,
+    ) -> impl Future<Output = ()> + Send;
+
+    /// Called every `TICK_INTERVAL` with the time since the last call.
+    fn on_tick(&self, _state: &mut ConnectionState, _period: Duration) {}
+
+    /// Called once a new connection is subscribed, before any of its frames.
+    /// Frames may have been missed since the previous connection; a rotation
+    /// overlaps the old connection, so it does not call this.
+    fn on_connected(&self, _state: &mut ConnectionState) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+/// Runs combined-stream connections for one kind of market data: one task
+/// per feed, each keeping the handle's symbols subscribed, applying runtime
+/// subscription changes and replacing its connection every
+/// `rotation.interval` so Binance's 24-hour cutoff never leaves a gap.
+#[derive(Debug, Clone)]
+pub struct ConnectionDriver<H> {
+    /// Used in log messages and reconnector names, e.g. "Book Ticker".
+    pub name: String,
+    pub stream_name: fn(&str) -> String,
+    /// Endpoint of every feed; a second entry runs redundant connections.
+    pub feeds: Vec<(Feed, String)>,
+    pub reconnect: ReconnectPolicy,
+    pub rotation: RotationPolicy,
+    pub handler: H,
+    /// Told when a connection task fails; failures are logged either way.
+    pub failures: Option<mpsc::UnboundedSender<ConnectionTaskError>>,
+}
+
+impl<H: FrameHandler> ConnectionDriver<H> {
+    /// Starts connection `id` for `symbols` and returns its handle.
+    pub fn spawn(&self, id: usize, symbols: Vec<String>) -> ConnectionHandle {
+        let mut handle = ConnectionHandle::new(id, symbols);
+        let mut receivers = Vec::new();
+        for (feed, url) in &self.feeds {
+            let reconnector = Reconnector::new(
+                format!("{} Stream connection {}{}", self.name, id, feed),
+                self.reconnect.clone(),
+            );
+            receivers.push(handle.add_feed(*feed, url.clone(), reconnector));
+        }
+        for (index, commands) in receivers.into_iter().enumerate() {
+            let driver = self.clone();
+            let task_handle = handle.clone();
+            let task = tokio::spawn(async move { driver.run(task_handle, index, commands).await });
+            let error = ConnectionTaskError {
+                name: self.name.clone(),
+                connection: id,
+                feed: handle.feeds[index].feed,
+                reason: String::new(),
+            };
+            let failures = self.failures.clone();
+            tokio::spawn(async move {
+                let reason = match task.await {
+                    Ok(()) => "task returned".to_string(),
+                    Err(e) => e.to_string(),
+                };
+                let error = ConnectionTaskError { reason, ..error };
+                info!("{}", error);
+                if let Some(failures) = failures {
+                    let _ = failures.send(error);
+                }
+            });
+        }
+        handle
+    }
+
+    async fn run(
+        &self,
+        handle: ConnectionHandle,
+        feed_index: usize,
+        mut commands: mpsc::UnboundedReceiver<ConnectionCommand>,
+    ) {
+        let feed = &handle.feeds[feed_index];
+        let reconnector = &feed.reconnector;
+        let rotation = &self.rotation;
+        let mut state =
+            ConnectionState::new(handle.id, feed.feed, self.stream_name).with_handle(&handle);
+        let mut tick = time::interval(TICK_INTERVAL);
+        loop {
+            let mut session = self.connect_with_backoff(feed).await;
+            state.subscribe_all(&mut session, &handle).await;
+            self.handler.on_connected(&mut state).await;
+            let mut rotate_at = session.connected_at + rotation.interval;
+            loop {
+                tokio::select! {
+                    _ = tick.tick() => {
+                        self.handler.on_tick(&mut state, tick.period());
+                    }
+                    message = session.read.next() => {
+                        if !self.handle_message(&mut session, message, &mut state).await {
+                            break;
+                        }
+                    }
+                    Some(command) = commands.recv() => {
+                        state.execute(&mut session, command).await;
+                    }
+                    _ = time::sleep_until(rotate_at) => {
+                        let (next, rotated) =
+                            self.rotate(&handle, feed, session, &mut state).await;
+                        session = next;
+                        if rotated {
+                            reconnector.on_connected();
+                            rotate_at = session.connected_at + rotation.interval;
+                        } else {
+                            rotate_at = Instant::now() + rotation.retry_delay;
+                        }
+                    }
+                }
+            }
+            state.end_session();
+            info!(
+                "{} connection {}{} lost, reconnecting...",
+                self.name, handle.id, feed.feed
+            );
+            reconnector
+                .wait_after_disconnect(session.connected_at.elapsed())
+                .await;
+        }
+    }
+
+    async fn connect_with_backoff(&self, feed: &FeedHandle) -> WsSession {
+        loop {
+            match WsSession::connect(&feed.url).await {
+                Ok(session) => {
+                    info!("Listen to {} Stream (feed {})", self.name, feed.feed);
+                    feed.reconnector.on_connected();
+                    return session;
+                }
+                Err(e) => {
+                    info!("Failed to connect: {}, retrying...", e);
+                    feed.reconnector.wait_after_failure().await;
+                }
+            }
+        }
+    }
+
+    /// Opens and subscribes a replacement for `current`, reads both
+    /// connections for `rotation.overlap` and then closes the old one.
+    /// Handlers must tolerate updates seen on both. Returns the session to
+    /// keep reading and whether the rotation happened.
+    async fn rotate(
+        &self,
+        handle: &ConnectionHandle,
+        feed: &FeedHandle,
+        mut current: WsSession,
+        state: &mut ConnectionState,
+    ) -> (WsSession, bool) {
+        info!(
+            "Rotating {} connection {}{}",
+            self.name, handle.id, feed.feed
+        );
+        let mut next = match WsSession::connect(&feed.url).await {
+            Ok(session) => session,
+            Err(e) => {
+                info!("Failed to open replacement connection: {}", e);
+                return (current, false);
+            }
+        };
+        state.subscribe_all(&mut next, handle).await;
+        let overlap_end = Instant::now() + self.rotation.overlap;
+        loop {
+            tokio::select! {
+                message = current.read.next() => {
+                    if !self.handle_message(&mut current, message, state).await {
+                        info!("Old {} connection closed during rotation", self.name);
+                        state.end_session();
+                        return (next, true);
+                    }
+                }
+                message = next.read.next() => {
+                    if !self.handle_message(&mut next, message, state).await {
+                        info!("Replacement {} connection failed during rotation", self.name);
+                        next.close().await;
+                        return (current, false);
+                    }
+                }
+                _ = time::sleep_until(overlap_end) => break,
+            }
+        }
+        current.close().await;
+        // Requests are only sent on `current`, so anything unanswered by now
+        // never will be.
+        state.end_session();
+        info!(
+            "{} connection {}{} rotated",
+            self.name, handle.id, feed.feed
+        );
+        (next, true)
+    }
+
+    /// Handles one frame. Returns `false` once the connection has ended.
+    async fn handle_message(
+        &self,
+        session: &mut WsSession,
+        message: Option<Result<Message, tungstenite::Error>>,
+        state: &mut ConnectionState,
+    ) -> bool {
+        match message {
+            Some(Ok(Message::Text(text))) => {
+                self.handler.handle_text(&text, state).await;
+                true
+            }
+            Some(Ok(Message::Ping(payload))) => {
+                session.pong(payload).await;
+                true
+            }
+            Some(Ok(non_text_message)) => {
+                info!("Received Non Text Messages {:?}", non_text_message);
+                true
+            }
+            Some(Err(e)) => {
+                info!("Error Message {}", e);
+                false
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::{self, Duration, Instant};
+
+/// Binance accepts at most this many streams on one connection.
+pub const MAX_STREAMS_PER_CONNECTION: usize = 200;
+/// Binance accepts at most this many incoming messages per second on one
+/// connection before disconnecting it.
+pub const MAX_CONTROL_MESSAGES_PER_SECOND: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ControlMethod {
+    Subscribe,
+    Unsubscribe,
+    ListSubscriptions,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ControlRequest<'a> {
+    pub method: ControlMethod,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    pub params: &'a [String],
+    pub id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ControlError {
+    pub code: i64,
+    pub msg: String,
+}
+
+/// Reply to a `ControlRequest`, e.g. `{"result":null,"id":1}`.
+#[derive(Debug, Deserialize)]
+pub struct ControlResponse {
+    pub id: Option<u64>,
+    #[serde(default)]
+    pub result: Option<Value>,
+    #[serde(default)]
+    pub error: Option<ControlError>,
+}
+
+/// Spaces out control messages so a connection stays under
+/// `MAX_CONTROL_MESSAGES_PER_SECOND`, keeping headroom for pongs.
+#[derive(Debug)]
+pub struct ControlRateLimiter {
+    min_interval: Duration,
+    next_allowed: Instant,
+}
+
+impl Default for ControlRateLimiter {
+    fn default() -> Self {
+        ControlRateLimiter::new(MAX_CONTROL_MESSAGES_PER_SECOND - 2)
+    }
+}
+
+impl ControlRateLimiter {
+    pub fn new(messages_per_second: u32) -> Self {
+        ControlRateLimiter {
+            min_interval: Duration::from_secs(1) / messages_per_second.max(1),
+            next_allowed: Instant::now(),
+        }
+    }
+
+    pub async fn acquire(&mut self) {
+        let now = Instant::now();
+        if self.next_allowed > now {
+            time::sleep_until(self.next_allowed).await;
+        }
+        self.next_allowed = self.next_allowed.max(now) + self.min_interval;
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// One of the redundant connections carrying a partition's symbols.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Feed {
+    #[default]
+    A,
+    B,
+}
+
+impl fmt::Display for Feed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Feed::A => write!(f, "A"),
+            Feed::B => write!(f, "B"),
+        }
+    }
+}
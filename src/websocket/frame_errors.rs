@@ -0,0 +1,279 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::time::{Duration, Instant};
+use tracing::info;
+
+/// Why a text frame could not be applied. None of these end the stream.
+#[derive(Error, Debug)]
+pub enum FrameError {
+    #[error("malformed frame: {0}")]
+    Malformed(String),
+    #[error("no handler for stream {0}")]
+    Unrouted(String),
+    #[error("invalid {field} {value:?} for {symbol}")]
+    InvalidNumber {
+        symbol: String,
+        field: &'static str,
+        value: String,
+    },
+    #[error("control request {id:?} rejected: {code} {msg}")]
+    ControlRejected {
+        id: Option<u64>,
+        code: i64,
+        msg: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameErrorKind {
+    Malformed,
+    Unrouted,
+    InvalidNumber,
+    ControlRejected,
+}
+
+impl FrameErrorKind {
+    const ALL: [FrameErrorKind; 4] = [
+        FrameErrorKind::Malformed,
+        FrameErrorKind::Unrouted,
+        FrameErrorKind::InvalidNumber,
+        FrameErrorKind::ControlRejected,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl FrameError {
+    pub fn kind(&self) -> FrameErrorKind {
+        match self {
+            FrameError::Malformed(_) => FrameErrorKind::Malformed,
+            FrameError::Unrouted(_) => FrameErrorKind::Unrouted,
+            FrameError::InvalidNumber { .. } => FrameErrorKind::InvalidNumber,
+            FrameError::ControlRejected { .. } => FrameErrorKind::ControlRejected,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameMetricsSnapshot {
+    /// Successful replies to `SUBSCRIBE`/`UNSUBSCRIBE`/`LIST_SUBSCRIPTIONS`.
+    pub control_acks: u64,
+    pub control_rejected: u64,
+    pub malformed: u64,
+    pub unrouted: u64,
+    pub invalid_number: u64,
+}
+
+#[derive(Debug)]
+struct SampleWindow {
+    next_log_at: Option<Instant>,
+    suppressed: u64,
+}
+
+#[derive(Debug)]
+struct FrameErrorCounters {
+    control_acks: AtomicU64,
+    errors: [AtomicU64; 4],
+    windows: Mutex<Vec<SampleWindow>>,
+}
+
+/// Counts frame errors and logs a sample of them: at most one line per
+/// error kind every `log_interval`, noting how many were suppressed, so a
+/// flood of bad frames cannot flood the logs. Clones share counters.
+#[derive(Debug, Clone)]
+pub struct FrameErrorReporter {
+    name: String,
+    log_interval: Duration,
+    counters: Arc<FrameErrorCounters>,
+}
+
+impl FrameErrorReporter {
+    pub fn new(name: impl Into<String>, log_interval: Duration) -> Self {
+        let windows = FrameErrorKind::ALL
+            .iter()
+            .map(|_| SampleWindow {
+                next_log_at: None,
+                suppressed: 0,
+            })
+            .collect();
+        FrameErrorReporter {
+            name: name.into(),
+            log_interval,
+            counters: Arc::new(FrameErrorCounters {
+                control_acks: AtomicU64::new(0),
+                errors: Default::default(),
+                windows: Mutex::new(windows),
+            }),
+        }
+    }
+
+    pub fn record_ack(&self) {
+        self.counters.control_acks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record(&self, error: &FrameError) {
+        let kind = error.kind();
+        self.counters.errors[kind.index()].fetch_add(1, Ordering::Relaxed);
+        let Some(suppressed) = self.sample(kind) else {
+            return;
+        };
+        if suppressed > 0 {
+            info!(
+                "{}: {} ({} similar errors suppressed)",
+                self.name, error, suppressed
+            );
+        } else {
+            info!("{}: {}", self.name, error);
+        }
+    }
+
+    /// Returns how many errors of `kind` were suppressed since the last
+    /// logged one when this one should be logged, or `None` to suppress it.
+    fn sample(&self, kind: FrameErrorKind) -> Option<u64> {
+        let mut windows = self.counters.windows.lock().unwrap();
+        let window = &mut windows[kind.index()];
+        let now = Instant::now();
+        if window.next_log_at.is_some_and(|next| now < next) {
+            window.suppressed += 1;
+            return None;
+        }
+        window.next_log_at = Some(now + self.log_interval);
+        Some(std::mem::take(&mut window.suppressed))
+    }
+
+    pub fn snapshot(&self) -> FrameMetricsSnapshot {
+        let errors = &self.counters.errors;
+        let count = |kind: FrameErrorKind| errors[kind.index()].load(Ordering::Relaxed);
+        FrameMetricsSnapshot {
+            control_acks: self.counters.control_acks.load(Ordering::Relaxed),
+            control_rejected: count(FrameErrorKind::ControlRejected),
+            malformed: count(FrameErrorKind::Malformed),
+            unrouted: count(FrameErrorKind::Unrouted),
+            invalid_number: count(FrameErrorKind::InvalidNumber),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::connection::ConnectionState;
+    use crate::websocket::feed::Feed;
+    use crate::websocket::router::{Routed, StreamRouter};
+    use tokio::time;
+
+    fn invalid_number() -> FrameError {
+        FrameError::InvalidNumber {
+            symbol: "BTCUSDT".to_string(),
+            field: "bid",
+            value: "n/a".to_string(),
+        }
+    }
+
+    #[test]
+    fn classifies_errors_by_kind() {
+        let errors = [
+            (
+                FrameError::Malformed("x".to_string()),
+                FrameErrorKind::Malformed,
+            ),
+            (
+                FrameError::Unrouted("x".to_string()),
+                FrameErrorKind::Unrouted,
+            ),
+            (invalid_number(), FrameErrorKind::InvalidNumber),
+            (
+                FrameError::ControlRejected {
+                    id: Some(1),
+                    code: 2,
+                    msg: "Invalid request".to_string(),
+                },
+                FrameErrorKind::ControlRejected,
+            ),
+        ];
+        for (error, kind) in errors {
+            assert_eq!(error.kind(), kind);
+        }
+        assert_eq!(
+            invalid_number().to_string(),
+            r#"invalid bid "n/a" for BTCUSDT"#
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn counts_each_kind_separately() {
+        let reporter = FrameErrorReporter::new("test", Duration::from_secs(10));
+        let clone = reporter.clone();
+        for _ in 0..3 {
+            reporter.record(&FrameError::Malformed("x".to_string()));
+        }
+        clone.record(&FrameError::Unrouted("x".to_string()));
+        clone.record(&invalid_number());
+        clone.record_ack();
+        let snapshot = reporter.snapshot();
+        assert_eq!(
+            (
+                snapshot.malformed,
+                snapshot.unrouted,
+                snapshot.invalid_number,
+                snapshot.control_rejected,
+                snapshot.control_acks,
+            ),
+            (3, 1, 1, 0, 1)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn logs_one_error_per_kind_each_interval() {
+        let reporter = FrameErrorReporter::new("test", Duration::from_secs(10));
+        assert_eq!(reporter.sample(FrameErrorKind::Malformed), Some(0));
+        assert_eq!(reporter.sample(FrameErrorKind::Malformed), None);
+        assert_eq!(reporter.sample(FrameErrorKind::Malformed), None);
+        // Other kinds have their own window.
+        assert_eq!(reporter.sample(FrameErrorKind::Unrouted), Some(0));
+
+        time::advance(Duration::from_secs(9)).await;
+        assert_eq!(reporter.sample(FrameErrorKind::Malformed), None);
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(reporter.sample(FrameErrorKind::Malformed), Some(3));
+        assert_eq!(reporter.sample(FrameErrorKind::Malformed), None);
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(reporter.sample(FrameErrorKind::Malformed), Some(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn separates_acknowledged_and_rejected_control_frames() {
+        let router: StreamRouter<()> = StreamRouter::new();
+        let mut state = ConnectionState::new(0, Feed::A, |symbol| format!("{}@bookTicker", symbol));
+        let reporter = FrameErrorReporter::new("test", Duration::from_secs(10));
+        for text in [
+            r#"{"result":null,"id":1}"#,
+            r#"{"error":{"code":2,"msg":"Invalid request"},"id":2}"#,
+            r#"{"result":null,"id":3}"#,
+        ] {
+            let Routed::Control(response) = router.route(text) else {
+                panic!("{} is not a control frame", text);
+            };
+            match state.handle_response(response) {
+                Ok(()) => reporter.record_ack(),
+                Err(e) => {
+                    assert!(matches!(
+                        e,
+                        FrameError::ControlRejected {
+                            id: Some(2),
+                            code: 2,
+                            ..
+                        }
+                    ));
+                    reporter.record(&e);
+                }
+            }
+        }
+        let snapshot = reporter.snapshot();
+        assert_eq!((snapshot.control_acks, snapshot.control_rejected), (2, 1));
+    }
+}
@@ -0,0 +1,8 @@
+pub mod connection;
+pub mod control;
+pub mod feed;
+pub mod frame_errors;
+pub mod reconnect;
+pub mod rotation;
+pub mod router;
+pub mod session;
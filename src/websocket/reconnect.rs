@@ -0,0 +1,286 @@
+use crate::clock::current_time_millis;
+use rand::Rng;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time;
+use tracing::info;
+
+/// How a websocket client waits between reconnect attempts.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    /// Fraction of each delay that is randomised, between 0.0 and 1.0.
+    pub jitter: f64,
+    /// Consecutive failures after which the circuit opens.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a single trial connection.
+    pub open_duration: Duration,
+    /// Sessions shorter than this count as failures when they drop, so a
+    /// connection that is accepted and then closed straight away backs off too.
+    pub stable_after: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.5,
+            failure_threshold: 10,
+            open_duration: Duration::from_secs(300),
+            stable_after: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before the attempt following `failures` consecutive failures.
+    pub fn backoff_delay(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(63) as i32;
+        let delay = self.initial_delay.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+        let delay = delay.min(self.max_delay.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 - jitter * rand::rng().random::<f64>();
+        Duration::from_secs_f64(delay * factor)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CircuitState {
+    /// Connecting normally, backing off after failures.
+    Closed,
+    /// Too many consecutive failures; waiting `open_duration` before trying again.
+    Open,
+    /// Cooldown elapsed; the next attempt decides whether the circuit closes.
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconnectStatus {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub total_failures: u64,
+    pub total_connects: u64,
+    pub last_delay_ms: u64,
+    /// Epoch milliseconds of the last successful connect.
+    pub last_connected_at: Option<u64>,
+}
+
+impl Default for ReconnectStatus {
+    fn default() -> Self {
+        ReconnectStatus {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            total_failures: 0,
+            total_connects: 0,
+            last_delay_ms: 0,
+            last_connected_at: None,
+        }
+    }
+}
+
+/// Tracks reconnect attempts for one connection. Clones share state, so the
+/// status can be read from health checks while the connection task runs.
+#[derive(Debug, Clone)]
+pub struct Reconnector {
+    name: String,
+    policy: ReconnectPolicy,
+    status: Arc<Mutex<ReconnectStatus>>,
+}
+
+impl Reconnector {
+    pub fn new(name: impl Into<String>, policy: ReconnectPolicy) -> Self {
+        Reconnector {
+            name: name.into(),
+            policy,
+            status: Arc::new(Mutex::new(ReconnectStatus::default())),
+        }
+    }
+
+    pub fn status(&self) -> ReconnectStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn on_connected(&self) {
+        let mut status = self.status.lock().unwrap();
+        if status.state != CircuitState::Closed {
+            info!("{} connected, circuit closed", self.name);
+        }
+        status.state = CircuitState::Closed;
+        status.total_connects += 1;
+        status.last_connected_at = Some(current_time_millis());
+    }
+
+    /// Waits before the next attempt after a failed connect.
+    pub async fn wait_after_failure(&self) {
+        let delay = self.on_failure();
+        time::sleep(delay).await;
+        let mut status = self.status.lock().unwrap();
+        if status.state == CircuitState::Open {
+            info!("{} circuit half-open, trying one connection", self.name);
+            status.state = CircuitState::HalfOpen;
+        }
+    }
+
+    /// Waits before reconnecting after an established session dropped.
+    /// Only sessions that lasted `stable_after` reset the failure count;
+    /// shorter ones count as failures.
+    pub async fn wait_after_disconnect(&self, session: Duration) {
+        if session >= self.policy.stable_after {
+            self.status.lock().unwrap().consecutive_failures = 0;
+            return;
+        }
+        self.wait_after_failure().await;
+    }
+
+    fn on_failure(&self) -> Duration {
+        let mut status = self.status.lock().unwrap();
+        status.consecutive_failures += 1;
+        status.total_failures += 1;
+        let trip = status.state == CircuitState::HalfOpen
+            || status.consecutive_failures >= self.policy.failure_threshold;
+        let delay = if trip {
+            if status.state != CircuitState::Open {
+                info!(
+                    "{} circuit open after {} consecutive failures, pausing for {:?}",
+                    self.name, status.consecutive_failures, self.policy.open_duration
+                );
+            }
+            status.state = CircuitState::Open;
+            self.policy.open_duration
+        } else {
+            self.policy.backoff_delay(status.consecutive_failures)
+        };
+        status.last_delay_ms = delay.as_millis() as u64;
+        delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            multiplier: 2.0,
+            jitter: 0.0,
+            failure_threshold: 3,
+            open_duration: Duration::from_secs(10),
+            stable_after: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_max_delay() {
+        let policy = policy();
+        let delays: Vec<_> = (1..=6)
+            .map(|failures| policy.backoff_delay(failures))
+            .collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis)
+        );
+        assert_eq!(policy.backoff_delay(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff_delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jitter_only_shortens_the_delay_by_its_fraction() {
+        let jittered = ReconnectPolicy {
+            jitter: 0.5,
+            ..policy()
+        };
+        for _ in 0..1000 {
+            let delay = jittered.backoff_delay(3);
+            assert!(delay > Duration::from_millis(200), "{:?}", delay);
+            assert!(delay <= Duration::from_millis(400), "{:?}", delay);
+            let capped = jittered.backoff_delay(20);
+            assert!(capped > Duration::from_millis(500), "{:?}", capped);
+            assert!(capped <= Duration::from_secs(1), "{:?}", capped);
+        }
+        // Out of range jitter is clamped rather than producing negative delays.
+        let wild = ReconnectPolicy {
+            jitter: 3.0,
+            ..policy()
+        };
+        assert!(wild.backoff_delay(2) <= Duration::from_millis(200));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn opens_the_circuit_and_closes_it_after_a_successful_trial() {
+        let reconnector = Reconnector::new("test", policy());
+        for failures in 1..=2 {
+            reconnector.wait_after_failure().await;
+            let status = reconnector.status();
+            assert_eq!(status.state, CircuitState::Closed);
+            assert_eq!(status.consecutive_failures, failures);
+        }
+
+        let started = time::Instant::now();
+        let trial = tokio::spawn({
+            let reconnector = reconnector.clone();
+            async move { reconnector.wait_after_failure().await }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(reconnector.status().state, CircuitState::Open);
+        assert_eq!(reconnector.status().last_delay_ms, 10_000);
+        trial.await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(10));
+        assert_eq!(reconnector.status().state, CircuitState::HalfOpen);
+
+        reconnector.on_connected();
+        let status = reconnector.status();
+        assert_eq!(status.state, CircuitState::Closed);
+        assert_eq!(status.total_connects, 1);
+        assert_eq!(status.total_failures, 3);
+        assert!(status.last_connected_at.is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_failed_trial_reopens_the_circuit() {
+        let reconnector = Reconnector::new("test", policy());
+        for _ in 0..3 {
+            reconnector.wait_after_failure().await;
+        }
+        assert_eq!(reconnector.status().state, CircuitState::HalfOpen);
+        let started = time::Instant::now();
+        reconnector.wait_after_failure().await;
+        assert_eq!(started.elapsed(), Duration::from_secs(10));
+        assert_eq!(reconnector.status().state, CircuitState::HalfOpen);
+        assert_eq!(reconnector.status().consecutive_failures, 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_stable_sessions_reset_the_failure_count() {
+        let reconnector = Reconnector::new("test", policy());
+        reconnector.wait_after_failure().await;
+        reconnector.on_connected();
+
+        // A session dropped before `stable_after` backs off like a failure.
+        let started = time::Instant::now();
+        reconnector
+            .wait_after_disconnect(Duration::from_secs(1))
+            .await;
+        assert_eq!(started.elapsed(), Duration::from_millis(200));
+        assert_eq!(reconnector.status().consecutive_failures, 2);
+
+        let started = time::Instant::now();
+        reconnector
+            .wait_after_disconnect(Duration::from_secs(5))
+            .await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert_eq!(reconnector.status().consecutive_failures, 0);
+        assert_eq!(reconnector.status().total_failures, 2);
+
+        // The next failure starts over from the initial delay.
+        let started = time::Instant::now();
+        reconnector.wait_after_failure().await;
+        assert_eq!(started.elapsed(), Duration::from_millis(100));
+    }
+}
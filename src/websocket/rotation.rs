@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+/// When to replace a long-lived connection before the exchange drops it.
+/// Binance closes every stream connection after 24 hours.
+#[derive(Debug, Clone)]
+pub struct RotationPolicy {
+    /// Connection age at which a replacement is opened.
+    pub interval: Duration,
+    /// How long the old and new connections are read together before the
+    /// old one is closed.
+    pub overlap: Duration,
+    /// Wait before trying again when the replacement fails to connect.
+    pub retry_delay: Duration,
+}
+
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        RotationPolicy {
+            interval: Duration::from_secs(23 * 3600),
+            overlap: Duration::from_secs(10),
+            retry_delay: Duration::from_secs(60),
+        }
+    }
+}
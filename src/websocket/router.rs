@@ -0,0 +1,297 @@
+use crate::websocket::control::ControlResponse;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Combined-stream frame with the payload left undecoded until routed.
+#[derive(Deserialize)]
+struct RawFrame<'a> {
+    stream: &'a str,
+    #[serde(borrow)]
+    data: &'a RawValue,
+}
+
+/// Outcome of routing one text frame.
+#[derive(Debug)]
+pub enum Routed<M> {
+    Message(M),
+    /// Reply to a `SUBSCRIBE`/`UNSUBSCRIBE`/`LIST_SUBSCRIPTIONS` request.
+    Control(ControlResponse),
+    /// A stream no handler is registered for.
+    Unrouted(String),
+    /// Not JSON, or a payload its handler could not decode.
+    Malformed(String),
+}
+
+type Handler<M> = Box<dyn Fn(&RawValue) -> Result<M, serde_json::Error> + Send + Sync>;
+
+struct Route<M> {
+    pattern: String,
+    handler: Handler<M>,
+}
+
+impl<M> Route<M> {
+    /// Patterns match the stream kind, i.e. the name without the leading
+    /// `<symbol>@` (`bookTicker`, `depth@100ms`), or the full name of
+    /// all-market streams (`!markPrice@arr@1s`). A trailing `*` matches any
+    /// suffix, e.g. `kline_*`.
+    fn matches(&self, stream: &str) -> bool {
+        let kind = if stream.starts_with('!') {
+            stream
+        } else {
+            stream.split_once('@').map_or(stream, |(_, kind)| kind)
+        };
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => kind.starts_with(prefix),
+            None => kind == self.pattern,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RouterCounters {
+    routed: AtomicU64,
+    control: AtomicU64,
+    unrouted: AtomicU64,
+    malformed: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouterStatsSnapshot {
+    pub routed: u64,
+    pub control: u64,
+    pub unrouted: u64,
+    pub malformed: u64,
+}
+
+/// Dispatches combined-stream frames by their `stream` field to typed
+/// handlers, each mapping its payload into the caller's message type `M`.
+/// Frames that match no handler or fail to decode are counted instead of
+/// ending the connection. Clones share routes and counters.
+pub struct StreamRouter<M> {
+    routes: Arc<Vec<Route<M>>>,
+    counters: Arc<RouterCounters>,
+}
+
+impl<M> Clone for StreamRouter<M> {
+    fn clone(&self) -> Self {
+        StreamRouter {
+            routes: self.routes.clone(),
+            counters: self.counters.clone(),
+        }
+    }
+}
+
+impl<M> fmt::Debug for StreamRouter<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let patterns: Vec<&str> = self.routes.iter().map(|r| r.pattern.as_str()).collect();
+        f.debug_struct("StreamRouter")
+            .field("routes", &patterns)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl<M> Default for StreamRouter<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> StreamRouter<M> {
+    pub fn new() -> Self {
+        StreamRouter {
+            routes: Arc::new(Vec::new()),
+            counters: Arc::new(RouterCounters::default()),
+        }
+    }
+
+    /// Registers `handler` for streams matching `pattern`; the first
+    /// matching route wins. Routes are added before the router is cloned.
+    pub fn on<T, F>(mut self, pattern: &str, handler: F) -> Self
+    where
+        T: DeserializeOwned,
+        F: Fn(T) -> M + Send + Sync + 'static,
+    {
+        let route = Route {
+            pattern: pattern.to_string(),
+            handler: Box::new(move |data| serde_json::from_str(data.get()).map(&handler)),
+        };
+        Arc::get_mut(&mut self.routes)
+            .expect("routes are registered before the router is shared")
+            .push(route);
+        self
+    }
+
+    pub fn route(&self, text: &str) -> Routed<M> {
+        let frame: RawFrame = match serde_json::from_str(text) {
+            Ok(frame) => frame,
+            Err(frame_error) => {
+                return match serde_json::from_str::<ControlResponse>(text) {
+                    Ok(response) if response.id.is_some() || response.error.is_some() => {
+                        self.counters.control.fetch_add(1, Ordering::Relaxed);
+                        Routed::Control(response)
+                    }
+                    _ => self.malformed(format!("{}: {}", frame_error, text)),
+                };
+            }
+        };
+        let Some(route) = self.routes.iter().find(|route| route.matches(frame.stream)) else {
+            self.counters.unrouted.fetch_add(1, Ordering::Relaxed);
+            return Routed::Unrouted(frame.stream.to_string());
+        };
+        match (route.handler)(frame.data) {
+            Ok(message) => {
+                self.counters.routed.fetch_add(1, Ordering::Relaxed);
+                Routed::Message(message)
+            }
+            Err(e) => self.malformed(format!("{} payload: {}", frame.stream, e)),
+        }
+    }
+
+    pub fn stats(&self) -> RouterStatsSnapshot {
+        RouterStatsSnapshot {
+            routed: self.counters.routed.load(Ordering::Relaxed),
+            control: self.counters.control.load(Ordering::Relaxed),
+            unrouted: self.counters.unrouted.load(Ordering::Relaxed),
+            malformed: self.counters.malformed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn malformed(&self, reason: String) -> Routed<M> {
+        self.counters.malformed.fetch_add(1, Ordering::Relaxed);
+        Routed::Malformed(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Payload {
+        s: String,
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Message {
+        Ticker(String),
+        Kline(String),
+        Mark(String),
+    }
+
+    fn router() -> StreamRouter<Message> {
+        StreamRouter::new()
+            .on("bookTicker", |payload: Payload| Message::Ticker(payload.s))
+            .on("kline_*", |payload: Payload| Message::Kline(payload.s))
+            .on("!markPrice@arr", |payload: Payload| {
+                Message::Mark(payload.s)
+            })
+    }
+
+    fn frame(stream: &str) -> String {
+        format!(r#"{{"stream":"{}","data":{{"s":"BTCUSDT"}}}}"#, stream)
+    }
+
+    fn message(routed: Routed<Message>) -> Option<Message> {
+        match routed {
+            Routed::Message(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn routes_by_stream_kind_and_wildcard_suffix() {
+        let router = router();
+        let ticker = Message::Ticker("BTCUSDT".to_string());
+        assert_eq!(
+            message(router.route(&frame("btcusdt@bookTicker"))),
+            Some(ticker)
+        );
+        for stream in ["btcusdt@kline_1m", "ethusdt@kline_1h", "btcusdt@kline_"] {
+            assert_eq!(
+                message(router.route(&frame(stream))),
+                Some(Message::Kline("BTCUSDT".to_string())),
+                "{}",
+                stream
+            );
+        }
+        assert_eq!(
+            message(router.route(&frame("!markPrice@arr"))),
+            Some(Message::Mark("BTCUSDT".to_string()))
+        );
+        assert_eq!(router.stats().routed, 5);
+    }
+
+    #[test]
+    fn the_first_matching_route_wins() {
+        let router = StreamRouter::new()
+            .on("kline_1m", |payload: Payload| Message::Ticker(payload.s))
+            .on("kline_*", |payload: Payload| Message::Kline(payload.s));
+        assert!(matches!(
+            router.route(&frame("btcusdt@kline_1m")),
+            Routed::Message(Message::Ticker(_))
+        ));
+        assert!(matches!(
+            router.route(&frame("btcusdt@kline_5m")),
+            Routed::Message(Message::Kline(_))
+        ));
+    }
+
+    #[test]
+    fn counts_streams_without_a_route() {
+        let router = router();
+        for stream in [
+            "btcusdt@bookTickerX",
+            "btcusdt@kline",
+            "btcusdt@depth@100ms",
+            "!markPrice@arr@1s",
+        ] {
+            match router.route(&frame(stream)) {
+                Routed::Unrouted(unrouted) => assert_eq!(unrouted, stream),
+                other => panic!("{} routed as {:?}", stream, other),
+            }
+        }
+        assert_eq!(router.stats().unrouted, 4);
+        assert_eq!(router.stats().routed, 0);
+    }
+
+    #[test]
+    fn separates_control_replies_from_malformed_frames() {
+        let router = router();
+        assert!(matches!(
+            router.route(r#"{"result":null,"id":1}"#),
+            Routed::Control(ControlResponse { id: Some(1), .. })
+        ));
+        match router.route(r#"{"error":{"code":2,"msg":"Invalid request"},"id":null}"#) {
+            Routed::Control(response) => assert_eq!(response.error.unwrap().code, 2),
+            other => panic!("expected a control reply, got {:?}", other),
+        }
+        for text in [
+            "not json",
+            r#"{"result":null}"#,
+            r#"{"stream":"btcusdt@bookTicker","data":{"x":1}}"#,
+        ] {
+            assert!(
+                matches!(router.route(text), Routed::Malformed(_)),
+                "{}",
+                text
+            );
+        }
+        let stats = router.stats();
+        assert_eq!((stats.control, stats.malformed, stats.routed), (2, 3, 0));
+    }
+
+    #[test]
+    fn clones_share_counters() {
+        let router = router();
+        let clone = router.clone();
+        clone.route(&frame("btcusdt@bookTicker"));
+        clone.route("not json");
+        let stats = router.stats();
+        assert_eq!((stats.routed, stats.malformed), (1, 1));
+    }
+}
@@ -0,0 +1,49 @@
+use crate::websocket::control::ControlRequest;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tracing::info;
+
+pub type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// One open websocket connection, split into its write and read halves.
+pub struct WsSession {
+    pub write: SplitSink<WsStream, Message>,
+    pub read: SplitStream<WsStream>,
+    pub connected_at: Instant,
+}
+
+impl WsSession {
+    pub async fn connect(url: &str) -> Result<WsSession, tungstenite::Error> {
+        let (ws_stream, _) = connect_async(url).await?;
+        let (write, read) = ws_stream.split();
+        Ok(WsSession {
+            write,
+            read,
+            connected_at: Instant::now(),
+        })
+    }
+
+    pub async fn pong(&mut self, payload: Vec<u8>) {
+        if let Err(e) = self.write.send(Message::Pong(payload)).await {
+            info!("Failed to send Pong response: {}", e);
+        }
+    }
+
+    pub async fn send_control(
+        &mut self,
+        request: &ControlRequest<'_>,
+    ) -> Result<(), tungstenite::Error> {
+        let text = serde_json::to_string(request).unwrap_or_default();
+        self.write.send(Message::Text(text)).await
+    }
+
+    /// Sends a close frame; errors are ignored since the connection is being
+    /// discarded anyway.
+    pub async fn close(mut self) {
+        let _ = self.write.close().await;
+    }
+}